@@ -1,8 +1,16 @@
 use {
-    anyhow::Result,
-    axum::{routing::get, Router},
-    rtmp::{channels::define, channels::define::ChannelEventProducer},
+    anyhow::{anyhow, Result},
+    axum::{
+        extract::Path,
+        routing::{get, post},
+        Json, Router,
+    },
+    rtmp::{
+        channels::{define, define::ChannelEventProducer, errors::ChannelError},
+        file_source::define::FileSource,
+    },
     std::sync::Arc,
+    uuid::Uuid,
     {
         tokio,
         tokio::sync::{mpsc, oneshot},
@@ -16,7 +24,12 @@ struct ApiService {
 impl ApiService {
     async fn root(&self) -> String {
         String::from(
-            "Usage of gms http api:\n  ./get_stream_status  get audio and video stream statistic information.\n",
+            "Usage of gms http api:\n  ./get_stream_status  get audio and video stream statistic information.\n  \
+             POST ./kick_publisher/{app_name}/{stream_name}  cut off the publisher of a stream.\n  \
+             POST ./kick_subscriber/{id}  cut off a subscriber by its id from the statistics.\n  \
+             ./file_sources  list the flv files being published.\n  \
+             POST ./start_file_source  publish an flv file of the file sources directory, the json body has path, app_name, stream_name and loop.\n  \
+             POST ./stop_file_source/{app_name}/{stream_name}  stop publishing an flv file.\n",
         )
     }
     async fn get_stream_status(&self) -> Result<String> {
@@ -51,6 +64,63 @@ impl ApiService {
 
         Ok(String::from(""))
     }
+
+    async fn kick_publisher(&self, app_name: String, stream_name: String) -> Result<String> {
+        let (responder, receiver) = oneshot::channel();
+        let channel_event = define::ChannelEvent::KickPublisher {
+            app_name,
+            stream_name,
+            responder,
+        };
+        self.send_event(channel_event, receiver).await
+    }
+
+    async fn kick_subscriber(&self, id: String) -> Result<String> {
+        let id = Uuid::parse_str(&id)?;
+        let (responder, receiver) = oneshot::channel();
+        let channel_event = define::ChannelEvent::KickSubscriber { id, responder };
+        self.send_event(channel_event, receiver).await
+    }
+
+    async fn get_file_sources(&self) -> Result<String> {
+        let (responder, receiver) = oneshot::channel();
+        let channel_event = define::ChannelEvent::ListFileSources { responder };
+        if self.channel_event_producer.send(channel_event).is_err() {
+            return Err(anyhow!("send api event error"));
+        }
+        Ok(serde_json::to_string(&receiver.await?)?)
+    }
+
+    async fn start_file_source(&self, source: FileSource) -> Result<String> {
+        let (responder, receiver) = oneshot::channel();
+        let channel_event = define::ChannelEvent::StartFileSource { source, responder };
+        self.send_event(channel_event, receiver).await
+    }
+
+    async fn stop_file_source(&self, app_name: String, stream_name: String) -> Result<String> {
+        let (responder, receiver) = oneshot::channel();
+        let channel_event = define::ChannelEvent::StopFileSource {
+            app_name,
+            stream_name,
+            responder,
+        };
+        self.send_event(channel_event, receiver).await
+    }
+
+    /* Sends an event answered with a result only, and reports it. */
+    async fn send_event(
+        &self,
+        channel_event: define::ChannelEvent,
+        receiver: oneshot::Receiver<Result<(), ChannelError>>,
+    ) -> Result<String> {
+        if self.channel_event_producer.send(channel_event).is_err() {
+            return Err(anyhow!("send api event error"));
+        }
+        match receiver.await? {
+            Ok(()) => Ok(String::from("ok")),
+            Err(err) => Err(anyhow!("{}", err)),
+        }
+    }
 }
 
 pub async fn run(producer: ChannelEventProducer, port: usize) {
@@ -72,9 +142,66 @@ pub async fn run(producer: ChannelEventProducer, port: usize) {
         }
     };
 
+    let api_kick_publisher = api.clone();
+    let kick_publisher = move |Path((app_name, stream_name)): Path<(String, String)>| async move {
+        match api_kick_publisher
+            .kick_publisher(app_name, stream_name)
+            .await
+        {
+            Ok(response) => response,
+            Err(err) => format!("error: {err}"),
+        }
+    };
+
+    let api_kick_subscriber = api.clone();
+    let kick_subscriber = move |Path(id): Path<String>| async move {
+        match api_kick_subscriber.kick_subscriber(id).await {
+            Ok(response) => response,
+            Err(err) => format!("error: {err}"),
+        }
+    };
+
+    let api_get_file_sources = api.clone();
+    let file_sources = move || async move {
+        match api_get_file_sources.get_file_sources().await {
+            Ok(response) => response,
+            Err(err) => format!("error: {err}"),
+        }
+    };
+
+    let api_start_file_source = api.clone();
+    let start_file_source = move |Json(source): Json<FileSource>| async move {
+        match api_start_file_source.start_file_source(source).await {
+            Ok(response) => response,
+            Err(err) => format!("error: {err}"),
+        }
+    };
+
+    let api_stop_file_source = api.clone();
+    let stop_file_source = move |Path((app_name, stream_name)): Path<(String, String)>| async move {
+        match api_stop_file_source
+            .stop_file_source(app_name, stream_name)
+            .await
+        {
+            Ok(response) => response,
+            Err(err) => format!("error: {err}"),
+        }
+    };
+
     let app = Router::new()
         .route("/", get(root))
-        .route("/get_stream_status", get(status));
+        .route("/get_stream_status", get(status))
+        .route(
+            "/kick_publisher/:app_name/:stream_name",
+            post(kick_publisher),
+        )
+        .route("/kick_subscriber/:id", post(kick_subscriber))
+        .route("/file_sources", get(file_sources))
+        .route("/start_file_source", post(start_file_source))
+        .route(
+            "/stop_file_source/:app_name/:stream_name",
+            post(stop_file_source),
+        );
     tracing::info!("Http api server listening on http://{}", sock_addr);
 
     let server = axum::Server::bind(&sock_addr);
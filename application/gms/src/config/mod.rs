@@ -2,8 +2,10 @@ pub mod errors;
 
 use errors::ConfigError;
 
+use rtmp::channels::define::{BackpressurePolicy, PublishConflictPolicy, StartMode};
 use rtmp::session::server_session::RtmpWebhookConfig;
 use serde_derive::Deserialize;
+use std::collections::HashMap;
 use std::fs;
 use std::vec::Vec;
 
@@ -14,6 +16,8 @@ pub struct Config {
     pub hls: Option<HlsConfig>,
     pub httpapi: Option<HttpApi>,
     pub log: Option<LogConfig>,
+    // settings overriding the ones above for an app name
+    pub apps: Option<HashMap<String, AppConfig>>,
 }
 
 impl Config {
@@ -30,9 +34,23 @@ impl Config {
                 enabled: true,
                 webhooks: None,
                 gop_num: Some(1),
+                gop_max_duration: None,
+                gop_max_bytes: None,
+                start_mode: None,
                 port: rtmp_port,
+                rtmps: None,
+                ping_interval: None,
+                ping_max_missed: None,
+                publish_conflict: None,
+                subscribe_wait_timeout: None,
+                publish_idle_timeout: None,
+                subscriber_idle_timeout: None,
+                backpressure: None,
                 pull: None,
                 push: None,
+                file_sources: None,
+                file_sources_dir: None,
+                failover: None,
             });
         }
 
@@ -49,6 +67,9 @@ impl Config {
             hls_config = Some(HlsConfig {
                 enabled: true,
                 port: hls_port,
+                segment_duration: None,
+                partial_segment_duration: None,
+                live_segment_count: None,
             });
         }
 
@@ -71,6 +92,7 @@ impl Config {
             hls: hls_config,
             httpapi: httpapi_config,
             log: log_config,
+            apps: None,
         }
     }
 }
@@ -79,21 +101,82 @@ pub struct RtmpConfig {
     pub enabled: bool,
     pub port: usize,
     pub gop_num: Option<usize>,
+    // the cached gops may span gop_max_duration seconds and take gop_max_bytes
+    pub gop_max_duration: Option<u64>,
+    pub gop_max_bytes: Option<usize>,
+    // latest_keyframe or full_cache (default)
+    pub start_mode: Option<StartMode>,
+    pub rtmps: Option<RtmpsConfig>,
+    // keepalive ping interval in seconds (0 disables the pings) and how many
+    // pings a peer may leave unanswered before it is disconnected
+    pub ping_interval: Option<u64>,
+    pub ping_max_missed: Option<u32>,
+    // what happens to a publish to a stream which is already being published:
+    // reject (default), kick_existing or queue, overridable per app
+    pub publish_conflict: Option<PublishConflictPolicy>,
+    // seconds a player waits for a stream which is not published yet
+    pub subscribe_wait_timeout: Option<u64>,
+    // seconds without media after which a stream is unpublished, and without
+    // reads after which a player is disconnected (0 disables the check)
+    pub publish_idle_timeout: Option<u64>,
+    pub subscriber_idle_timeout: Option<u64>,
+    // the queue of a player which does not keep up: non-key video frames are
+    // dropped first, then whole gops, then the player is disconnected
+    pub backpressure: Option<BackpressurePolicy>,
     pub pull: Option<RtmpPullConfig>,
     pub push: Option<Vec<RtmpPushConfig>>,
     pub webhooks: Option<RtmpWebhookConfig>,
+    pub file_sources: Option<Vec<RtmpFileSourceConfig>>,
+    // the directory of the flv files the http api may publish, without it
+    // only the file sources above are published
+    pub file_sources_dir: Option<String>,
+    // "app_name/stream_name" of a stream to the one its players are moved to
+    // when it is unpublished, they are moved back once it is published again
+    pub failover: Option<HashMap<String, String>>,
+}
+#[derive(Debug, Deserialize, Clone)]
+pub struct RtmpsConfig {
+    pub enabled: bool,
+    pub port: usize,
+    // PEM encoded certificate chain and private key
+    pub cert_path: String,
+    pub key_path: String,
 }
 #[derive(Debug, Deserialize, Clone)]
 pub struct RtmpPullConfig {
     pub enabled: bool,
     pub address: String,
     pub port: usize,
+    // connect over TLS (rtmps), the certificate is verified against
+    // tls_server_name, the address by default (not an ip address)
+    pub tls: Option<bool>,
+    pub tls_server_name: Option<String>,
+    // start with the digest (complex) handshake
+    pub complex_handshake: Option<bool>,
 }
 #[derive(Debug, Deserialize, Clone)]
 pub struct RtmpPushConfig {
     pub enabled: bool,
     pub address: String,
     pub port: usize,
+    // connect over TLS (rtmps), the certificate is verified against
+    // tls_server_name, the address by default (not an ip address)
+    pub tls: Option<bool>,
+    pub tls_server_name: Option<String>,
+    // start with the digest (complex) handshake
+    pub complex_handshake: Option<bool>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct RtmpFileSourceConfig {
+    pub enabled: bool,
+    // an flv file published as app_name/stream_name, paced by its timestamps
+    pub path: String,
+    pub app_name: String,
+    pub stream_name: String,
+    // play the file again once it ends
+    #[serde(default, rename = "loop")]
+    pub looped: bool,
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -106,6 +189,35 @@ pub struct HttpFlvConfig {
 pub struct HlsConfig {
     pub enabled: bool,
     pub port: usize,
+    // segment duration in seconds, partial segment duration in milliseconds
+    // and how many segments the live playlist lists
+    pub segment_duration: Option<i64>,
+    pub partial_segment_duration: Option<i64>,
+    pub live_segment_count: Option<usize>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct AppConfig {
+    pub gop_num: Option<usize>,
+    pub gop_max_duration: Option<u64>,
+    pub gop_max_bytes: Option<usize>,
+    // streams which may be published and players which may play them (0 is unlimited)
+    pub max_publishers: Option<usize>,
+    pub max_subscribers: Option<usize>,
+    pub httpflv_enabled: Option<bool>,
+    pub publish_conflict: Option<PublishConflictPolicy>,
+    pub hls: Option<AppHlsConfig>,
+    // replace the global relay targets for the app's streams
+    pub push: Option<Vec<RtmpPushConfig>>,
+    pub webhooks: Option<RtmpWebhookConfig>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct AppHlsConfig {
+    pub enabled: Option<bool>,
+    pub segment_duration: Option<i64>,
+    pub partial_segment_duration: Option<i64>,
+    pub live_segment_count: Option<usize>,
 }
 
 pub enum LogLevel {
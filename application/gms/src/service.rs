@@ -2,18 +2,29 @@ use hls::hls_event_manager::HlsEventManager;
 
 use {
     super::api,
-    super::config::Config,
+    super::config::{AppConfig, Config, RtmpPushConfig},
     //https://rustcc.cn/article?id=6dcbf032-0483-4980-8bfe-c64a7dfb33c7
     anyhow::Result,
+    hls::define::HlsSettings,
     hls::rtmp_event_processor::RtmpEventProcessor,
     hls::server as hls_server,
     httpflv::server as httpflv_server,
     rtmp::{
-        channels::ChannelsManager,
+        channels::{
+            define::{AppProfile, ChannelEventProducer},
+            ChannelsManager,
+        },
+        file_source::define::FileSource,
         relay::{pull_client::PullClient, push_client::PushClient},
         rtmp::RtmpServer,
+        session::{define as session_define, server_session::RtmpWebhookConfig},
+        tls,
+    },
+    std::{
+        collections::{HashMap, HashSet},
+        path::PathBuf,
+        time::Duration,
     },
-
     tokio,
 };
 
@@ -28,6 +39,7 @@ impl Service {
 
     pub async fn run(&mut self) -> Result<()> {
         let mut channel = ChannelsManager::new();
+        self.set_app_profiles(&mut channel);
 
         self.start_httpflv(&mut channel).await?;
         self.start_hls(&mut channel).await?;
@@ -38,6 +50,71 @@ impl Service {
         Ok(())
     }
 
+    fn app_cfgs(&self) -> impl Iterator<Item = (&String, &AppConfig)> {
+        self.cfg.apps.iter().flatten()
+    }
+
+    fn set_app_profiles(&self, channel: &mut ChannelsManager) {
+        for (app_name, app_cfg) in self.app_cfgs() {
+            tracing::info!("app profile of {}", app_name);
+            channel.set_app_profile(
+                app_name.clone(),
+                AppProfile {
+                    gop_num: app_cfg.gop_num,
+                    gop_max_duration: app_cfg.gop_max_duration.map(Duration::from_secs),
+                    gop_max_bytes: app_cfg.gop_max_bytes,
+                    max_publishers: app_cfg.max_publishers,
+                    max_subscribers: app_cfg.max_subscribers,
+                    httpflv_enabled: app_cfg.httpflv_enabled,
+                    publish_conflict: app_cfg.publish_conflict,
+                },
+            );
+        }
+    }
+
+    /* None for a disabled relay target. */
+    fn new_push_client(
+        channel: &mut ChannelsManager,
+        producer: &ChannelEventProducer,
+        push_value: &RtmpPushConfig,
+    ) -> Option<PushClient> {
+        if !push_value.enabled {
+            return None;
+        }
+        tracing::info!("start rtmp push client..");
+        let address = format!(
+            "{ip}:{port}",
+            ip = push_value.address,
+            port = push_value.port
+        );
+
+        let mut push_client = PushClient::new(
+            address,
+            channel.get_client_event_consumer(),
+            producer.clone(),
+        );
+        if push_value.tls.unwrap_or(false) {
+            push_client.set_tls_server_name(
+                push_value
+                    .tls_server_name
+                    .clone()
+                    .unwrap_or_else(|| push_value.address.clone()),
+            );
+        }
+        push_client.set_complex_handshake(push_value.complex_handshake.unwrap_or(false));
+        channel.set_rtmp_push_enabled(true);
+
+        Some(push_client)
+    }
+
+    fn spawn_push_client(mut push_client: PushClient) {
+        tokio::spawn(async move {
+            if let Err(err) = push_client.run().await {
+                tracing::error!("push client error {}\n", err);
+            }
+        });
+    }
+
     async fn start_http_api_server(&mut self, channel: &mut ChannelsManager) -> Result<()> {
         let httpapi_cfg = &self.cfg.httpapi;
         let producer = channel.get_channel_event_producer();
@@ -71,33 +148,84 @@ impl Service {
             };
 
             channel.set_rtmp_gop_num(gop_num);
+            if let Some(max_duration) = rtmp_cfg_value.gop_max_duration {
+                channel.set_rtmp_gop_max_duration(Duration::from_secs(max_duration));
+            }
+            if let Some(max_bytes) = rtmp_cfg_value.gop_max_bytes {
+                channel.set_rtmp_gop_max_bytes(max_bytes);
+            }
+            if let Some(start_mode) = rtmp_cfg_value.start_mode {
+                channel.set_start_mode(start_mode);
+            }
+            if let Some(policy) = rtmp_cfg_value.publish_conflict {
+                channel.set_publish_conflict_policy(policy);
+            }
+            if let Some(timeout) = rtmp_cfg_value.subscribe_wait_timeout {
+                channel.set_subscribe_wait_timeout(Duration::from_secs(timeout));
+            }
+            if let Some(timeout) = rtmp_cfg_value.publish_idle_timeout {
+                channel.set_publish_idle_timeout(Duration::from_secs(timeout));
+            }
+            if let Some(timeout) = rtmp_cfg_value.subscriber_idle_timeout {
+                channel.set_subscriber_idle_timeout(Duration::from_secs(timeout));
+            }
+            if let Some(policy) = rtmp_cfg_value.backpressure {
+                channel.set_backpressure_policy(policy);
+            }
+            if let Some(failover_streams) = &rtmp_cfg_value.failover {
+                for (stream, backup_stream) in failover_streams {
+                    match (stream.split_once('/'), backup_stream.split_once('/')) {
+                        (
+                            Some((app_name, stream_name)),
+                            Some((backup_app_name, backup_stream_name)),
+                        ) => {
+                            channel.set_failover_stream(
+                                app_name.to_string(),
+                                stream_name.to_string(),
+                                backup_app_name.to_string(),
+                                backup_stream_name.to_string(),
+                            );
+                        }
+                        _ => {
+                            tracing::error!(
+                                "failover streams are app_name/stream_name: {} = {}",
+                                stream,
+                                backup_stream
+                            );
+                        }
+                    }
+                }
+            }
             let producer = channel.get_channel_event_producer();
 
-            /*static push */
+            /*static push, the apps with their own relay targets are only
+            pushed to those*/
+            let app_push_cfgs: HashMap<&String, &Vec<RtmpPushConfig>> = self
+                .app_cfgs()
+                .filter_map(|(app_name, app_cfg)| Some((app_name, app_cfg.push.as_ref()?)))
+                .collect();
             if let Some(push_cfg_values) = &rtmp_cfg_value.push {
+                let excluded_app_names: HashSet<String> = app_push_cfgs
+                    .keys()
+                    .map(|app_name| (*app_name).clone())
+                    .collect();
                 for push_value in push_cfg_values {
-                    if !push_value.enabled {
-                        continue;
+                    if let Some(mut push_client) =
+                        Self::new_push_client(channel, &producer, push_value)
+                    {
+                        push_client.set_excluded_app_names(excluded_app_names.clone());
+                        Self::spawn_push_client(push_client);
+                    }
+                }
+            }
+            for (app_name, push_cfg_values) in &app_push_cfgs {
+                for push_value in *push_cfg_values {
+                    if let Some(mut push_client) =
+                        Self::new_push_client(channel, &producer, push_value)
+                    {
+                        push_client.set_app_name((*app_name).clone());
+                        Self::spawn_push_client(push_client);
                     }
-                    tracing::info!("start rtmp push client..");
-                    let address = format!(
-                        "{ip}:{port}",
-                        ip = push_value.address,
-                        port = push_value.port
-                    );
-
-                    let mut push_client = PushClient::new(
-                        address,
-                        channel.get_client_event_consumer(),
-                        producer.clone(),
-                    );
-                    tokio::spawn(async move {
-                        if let Err(err) = push_client.run().await {
-                            tracing::error!("push client error {}\n", err);
-                        }
-                    });
-
-                    channel.set_rtmp_push_enabled(true);
                 }
             }
             /*static pull*/
@@ -114,6 +242,16 @@ impl Service {
                         channel.get_client_event_consumer(),
                         producer.clone(),
                     );
+                    if pull_cfg_value.tls.unwrap_or(false) {
+                        pull_client.set_tls_server_name(
+                            pull_cfg_value
+                                .tls_server_name
+                                .clone()
+                                .unwrap_or_else(|| pull_cfg_value.address.clone()),
+                        );
+                    }
+                    pull_client
+                        .set_complex_handshake(pull_cfg_value.complex_handshake.unwrap_or(false));
 
                     tokio::spawn(async move {
                         if let Err(err) = pull_client.run().await {
@@ -125,6 +263,80 @@ impl Service {
                 }
             }
 
+            /*flv files published as live streams*/
+            if let Some(file_source_cfg_values) = &rtmp_cfg_value.file_sources {
+                for file_source_value in file_source_cfg_values {
+                    if !file_source_value.enabled {
+                        continue;
+                    }
+                    let source = FileSource {
+                        path: file_source_value.path.clone(),
+                        app_name: file_source_value.app_name.clone(),
+                        stream_name: file_source_value.stream_name.clone(),
+                        looped: file_source_value.looped,
+                    };
+                    if let Err(err) = channel.start_file_source(source) {
+                        tracing::error!("start file source error: {}", err);
+                    }
+                }
+            }
+            if let Some(dir) = &rtmp_cfg_value.file_sources_dir {
+                channel.set_file_sources_dir(PathBuf::from(dir));
+            }
+
+            let ping_interval = Duration::from_secs(
+                rtmp_cfg_value
+                    .ping_interval
+                    .unwrap_or(session_define::PING_INTERVAL_SECS),
+            );
+            let ping_max_missed = rtmp_cfg_value
+                .ping_max_missed
+                .unwrap_or(session_define::PING_MAX_MISSED);
+
+            let app_webhook_configs: HashMap<String, RtmpWebhookConfig> = self
+                .app_cfgs()
+                .filter_map(|(app_name, app_cfg)| {
+                    Some((app_name.clone(), app_cfg.webhooks.clone()?))
+                })
+                .collect();
+
+            /*rtmps listener*/
+            if let Some(rtmps_cfg_value) = &rtmp_cfg_value.rtmps {
+                if rtmps_cfg_value.enabled {
+                    let tls_config = match tls::load_server_config(
+                        &rtmps_cfg_value.cert_path,
+                        &rtmps_cfg_value.key_path,
+                    ) {
+                        Ok(tls_config) => tls_config,
+                        Err(err) => {
+                            return Err(anyhow::anyhow!("rtmps tls config error: {}", err));
+                        }
+                    };
+                    let webhook_config = match &rtmp_cfg_value.webhooks {
+                        Some(webhooks_cfg_value) => webhooks_cfg_value.clone(),
+                        None => RtmpWebhookConfig {
+                            enabled: false,
+                            publish_url: String::new(),
+                            publish_done_url: String::new(),
+                        },
+                    };
+
+                    let listen_port = rtmps_cfg_value.port;
+                    let address = format!("0.0.0.0:{listen_port}");
+
+                    let mut rtmps_server =
+                        RtmpServer::new(address, producer.clone(), webhook_config);
+                    rtmps_server.set_tls_acceptor(tls::new_acceptor(tls_config));
+                    rtmps_server.set_keepalive(ping_interval, ping_max_missed);
+                    rtmps_server.set_app_webhook_configs(app_webhook_configs.clone());
+                    tokio::spawn(async move {
+                        if let Err(err) = rtmps_server.run().await {
+                            tracing::error!("rtmps server error: {}\n", err);
+                        }
+                    });
+                }
+            }
+
             let listen_port = rtmp_cfg_value.port;
             let address = format!("0.0.0.0:{listen_port}");
 
@@ -132,6 +344,8 @@ impl Service {
             if let Some(webhooks_cfg_value) = &rtmp_cfg_value.webhooks {
                 let mut rtmp_server =
                     RtmpServer::new(address, producer, webhooks_cfg_value.clone());
+                rtmp_server.set_keepalive(ping_interval, ping_max_missed);
+                rtmp_server.set_app_webhook_configs(app_webhook_configs);
                 tokio::spawn(async move {
                     if let Err(err) = rtmp_server.run().await {
                         //print!("rtmp server  error {}\n", err);
@@ -169,7 +383,41 @@ impl Service {
         let hls_cfg = &self.cfg.hls;
 
         if let Some(hls_cfg_value) = hls_cfg {
-            if !hls_cfg_value.enabled {
+            let default_settings = HlsSettings::default();
+            let settings = HlsSettings {
+                enabled: hls_cfg_value.enabled,
+                segment_duration: hls_cfg_value
+                    .segment_duration
+                    .unwrap_or(default_settings.segment_duration),
+                partial_segment_duration: hls_cfg_value
+                    .partial_segment_duration
+                    .unwrap_or(default_settings.partial_segment_duration),
+                live_segment_count: hls_cfg_value
+                    .live_segment_count
+                    .unwrap_or(default_settings.live_segment_count),
+            };
+            let app_settings: HashMap<String, HlsSettings> = self
+                .app_cfgs()
+                .filter_map(|(app_name, app_cfg)| {
+                    let app_hls_cfg = app_cfg.hls.as_ref()?;
+                    let app_settings = HlsSettings {
+                        enabled: app_hls_cfg.enabled.unwrap_or(settings.enabled),
+                        segment_duration: app_hls_cfg
+                            .segment_duration
+                            .unwrap_or(settings.segment_duration),
+                        partial_segment_duration: app_hls_cfg
+                            .partial_segment_duration
+                            .unwrap_or(settings.partial_segment_duration),
+                        live_segment_count: app_hls_cfg
+                            .live_segment_count
+                            .unwrap_or(settings.live_segment_count),
+                    };
+                    Some((app_name.clone(), app_settings))
+                })
+                .collect();
+
+            /*the server also runs for the apps which enable hls themselves*/
+            if !settings.enabled && !app_settings.values().any(|settings| settings.enabled) {
                 return Ok(());
             }
 
@@ -180,6 +428,10 @@ impl Service {
             let client_event_consumer = channel.get_client_event_consumer();
             let mut rtmp_event_processor =
                 RtmpEventProcessor::new(client_event_consumer, event_producer, hls_dispatch);
+            rtmp_event_processor.set_settings(settings);
+            for (app_name, app_settings) in app_settings {
+                rtmp_event_processor.set_app_settings(app_name, app_settings);
+            }
 
             tokio::spawn(async move {
                 if let Err(err) = rtmp_event_processor.run().await {
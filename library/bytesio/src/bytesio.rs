@@ -5,7 +5,7 @@ use bytes::BytesMut;
 
 use std::time::Duration;
 
-use tokio::net::TcpStream;
+use tokio::io::{AsyncRead, AsyncWrite};
 use tokio::time::sleep;
 
 use tokio_stream::StreamExt;
@@ -15,13 +15,20 @@ use std::time::{SystemTime, UNIX_EPOCH};
 use tokio_util::codec::BytesCodec;
 use tokio_util::codec::Framed;
 
+/*The transport under BytesIO, a plain TcpStream or a TLS stream
+wrapping it.*/
+pub trait NetStream: AsyncRead + AsyncWrite + Unpin + Send {}
+
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> NetStream for T {}
+
 pub struct BytesIO {
-    stream: Framed<TcpStream, BytesCodec>,
+    stream: Framed<Box<dyn NetStream>, BytesCodec>,
     //timeout: Duration,
 }
 
 impl BytesIO {
-    pub fn new(stream: TcpStream) -> Self {
+    pub fn new<S: NetStream + 'static>(stream: S) -> Self {
+        let stream: Box<dyn NetStream> = Box::new(stream);
         Self {
             stream: Framed::new(stream, BytesCodec::new()),
             // timeout: ms,
@@ -18,6 +18,30 @@ pub mod avc_packet_type {
     pub const AVC_EOS: u8 = 2;
 }
 
+/*
+ Enhanced RTMP: when the IsExHeader bit of the first byte is set, the low
+ 4 bits carry the PacketType instead of the CodecID, and a FourCC follows.
+ https://github.com/veovera/enhanced-rtmp
+*/
+pub const IS_EX_HEADER: u8 = 0x80;
+
+pub mod video_packet_type {
+    pub const SEQUENCE_START: u8 = 0;
+    pub const CODED_FRAMES: u8 = 1;
+    pub const SEQUENCE_END: u8 = 2;
+    // CodedFrames without the composition time, which is 0
+    pub const CODED_FRAMES_X: u8 = 3;
+    pub const METADATA: u8 = 4;
+    pub const MPEG2TS_SEQUENCE_START: u8 = 5;
+}
+
+pub mod fourcc {
+    pub const AVC: [u8; 4] = *b"avc1";
+    pub const HEVC: [u8; 4] = *b"hvc1";
+    pub const AV1: [u8; 4] = *b"av01";
+    pub const VP9: [u8; 4] = *b"vp09";
+}
+
 pub mod frame_type {
     /*
         1: keyframe (for AVC, a seekable frame)
@@ -36,6 +60,10 @@ pub enum AvcCodecId {
     UNKNOWN = 0,
     H264 = 7,
     HEVC = 12,
+    /*AV1 and VP9 have no legacy codec id, the values are only used
+    internally for streams sent with the Enhanced RTMP FourCC.*/
+    AV1 = 13,
+    VP9 = 14,
 }
 
 pub fn u8_2_avc_codec_id(codec_id: u8) -> AvcCodecId {
@@ -46,6 +74,16 @@ pub fn u8_2_avc_codec_id(codec_id: u8) -> AvcCodecId {
     }
 }
 
+pub fn fourcc_2_avc_codec_id(fourcc: &[u8; 4]) -> AvcCodecId {
+    match *fourcc {
+        fourcc::AVC => AvcCodecId::H264,
+        fourcc::HEVC => AvcCodecId::HEVC,
+        fourcc::AV1 => AvcCodecId::AV1,
+        fourcc::VP9 => AvcCodecId::VP9,
+        _ => AvcCodecId::UNKNOWN,
+    }
+}
+
 pub mod tag_type {
     pub const AUDIO: u8 = 8;
     pub const VIDEO: u8 = 9;
@@ -2,7 +2,7 @@ use {
     super::{
         define::{aac_packet_type, avc_packet_type, tag_type, AvcCodecId, FlvData, SoundFormat},
         demuxer_tag::{AudioTagHeaderDemuxer, VideoTagHeaderDemuxer},
-        errors::FlvDemuxerError,
+        errors::{DemuxerErrorValue, FlvDemuxerError},
         mpeg4_aac::Mpeg4AacProcessor,
         mpeg4_avc::Mpeg4AvcProcessor,
     },
@@ -145,9 +145,11 @@ impl FlvVideoTagDemuxer {
         let remaining_bytes = video_tag_demuxer.get_remaining_bytes();
         let cts = header.composition_time;
 
-        self.avc_processor.extend_data(remaining_bytes);
-
+        /*only H.264 is remuxed, the other codecs (HEVC, AV1 and VP9 sent
+        with the Enhanced RTMP header) are passed through by rtmp/httpflv.*/
         if header.codec_id == AvcCodecId::H264 as u8 {
+            self.avc_processor.extend_data(remaining_bytes);
+
             match header.avc_packet_type {
                 avc_packet_type::AVC_SEQHDR => {
                     self.avc_processor.decoder_configuration_record_load()?;
@@ -229,6 +231,53 @@ impl FlvAudioTagDemuxer {
     }
 }
 
+pub const FLV_HEADER_SIZE: usize = 9;
+pub const FLV_TAG_HEADER_SIZE: usize = 11;
+pub const PREVIOUS_TAG_SIZE: usize = 4;
+
+/* Parses the flv header and returns its size, a header may be longer than
+the 9 bytes parsed. */
+pub fn parse_flv_header(bytes_reader: &mut BytesReader) -> Result<u32, FlvDemuxerError> {
+    /*signature*/
+    if &bytes_reader.read_bytes(3)?[..] != b"FLV" {
+        return Err(FlvDemuxerError {
+            value: DemuxerErrorValue::NotFlv,
+        });
+    }
+    /*version and flags*/
+    bytes_reader.read_bytes(2)?;
+    /*data offset*/
+    Ok(bytes_reader.read_u32::<BigEndian>()?)
+}
+
+/* The header of a tag, the tag data of data_size bytes follows. */
+pub struct FlvTagHeader {
+    pub tag_type: u8,
+    pub data_size: u32,
+    pub timestamp: u32,
+}
+
+impl FlvTagHeader {
+    pub fn parse(bytes_reader: &mut BytesReader) -> Result<Self, FlvDemuxerError> {
+        /*tag type, the upper bits are the reserved and the filter bits*/
+        let tag_type = bytes_reader.read_u8()? & 0x1f;
+        /*data size*/
+        let data_size = bytes_reader.read_u24::<BigEndian>()?;
+        /*timestamp*/
+        let timestamp = bytes_reader.read_u24::<BigEndian>()?;
+        /*timestamp extended*/
+        let timestamp_ext = bytes_reader.read_u8()?;
+        /*stream id*/
+        bytes_reader.read_u24::<BigEndian>()?;
+
+        Ok(Self {
+            tag_type,
+            data_size,
+            timestamp: (timestamp & 0xffffff) | ((timestamp_ext as u32) << 24),
+        })
+    }
+}
+
 pub struct FlvDemuxer {
     bytes_reader: BytesReader,
 }
@@ -241,8 +290,9 @@ impl FlvDemuxer {
     }
 
     pub fn read_flv_header(&mut self) -> Result<(), FlvDemuxerError> {
-        /*flv header*/
-        self.bytes_reader.read_bytes(9)?;
+        let header_size = parse_flv_header(&mut self.bytes_reader)? as usize;
+        self.bytes_reader
+            .read_bytes(header_size.saturating_sub(FLV_HEADER_SIZE))?;
         Ok(())
     }
 
@@ -250,23 +300,13 @@ impl FlvDemuxer {
         /*previous_tag_size*/
         self.bytes_reader.read_u32::<BigEndian>()?;
 
-        /*tag type*/
-        let tag_type = self.bytes_reader.read_u8()?;
-        /*data size*/
-        let data_size = self.bytes_reader.read_u24::<BigEndian>()?;
-        /*timestamp*/
-        let timestamp = self.bytes_reader.read_u24::<BigEndian>()?;
-        /*timestamp extended*/
-        let timestamp_ext = self.bytes_reader.read_u8()?;
-        /*stream id*/
-        self.bytes_reader.read_u24::<BigEndian>()?;
-
-        let dts: u32 = (timestamp & 0xffffff) | ((timestamp_ext as u32) << 24);
+        let header = FlvTagHeader::parse(&mut self.bytes_reader)?;
+        let dts = header.timestamp;
 
         /*data*/
-        let body = self.bytes_reader.read_bytes(data_size as usize)?;
+        let body = self.bytes_reader.read_bytes(header.data_size as usize)?;
 
-        match tag_type {
+        match header.tag_type {
             tag_type::VIDEO => {
                 return Ok(Some(FlvData::Video {
                     timestamp: dts,
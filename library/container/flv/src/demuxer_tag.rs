@@ -115,6 +115,21 @@ pub struct VideoTagHeader {
         5: video info/command frame
     */
     pub frame_type: u8,
+    /*
+        Enhanced RTMP ExVideoTagHeader, the codec is identified by the
+        FourCC and the packet type replaces the AVCPacketType.
+    */
+    pub is_ex_header: bool,
+    pub fourcc: [u8; 4],
+    /*
+        0: SequenceStart
+        1: CodedFrames
+        2: SequenceEnd
+        3: CodedFramesX
+        4: Metadata
+        5: MPEG2TSSequenceStart
+    */
+    pub packet_type: u8,
     /*
         1: JPEG (currently unused)
         2: Sorenson H.263
@@ -124,6 +139,7 @@ pub struct VideoTagHeader {
         6: Screen video version 2
         7: AVC
         12: HEVC
+        for the ex header, the AvcCodecId mapped from the FourCC
     */
     pub codec_id: u8,
     /*
@@ -139,11 +155,27 @@ impl VideoTagHeader {
     pub fn defalut() -> Self {
         VideoTagHeader {
             frame_type: 0,
+            is_ex_header: false,
+            fourcc: [0; 4],
+            packet_type: 0,
             codec_id: 0,
             avc_packet_type: 0,
             composition_time: 0,
         }
     }
+
+    pub fn is_key_frame(&self) -> bool {
+        self.frame_type == define::frame_type::KEY_FRAME
+    }
+
+    /*the AVC/HEVC decoder configuration record or its enhanced equivalent*/
+    pub fn is_sequence_header(&self) -> bool {
+        if self.is_ex_header {
+            self.packet_type == define::video_packet_type::SEQUENCE_START
+        } else {
+            self.avc_packet_type == define::avc_packet_type::AVC_SEQHDR
+        }
+    }
 }
 
 pub struct VideoTagHeaderDemuxer {
@@ -162,6 +194,10 @@ impl VideoTagHeaderDemuxer {
     pub fn parse_tag_header(&mut self) -> Result<VideoTagHeader, FlvDemuxerError> {
         let flags = self.bytes_reader.read_u8()?;
 
+        if flags & define::IS_EX_HEADER != 0 {
+            return self.parse_ex_tag_header(flags);
+        }
+
         self.tag.frame_type = flags >> 4;
         self.tag.codec_id = flags & 0x0f;
 
@@ -183,7 +219,93 @@ impl VideoTagHeaderDemuxer {
         Ok(self.tag.clone())
     }
 
+    /*
+        IsExHeader: UB[1]
+        FrameType: UB[3]
+        PacketType: UB[4]
+        FourCC: UI32
+        CompositionTime: SI24, only for the CodedFrames of AVC and HEVC
+    */
+    fn parse_ex_tag_header(&mut self, flags: u8) -> Result<VideoTagHeader, FlvDemuxerError> {
+        self.tag.is_ex_header = true;
+        self.tag.frame_type = (flags >> 4) & 0x07;
+        self.tag.packet_type = flags & 0x0f;
+
+        let fourcc = self.bytes_reader.read_bytes(4)?;
+        self.tag.fourcc.copy_from_slice(&fourcc[..]);
+        self.tag.codec_id = define::fourcc_2_avc_codec_id(&self.tag.fourcc) as u8;
+
+        /*keep the AVCPacketType in step, Metadata and MPEG2TSSequenceStart
+        have no AVC equivalent and keep their own values.*/
+        self.tag.avc_packet_type = match self.tag.packet_type {
+            define::video_packet_type::SEQUENCE_START => define::avc_packet_type::AVC_SEQHDR,
+            define::video_packet_type::CODED_FRAMES | define::video_packet_type::CODED_FRAMES_X => {
+                define::avc_packet_type::AVC_NALU
+            }
+            define::video_packet_type::SEQUENCE_END => define::avc_packet_type::AVC_EOS,
+            packet_type => packet_type,
+        };
+
+        self.tag.composition_time = 0;
+        if self.tag.packet_type == define::video_packet_type::CODED_FRAMES
+            && (self.tag.fourcc == define::fourcc::AVC || self.tag.fourcc == define::fourcc::HEVC)
+        {
+            for _ in 0..3 {
+                let time = self.bytes_reader.read_u8()?;
+                self.tag.composition_time = (self.tag.composition_time << 8) + time as u32;
+            }
+        }
+
+        Ok(self.tag.clone())
+    }
+
     pub fn get_remaining_bytes(&mut self) -> BytesMut {
         self.bytes_reader.extract_remaining_bytes()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::super::define::{self, AvcCodecId};
+    use super::VideoTagHeaderDemuxer;
+    use bytes::BytesMut;
+
+    #[test]
+    fn test_parse_ex_video_tag_header() {
+        //IsExHeader | KeyFrame | CodedFrames, "hvc1", composition time 0x000102
+        let data = [0x91, b'h', b'v', b'c', b'1', 0x00, 0x01, 0x02, 0xaa];
+        let mut demuxer = VideoTagHeaderDemuxer::new(BytesMut::from(&data[..]));
+        let tag = demuxer.parse_tag_header().unwrap();
+
+        assert!(tag.is_ex_header);
+        assert!(tag.is_key_frame());
+        assert!(!tag.is_sequence_header());
+        assert_eq!(tag.codec_id, AvcCodecId::HEVC as u8);
+        assert_eq!(tag.packet_type, define::video_packet_type::CODED_FRAMES);
+        assert_eq!(tag.avc_packet_type, define::avc_packet_type::AVC_NALU);
+        assert_eq!(tag.composition_time, 0x0102);
+        assert_eq!(&demuxer.get_remaining_bytes()[..], &[0xaa]);
+
+        //IsExHeader | KeyFrame | SequenceStart, "av01", no composition time
+        let data = [0x90, b'a', b'v', b'0', b'1', 0x81, 0x00];
+        let mut demuxer = VideoTagHeaderDemuxer::new(BytesMut::from(&data[..]));
+        let tag = demuxer.parse_tag_header().unwrap();
+
+        assert!(tag.is_sequence_header());
+        assert_eq!(tag.codec_id, AvcCodecId::AV1 as u8);
+        assert_eq!(&demuxer.get_remaining_bytes()[..], &[0x81, 0x00]);
+    }
+
+    #[test]
+    fn test_parse_legacy_video_tag_header() {
+        //KeyFrame | AVC, sequence header
+        let data = [0x17, 0x00, 0x00, 0x00, 0x00, 0x01];
+        let mut demuxer = VideoTagHeaderDemuxer::new(BytesMut::from(&data[..]));
+        let tag = demuxer.parse_tag_header().unwrap();
+
+        assert!(!tag.is_ex_header);
+        assert!(tag.is_key_frame());
+        assert!(tag.is_sequence_header());
+        assert_eq!(tag.codec_id, AvcCodecId::H264 as u8);
+    }
+}
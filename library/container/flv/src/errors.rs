@@ -87,6 +87,8 @@ pub enum DemuxerErrorValue {
     MpegAvcError(#[cause] MpegAvcError),
     #[fail(display = "mpeg aac error:{}\n", _0)]
     MpegAacError(#[cause] MpegAacError),
+    #[fail(display = "not an flv file\n")]
+    NotFlv,
 }
 
 impl From<BytesWriteError> for FlvDemuxerError {
@@ -1,7 +1,4 @@
-use {
-    super::errors::MuxerError, byteorder::BigEndian, bytes::BytesMut,
-    bytesio::bytes_writer::BytesWriter,
-};
+use {super::errors::MuxerError, byteorder::BigEndian, bytesio::bytes_writer::BytesWriter};
 
 const FLV_HEADER: [u8; 9] = [
     0x46, // 'F'
@@ -55,8 +52,8 @@ impl FlvMuxer {
         Ok(())
     }
 
-    pub fn write_flv_tag_body(&mut self, body: BytesMut) -> Result<(), MuxerError> {
-        self.writer.write(&body[..])?;
+    pub fn write_flv_tag_body(&mut self, body: &[u8]) -> Result<(), MuxerError> {
+        self.writer.write(body)?;
         Ok(())
     }
 
@@ -2,6 +2,28 @@ use xflv::demuxer::{FlvDemuxerAudioData, FlvDemuxerVideoData};
 
 pub const HLS_DURATION: u8 = 10;
 
+/* How the published streams are remuxed, globally or for an app: the
+segment_duration is in seconds, the partial_segment_duration in milliseconds
+and the live playlist lists live_segment_count segments. */
+#[derive(Debug, Clone, Copy)]
+pub struct HlsSettings {
+    pub enabled: bool,
+    pub segment_duration: i64,
+    pub partial_segment_duration: i64,
+    pub live_segment_count: usize,
+}
+
+impl Default for HlsSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            segment_duration: 5,
+            partial_segment_duration: 1000,
+            live_segment_count: 6,
+        }
+    }
+}
+
 pub enum FlvDemuxerData {
     Video { data: FlvDemuxerVideoData },
     Audio { data: FlvDemuxerAudioData },
@@ -36,6 +36,8 @@ pub struct Flv2HlsRemuxer {
     need_new_segment: bool,
     need_new_partial_segment: bool,
     segment_has_idr: bool,
+    //the publisher's timestamps jumped, a new segment starts at the next key frame
+    discontinuity: bool,
 
     video_pid: u16,
     audio_pid: u16,
@@ -52,6 +54,7 @@ impl Flv2HlsRemuxer {
         m3u8_consumer: M3u8Consumer,
         duration: i64,
         partial_seg_duration: i64,
+        live_ts_count: usize,
         app_name: String,
         stream_name: String,
     ) -> Self {
@@ -73,7 +76,14 @@ impl Flv2HlsRemuxer {
 
         let m3u8_name = format!("{stream_name}.m3u8");
 
-        let m3u8_handler = M3u8::new(hls_event_tx, duration, 6, m3u8_name, app_name, stream_name);
+        let m3u8_handler = M3u8::new(
+            hls_event_tx,
+            duration,
+            live_ts_count,
+            m3u8_name,
+            app_name,
+            stream_name,
+        );
 
         m3u8_handler.setup_m3u8_listener(m3u8_consumer);
 
@@ -96,6 +106,7 @@ impl Flv2HlsRemuxer {
             need_new_segment: false,
             need_new_partial_segment: false,
             segment_has_idr: false,
+            discontinuity: false,
 
             video_pid,
             audio_pid,
@@ -125,19 +136,21 @@ impl Flv2HlsRemuxer {
         Ok(())
     }
 
+    /* The segment after the current one is marked as following a discontinuity. */
+    pub fn on_discontinuity(&mut self) {
+        self.discontinuity = true;
+    }
+
     pub fn flush_remaining_data(&mut self) -> Result<(), MediaError> {
         let data = self.ts_muxer.get_data();
-        let mut discontinuity: bool = false;
-        if self.last_dts > self.last_ts_dts + 15 * 1000 {
-            discontinuity = true;
-        }
         self.m3u8_handler.add_segment(
             self.last_dts - self.last_ts_dts,
-            discontinuity,
+            self.discontinuity,
             true,
             data,
         )?;
         self.m3u8_handler.refresh_playlist(false)?;
+        self.discontinuity = false;
 
         Ok(())
     }
@@ -173,7 +186,7 @@ impl Flv2HlsRemuxer {
                     flags = MPEG_FLAG_IDR_FRAME;
                     self.segment_has_idr = true;
 
-                    if dts - self.last_ts_dts >= self.duration * 1000 {
+                    if self.discontinuity || dts - self.last_ts_dts >= self.duration * 1000 {
                         self.need_new_segment = true;
                     }
                 }
@@ -197,14 +210,14 @@ impl Flv2HlsRemuxer {
         }
 
         if self.need_new_segment {
-            let mut discontinuity: bool = false;
-            if dts > self.last_ts_dts + 15 * 1000 {
-                discontinuity = true;
-            }
             let data = self.ts_muxer.get_data();
 
-            self.m3u8_handler
-                .add_segment(dts - self.last_ts_dts, discontinuity, false, data)?;
+            self.m3u8_handler.add_segment(
+                dts - self.last_ts_dts,
+                self.discontinuity,
+                false,
+                data,
+            )?;
             self.m3u8_handler.refresh_playlist(true)?;
 
             self.ts_muxer.reset();
@@ -212,6 +225,7 @@ impl Flv2HlsRemuxer {
             self.last_ts_pts = pts;
             self.need_new_segment = false;
             self.segment_has_idr = false;
+            self.discontinuity = false;
         } else if self.need_new_partial_segment {
             let d = self.partial_ts_muxer.get_data();
 
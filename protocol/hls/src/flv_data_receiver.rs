@@ -2,23 +2,21 @@ use crate::hls_event_manager::M3u8Consumer;
 
 use {
     super::{
+        define::HlsSettings,
         errors::{HlsError, HlsErrorValue},
         flv2hls::Flv2HlsRemuxer,
         hls_event_manager::HlsEventProducer,
     },
+    bytes::BytesMut,
     rtmp::channels::define::{
-        ChannelData, ChannelDataConsumer, ChannelEvent, ChannelEventProducer,
+        ChannelData, ChannelEvent, ChannelEventProducer, SubscriberDataConsumer,
     },
     rtmp::session::{
-        common::SubscriberInfo,
+        common::{SessionInfo, SubscriberInfo},
         define::SubscribeType,
         errors::{SessionError, SessionErrorValue},
     },
-    std::time::Duration,
-    tokio::{
-        sync::{mpsc, oneshot},
-        time::sleep,
-    },
+    tokio::sync::{mpsc, oneshot},
     uuid::Uuid,
     xflv::define::FlvData,
 };
@@ -29,9 +27,10 @@ pub struct FlvDataReceiver {
     app_name: String,
     stream_name: String,
     event_producer: ChannelEventProducer,
-    data_consumer: ChannelDataConsumer,
+    data_consumer: SubscriberDataConsumer,
     media_processor: Flv2HlsRemuxer,
     subscriber_id: Uuid,
+    session_info: SessionInfo,
 }
 
 impl FlvDataReceiver {
@@ -41,10 +40,9 @@ impl FlvDataReceiver {
         event_producer: ChannelEventProducer,
         hls_event_tx: HlsEventProducer,
         m3u8_consumer: M3u8Consumer,
-
-        duration: i64,
+        settings: HlsSettings,
     ) -> Self {
-        let (_, data_consumer) = mpsc::unbounded_channel();
+        let (_, data_consumer) = mpsc::channel(1);
         let subscriber_id = Uuid::new_v4();
 
         Self {
@@ -56,12 +54,14 @@ impl FlvDataReceiver {
             media_processor: Flv2HlsRemuxer::new(
                 hls_event_tx,
                 m3u8_consumer,
-                duration,
-                1000,
+                settings.segment_duration,
+                settings.partial_segment_duration,
+                settings.live_segment_count,
                 app_name,
                 stream_name,
             ),
             subscriber_id,
+            session_info: SessionInfo::new(None),
         }
     }
 
@@ -74,28 +74,38 @@ impl FlvDataReceiver {
     }
 
     pub async fn receive_flv_data(&mut self) -> Result<(), HlsError> {
-        let mut retry_count = 0;
+        let kick_token = self.session_info.kick_token.clone();
 
         loop {
-            if let Some(data) = self.data_consumer.recv().await {
-                let flv_data: FlvData = match data {
-                    ChannelData::Audio { timestamp, data } => FlvData::Audio { timestamp, data },
-                    ChannelData::Video { timestamp, data } => FlvData::Video { timestamp, data },
-                    _ => continue,
-                };
-                retry_count = 0;
-                self.media_processor.process_flv_data(flv_data)?;
-            } else {
-                sleep(Duration::from_millis(100)).await;
-                retry_count += 1;
-            }
-            //When rtmp stream is interupted here we retry 10 times.
-            //maybe have a better way to judge the stream status.
-            //will do an optimization in the future.
-            //todo
-            if retry_count > 10 {
-                break;
-            }
+            let data = tokio::select! {
+                data = self.data_consumer.recv() => data,
+                _ = kick_token.cancelled() => {
+                    tracing::info!("hls subscriber {} is kicked", self.subscriber_id);
+                    break;
+                }
+            };
+            /*the channel is closed once the stream is unpublished*/
+            let data = match data {
+                Some(data) => data,
+                None => break,
+            };
+            /*the remuxer demuxes in place, so the shared frame is copied*/
+            let flv_data: FlvData = match data {
+                ChannelData::Audio { timestamp, data } => FlvData::Audio {
+                    timestamp,
+                    data: BytesMut::from(&data[..]),
+                },
+                ChannelData::Video { timestamp, data } => FlvData::Video {
+                    timestamp,
+                    data: BytesMut::from(&data[..]),
+                },
+                ChannelData::Discontinuity { .. } => {
+                    self.media_processor.on_discontinuity();
+                    continue;
+                }
+                _ => continue,
+            };
+            self.media_processor.process_flv_data(flv_data)?;
         }
 
         self.media_processor.clear_files()?;
@@ -111,61 +121,57 @@ impl FlvDataReceiver {
         app_name: String,
         stream_name: String,
     ) -> Result<(), HlsError> {
-        let mut retry_count: u8 = 0;
+        let (sender, receiver) = oneshot::channel();
 
-        loop {
-            let (sender, receiver) = oneshot::channel();
+        let sub_info = SubscriberInfo {
+            id: self.subscriber_id,
+            sub_type: SubscribeType::PlayerHls,
+            session_info: self.session_info.clone(),
+            start_mode: None,
+        };
 
-            let sub_info = SubscriberInfo {
-                id: self.subscriber_id,
-                sub_type: SubscribeType::PlayerHls,
-            };
+        let subscribe_event = ChannelEvent::Subscribe {
+            app_name,
+            stream_name,
+            info: sub_info,
+            responder: sender,
+        };
 
-            let subscribe_event = ChannelEvent::Subscribe {
-                app_name: app_name.clone(),
-                stream_name: stream_name.clone(),
-                info: sub_info,
-                responder: sender,
+        let rv = self.event_producer.send(subscribe_event);
+        if rv.is_err() {
+            let session_error = SessionError {
+                value: SessionErrorValue::SendChannelDataErr,
             };
+            return Err(HlsError {
+                value: HlsErrorValue::SessionError(session_error),
+            });
+        }
 
-            let rv = self.event_producer.send(subscribe_event);
-            if rv.is_err() {
-                let session_error = SessionError {
-                    value: SessionErrorValue::SendChannelDataErr,
-                };
-                return Err(HlsError {
-                    value: HlsErrorValue::SessionError(session_error),
-                });
-            }
-
-            match receiver.await {
-                Ok(consumer) => {
-                    self.data_consumer = consumer;
-                    break;
-                }
-                Err(_) => {
-                    if retry_count > 10 {
-                        let session_error = SessionError {
-                            value: SessionErrorValue::SubscribeCountLimitReach,
-                        };
-                        return Err(HlsError {
-                            value: HlsErrorValue::SessionError(session_error),
-                        });
-                    }
-                }
+        /*Waits until the stream is published or the channels give up.*/
+        let session_error = match receiver.await {
+            Ok(Ok(consumer)) => {
+                self.data_consumer = consumer;
+                return Ok(());
             }
+            Ok(Err(err)) => SessionError {
+                value: SessionErrorValue::ChannelError(err),
+            },
+            Err(_) => SessionError {
+                value: SessionErrorValue::ChannelEventSendErr,
+            },
+        };
 
-            sleep(Duration::from_millis(800)).await;
-            retry_count += 1;
-        }
-
-        Ok(())
+        Err(HlsError {
+            value: HlsErrorValue::SessionError(session_error),
+        })
     }
 
     pub async fn unsubscribe_from_rtmp_channels(&mut self) -> Result<(), HlsError> {
         let sub_info = SubscriberInfo {
             id: self.subscriber_id,
             sub_type: SubscribeType::PlayerHls,
+            session_info: self.session_info.clone(),
+            start_mode: None,
         };
 
         let subscribe_event = ChannelEvent::UnSubscribe {
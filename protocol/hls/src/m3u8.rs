@@ -88,6 +88,10 @@ pub struct M3u8 {
     live_ts_count: usize,
 
     segments: VecDeque<Segment>,
+    /*the next segment follows a discontinuity*/
+    next_discontinuity: bool,
+    /*the discontinuities removed from the live playlist*/
+    discontinuity_sequence: u64,
     is_header_generated: bool,
 
     m3u8_header: String,
@@ -117,6 +121,8 @@ impl M3u8 {
             is_live: true,
             live_ts_count,
             segments: VecDeque::new(),
+            next_discontinuity: false,
+            discontinuity_sequence: 0,
             is_header_generated: false,
             m3u8_folder,
             m3u8_header: String::new(),
@@ -147,10 +153,11 @@ impl M3u8 {
         });
     }
 
+    /* Completes the current segment, discontinuity marks the next one. */
     pub fn add_segment(
         &mut self,
         duration: i64,
-        _discontinuity: bool,
+        discontinuity: bool,
         _is_eof: bool,
         ts_data: BytesMut,
     ) -> Result<(), MediaError> {
@@ -158,6 +165,9 @@ impl M3u8 {
 
         if self.is_live && segment_count >= self.live_ts_count {
             let segment = self.segments.pop_front().unwrap();
+            if segment.discontinuity {
+                self.discontinuity_sequence += 1;
+            }
             self.ts_handler.delete(segment.path);
             let mut s = self.sequence_no.write().unwrap();
             *s += 1;
@@ -167,7 +177,11 @@ impl M3u8 {
 
         self.ts_handler.write(ts_data, false)?;
         // let segment = Segment::new(duration, discontinuity, ts_name, ts_path, is_eof, false);
-        self.segments.back_mut().unwrap().set_complete(duration);
+        /*no partial segment was written yet*/
+        if let Some(segment) = self.segments.back_mut() {
+            segment.set_complete(duration);
+        }
+        self.next_discontinuity |= discontinuity;
 
         // self.segments.push_back(segment);
 
@@ -194,7 +208,7 @@ impl M3u8 {
                 let mut seg = Segment::new(
                     ts_num,
                     duration,
-                    false,
+                    std::mem::take(&mut self.next_discontinuity),
                     format!("{ts_num}.ts"),
                     ts_path,
                     false,
@@ -263,6 +277,13 @@ impl M3u8 {
             self.sequence_no.read().unwrap()
         )
         .as_str();
+        if self.discontinuity_sequence > 0 {
+            self.m3u8_header += format!(
+                "#EXT-X-DISCONTINUITY-SEQUENCE:{}\n",
+                self.discontinuity_sequence
+            )
+            .as_str();
+        }
         self.m3u8_header += playlist_type;
         self.m3u8_header += allow_cache;
 
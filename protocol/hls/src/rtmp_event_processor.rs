@@ -1,11 +1,13 @@
 use {
     super::{
+        define::HlsSettings,
         errors::HlsError,
         errors::HlsErrorValue,
         flv_data_receiver::FlvDataReceiver,
         hls_event_manager::{DispatchEvent, DispatchEventProducer},
     },
     rtmp::channels::define::{ChannelEventProducer, ClientEvent, ClientEventConsumer},
+    std::collections::HashMap,
     tokio::sync::oneshot,
 };
 
@@ -13,6 +15,9 @@ pub struct RtmpEventProcessor {
     client_event_consumer: ClientEventConsumer,
     event_producer: ChannelEventProducer,
     hls_manager_dispatcher: DispatchEventProducer,
+    settings: HlsSettings,
+    /* The settings of the app names which override the global ones. */
+    app_settings: HashMap<String, HlsSettings>,
 }
 
 impl RtmpEventProcessor {
@@ -25,9 +30,19 @@ impl RtmpEventProcessor {
             client_event_consumer: consumer,
             event_producer,
             hls_manager_dispatcher,
+            settings: HlsSettings::default(),
+            app_settings: HashMap::new(),
         }
     }
 
+    pub fn set_settings(&mut self, settings: HlsSettings) {
+        self.settings = settings;
+    }
+
+    pub fn set_app_settings(&mut self, app_name: String, settings: HlsSettings) {
+        self.app_settings.insert(app_name, settings);
+    }
+
     pub async fn run(&mut self) -> Result<(), HlsError> {
         loop {
             let val = self.client_event_consumer.recv().await?;
@@ -36,6 +51,19 @@ impl RtmpEventProcessor {
                     app_name,
                     stream_name,
                 } => {
+                    let settings = match self.app_settings.get(&app_name) {
+                        Some(settings) => *settings,
+                        None => self.settings,
+                    };
+                    if !settings.enabled {
+                        tracing::info!(
+                            "hls is disabled, app_name: {}, stream_name: {}",
+                            app_name,
+                            stream_name
+                        );
+                        continue;
+                    }
+
                     let (resp_tx, resp_rx) = oneshot::channel();
 
                     let m = DispatchEvent::CreateChannel {
@@ -60,7 +88,7 @@ impl RtmpEventProcessor {
                         self.event_producer.clone(),
                         stream_channel_producer,
                         m3u8_consumer,
-                        5,
+                        settings,
                     );
 
                     tokio::spawn(async move {
@@ -76,6 +76,7 @@ mod tests {
             m3u8_rx,
             5,
             200,
+            6,
             String::from("live"),
             String::from("test"),
         );
@@ -1,5 +1,5 @@
 use {
-    futures::channel::mpsc::{UnboundedReceiver, UnboundedSender},
+    futures::channel::mpsc::{Receiver, Sender},
     {bytes::BytesMut, std::io},
 };
 pub mod tag_type {
@@ -7,5 +7,8 @@ pub mod tag_type {
     pub const VIDEO: u8 = 9;
     pub const SCRIPT_DATA_AMF: u8 = 18;
 }
-pub type HttpResponseDataProducer = UnboundedSender<io::Result<BytesMut>>;
-pub type HttpResponseDataConsumer = UnboundedReceiver<io::Result<BytesMut>>;
+/* The muxed tags not written to the viewer yet, a viewer which stops reading
+fills it and stops taking frames from its subscriber queue. */
+pub const HTTP_RESPONSE_BUFFER_SIZE: usize = 8;
+pub type HttpResponseDataProducer = Sender<io::Result<BytesMut>>;
+pub type HttpResponseDataConsumer = Receiver<io::Result<BytesMut>>;
@@ -5,19 +5,18 @@ use {
     },
     crate::rtmp::{
         cache::metadata::MetaData,
-        channels::define::{ChannelData, ChannelDataConsumer, ChannelEvent, ChannelEventProducer},
+        channels::define::{
+            ChannelData, ChannelEvent, ChannelEventProducer, StartMode, SubscriberDataConsumer,
+        },
         session::{
-            common::SubscriberInfo,
+            common::{SessionInfo, SubscriberInfo},
             define::SubscribeType,
             errors::{SessionError, SessionErrorValue},
         },
     },
-    bytes::BytesMut,
-    std::time::Duration,
-    tokio::{
-        sync::{mpsc, oneshot},
-        time::sleep,
-    },
+    bytes::{Bytes, BytesMut},
+    futures::SinkExt,
+    tokio::sync::{mpsc, oneshot},
     uuid::Uuid,
     xflv::muxer::{FlvMuxer, HEADER_LENGTH},
 };
@@ -29,9 +28,11 @@ pub struct HttpFlv {
     muxer: FlvMuxer,
 
     event_producer: ChannelEventProducer,
-    data_consumer: ChannelDataConsumer,
+    data_consumer: SubscriberDataConsumer,
     http_response_data_producer: HttpResponseDataProducer,
     subscriber_id: Uuid,
+    session_info: SessionInfo,
+    start_mode: Option<StartMode>,
 }
 
 impl HttpFlv {
@@ -40,8 +41,9 @@ impl HttpFlv {
         stream_name: String,
         event_producer: ChannelEventProducer,
         http_response_data_producer: HttpResponseDataProducer,
+        session_info: SessionInfo,
     ) -> Self {
-        let (_, data_consumer) = mpsc::unbounded_channel();
+        let (_, data_consumer) = mpsc::channel(1);
         let subscriber_id = Uuid::new_v4();
 
         Self {
@@ -52,9 +54,16 @@ impl HttpFlv {
             event_producer,
             http_response_data_producer,
             subscriber_id,
+            session_info,
+            start_mode: None,
         }
     }
 
+    /* Asked for by the player with the start_mode query parameter. */
+    pub fn set_start_mode(&mut self, start_mode: Option<StartMode>) {
+        self.start_mode = start_mode;
+    }
+
     pub async fn run(&mut self) -> Result<(), HttpFLvError> {
         self.subscribe_from_rtmp_channels().await?;
         self.send_media_stream().await?;
@@ -66,19 +75,27 @@ impl HttpFlv {
         self.muxer.write_flv_header()?;
         self.muxer.write_previous_tag_size(0)?;
 
-        self.flush_response_data()?;
+        self.flush_response_data().await?;
         let mut retry_count = 0;
+        let kick_token = self.session_info.kick_token.clone();
         //write flv body
         loop {
-            if let Some(data) = self.data_consumer.recv().await {
-                if let Err(err) = self.write_flv_tag(data) {
+            /*the next frame is taken once the viewer read the previous ones*/
+            let rv = tokio::select! {
+                rv = self.send_next_tag() => rv,
+                _ = kick_token.cancelled() => {
+                    tracing::info!("httpflv subscriber {} is kicked", self.subscriber_id);
+                    break;
+                }
+            };
+            match rv {
+                Ok(true) => retry_count = 0,
+                /*the channel is closed once the stream is unpublished*/
+                Ok(false) => break,
+                Err(err) => {
                     tracing::error!("write_flv_tag err: {}", err);
                     retry_count += 1;
-                } else {
-                    retry_count = 0;
                 }
-            } else {
-                retry_count += 1;
             }
             if retry_count > 10 {
                 break;
@@ -87,8 +104,18 @@ impl HttpFlv {
         self.unsubscribe_from_rtmp_channels().await
     }
 
-    pub fn write_flv_tag(&mut self, channel_data: ChannelData) -> Result<(), HttpFLvError> {
-        let common_data: BytesMut;
+    async fn send_next_tag(&mut self) -> Result<bool, HttpFLvError> {
+        match self.data_consumer.recv().await {
+            Some(data) => {
+                self.write_flv_tag(data).await?;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    pub async fn write_flv_tag(&mut self, channel_data: ChannelData) -> Result<(), HttpFLvError> {
+        let common_data: Bytes;
         let common_timestamp: u32;
         let tag_type: u8;
 
@@ -107,31 +134,35 @@ impl HttpFlv {
 
             ChannelData::MetaData { timestamp, data } => {
                 let mut metadata = MetaData::new();
-                metadata.save(data);
+                metadata.save(BytesMut::from(&data[..]));
                 let data = metadata.remove_set_data_frame()?;
 
-                common_data = data;
+                common_data = data.freeze();
                 common_timestamp = timestamp;
                 tag_type = tag_type::SCRIPT_DATA_AMF;
             }
+
+            /*flv has no discontinuity, the timestamps keep increasing*/
+            ChannelData::Discontinuity { .. } => return Ok(()),
         }
 
         let common_data_len = common_data.len() as u32;
 
         self.muxer
             .write_flv_tag_header(tag_type, common_data_len, common_timestamp)?;
-        self.muxer.write_flv_tag_body(common_data)?;
+        self.muxer.write_flv_tag_body(&common_data[..])?;
         self.muxer
             .write_previous_tag_size(common_data_len + HEADER_LENGTH)?;
 
-        self.flush_response_data()?;
+        self.flush_response_data().await?;
 
         Ok(())
     }
 
-    pub fn flush_response_data(&mut self) -> Result<(), HttpFLvError> {
+    /* Waits while the response buffer is full. */
+    pub async fn flush_response_data(&mut self) -> Result<(), HttpFLvError> {
         let data = self.muxer.writer.extract_current_bytes();
-        self.http_response_data_producer.start_send(Ok(data))?;
+        self.http_response_data_producer.send(Ok(data)).await?;
 
         Ok(())
     }
@@ -140,6 +171,8 @@ impl HttpFlv {
         let sub_info = SubscriberInfo {
             id: self.subscriber_id,
             sub_type: SubscribeType::PlayerHttpFlv,
+            session_info: self.session_info.clone(),
+            start_mode: self.start_mode,
         };
 
         let subscribe_event = ChannelEvent::UnSubscribe {
@@ -155,54 +188,114 @@ impl HttpFlv {
     }
 
     pub async fn subscribe_from_rtmp_channels(&mut self) -> Result<(), HttpFLvError> {
-        let mut retry_count: u8 = 0;
+        let (sender, receiver) = oneshot::channel();
 
-        loop {
-            let (sender, receiver) = oneshot::channel();
+        let sub_info = SubscriberInfo {
+            id: self.subscriber_id,
+            sub_type: SubscribeType::PlayerHttpFlv,
+            session_info: self.session_info.clone(),
+            start_mode: self.start_mode,
+        };
 
-            let sub_info = SubscriberInfo {
-                id: self.subscriber_id,
-                sub_type: SubscribeType::PlayerHttpFlv,
-            };
+        let subscribe_event = ChannelEvent::Subscribe {
+            app_name: self.app_name.clone(),
+            stream_name: self.stream_name.clone(),
+            info: sub_info,
+            responder: sender,
+        };
 
-            let subscribe_event = ChannelEvent::Subscribe {
-                app_name: self.app_name.clone(),
-                stream_name: self.stream_name.clone(),
-                info: sub_info,
-                responder: sender,
+        let rv = self.event_producer.send(subscribe_event);
+        if rv.is_err() {
+            let session_error = SessionError {
+                value: SessionErrorValue::SendChannelDataErr,
             };
+            return Err(HttpFLvError {
+                value: HttpFLvErrorValue::SessionError(session_error),
+            });
+        }
 
-            let rv = self.event_producer.send(subscribe_event);
-            if rv.is_err() {
-                let session_error = SessionError {
-                    value: SessionErrorValue::SendChannelDataErr,
-                };
-                return Err(HttpFLvError {
-                    value: HttpFLvErrorValue::SessionError(session_error),
-                });
+        /*Waits until the stream is published or the channels give up.*/
+        let session_error = match receiver.await {
+            Ok(Ok(consumer)) => {
+                self.data_consumer = consumer;
+                return Ok(());
             }
+            Ok(Err(err)) => SessionError {
+                value: SessionErrorValue::ChannelError(err),
+            },
+            Err(_) => SessionError {
+                value: SessionErrorValue::ChannelEventSendErr,
+            },
+        };
 
-            match receiver.await {
-                Ok(consumer) => {
-                    self.data_consumer = consumer;
-                    break;
-                }
-                Err(_) => {
-                    if retry_count > 10 {
-                        let session_error = SessionError {
-                            value: SessionErrorValue::SubscribeCountLimitReach,
-                        };
-                        return Err(HttpFLvError {
-                            value: HttpFLvErrorValue::SessionError(session_error),
-                        });
-                    }
-                }
-            }
+        Err(HttpFLvError {
+            value: HttpFLvErrorValue::SessionError(session_error),
+        })
+    }
+}
 
-            sleep(Duration::from_millis(800)).await;
-            retry_count += 1;
-        }
+#[cfg(test)]
+mod tests {
+    use {
+        super::HttpFlv,
+        crate::{
+            define::HTTP_RESPONSE_BUFFER_SIZE,
+            rtmp::{
+                channels::{define::ChannelData, ChannelsManager},
+                session::common::{PublisherInfo, SessionInfo},
+            },
+        },
+        bytes::Bytes,
+        futures::channel::mpsc::channel,
+        std::time::Duration,
+        tokio::time::{sleep, timeout, Instant},
+        uuid::Uuid,
+    };
 
-        Ok(())
+    #[tokio::test]
+    async fn test_idle_viewer() {
+        let mut manager = ChannelsManager::new();
+        manager.set_subscriber_idle_timeout(Duration::from_millis(100));
+        let (app_name, stream_name) = (String::from("live"), String::from("test"));
+        let publisher = PublisherInfo {
+            id: Uuid::new_v4(),
+            session_info: SessionInfo::new(None),
+        };
+        let producer = manager.publish(&app_name, &stream_name, publisher).unwrap();
+        let event_producer = manager.get_channel_event_producer();
+        tokio::spawn(async move { manager.run().await });
+
+        /*a viewer which never reads the response*/
+        let (response_producer, _response_consumer) = channel(HTTP_RESPONSE_BUFFER_SIZE);
+        let session_info = SessionInfo::new(None);
+        let kick_token = session_info.kick_token.clone();
+        let mut httpflv = HttpFlv::new(
+            app_name,
+            stream_name,
+            event_producer,
+            response_producer,
+            session_info,
+        );
+        let handle = tokio::spawn(async move { httpflv.run().await.is_ok() });
+        sleep(Duration::from_millis(50)).await;
+
+        /*its queue fills up once the response buffer is full, and it is kicked*/
+        let deadline = Instant::now() + Duration::from_secs(3);
+        let mut idx = 0u32;
+        while !kick_token.is_cancelled() && Instant::now() < deadline {
+            let flags: u8 = if idx.is_multiple_of(10) { 0x17 } else { 0x27 };
+            let data = ChannelData::Video {
+                timestamp: idx * 40,
+                data: Bytes::copy_from_slice(&[flags, 0x01, 0x00, 0x00, 0x00]),
+            };
+            assert!(producer.send(data).is_ok());
+            idx += 1;
+            sleep(Duration::from_millis(5)).await;
+        }
+        assert!(kick_token.is_cancelled());
+        assert!(timeout(Duration::from_secs(1), handle)
+            .await
+            .unwrap()
+            .unwrap());
     }
 }
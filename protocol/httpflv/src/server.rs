@@ -1,11 +1,18 @@
 use {
-    super::httpflv::HttpFlv,
-    futures::channel::mpsc::unbounded,
+    super::{define::HTTP_RESPONSE_BUFFER_SIZE, httpflv::HttpFlv},
+    futures::channel::mpsc::channel,
     hyper::{
+        header,
+        server::conn::AddrStream,
         service::{make_service_fn, service_fn},
         Body, Request, Response, Server, StatusCode,
     },
-    rtmp::channels::define::ChannelEventProducer,
+    rtmp::{
+        channels::define::{ChannelEventProducer, StartMode},
+        session::common::SessionInfo,
+        utils::query::parse_query,
+    },
+    std::net::SocketAddr,
 };
 
 type GenericError = Box<dyn std::error::Error + Send + Sync>;
@@ -15,6 +22,7 @@ static NOTFOUND: &[u8] = b"Not Found";
 async fn handle_connection(
     req: Request<Body>,
     event_producer: ChannelEventProducer, // event_producer: ChannelEventProducer
+    remote_addr: SocketAddr,
 ) -> Result<Response<Body>> {
     let path = req.uri().path();
 
@@ -26,14 +34,29 @@ async fn handle_connection(
             let app_name = String::from(rv[1]);
             let stream_name = String::from(rv[2]);
 
-            let (http_response_data_producer, http_response_data_consumer) = unbounded();
+            let (http_response_data_producer, http_response_data_consumer) =
+                channel(HTTP_RESPONSE_BUFFER_SIZE);
+
+            let mut session_info = SessionInfo::new(Some(remote_addr));
+            session_info.user_agent = req
+                .headers()
+                .get(header::USER_AGENT)
+                .and_then(|value| value.to_str().ok())
+                .map(String::from);
 
             let mut flv_hanlder = HttpFlv::new(
                 app_name,
                 stream_name,
                 event_producer,
                 http_response_data_producer,
+                session_info,
             );
+            let start_mode = req.uri().query().map(parse_query).and_then(|params| {
+                params
+                    .get("start_mode")
+                    .and_then(|value| StartMode::parse(value))
+            });
+            flv_hanlder.set_start_mode(start_mode);
 
             tokio::spawn(async move {
                 if let Err(err) = flv_hanlder.run().await {
@@ -59,11 +82,12 @@ pub async fn run(event_producer: ChannelEventProducer, port: usize) -> Result<()
     let listen_address = format!("0.0.0.0:{port}");
     let sock_addr = listen_address.parse().unwrap();
 
-    let new_service = make_service_fn(move |_| {
+    let new_service = make_service_fn(move |conn: &AddrStream| {
         let flv_copy = event_producer.clone();
-        async {
+        let remote_addr = conn.remote_addr();
+        async move {
             Ok::<_, GenericError>(service_fn(move |req| {
-                handle_connection(req, flv_copy.clone())
+                handle_connection(req, flv_copy.clone(), remote_addr)
             }))
         }
     });
@@ -1,18 +1,48 @@
 use {
     super::{amf0_markers, errors::Amf0ReadErrorValue, Amf0ReadError, Amf0ValueType},
+    crate::amf3::{
+        amf3_reader::Amf3Reader,
+        define::{MAX_DECODED_SIZE, MAX_NESTING_DEPTH},
+        Amf3ValueType,
+    },
     byteorder::BigEndian,
-    // bytes::BytesMut,
+    bytes::BytesMut,
     bytesio::bytes_reader::BytesReader,
-    std::collections::HashMap,
+    std::{collections::HashMap, mem},
 };
 
 pub struct Amf0Reader {
     reader: BytesReader,
+    /* objects, ecma arrays, strict arrays and typed objects
+    which can be referenced by the following values, with the decoded
+    size of their values. A reference is decoded as a copy, it counts
+    towards the decoded size like the original, see MAX_DECODED_SIZE. */
+    reference_table: Vec<(Amf0ValueType, usize)>,
+    //the decoded size when each value was reserved
+    reference_starts: Vec<usize>,
+    decoded_size: usize,
+    depth: usize,
 }
 
 impl Amf0Reader {
     pub fn new(reader: BytesReader) -> Self {
-        Self { reader }
+        Self {
+            reader,
+            reference_table: Vec::new(),
+            reference_starts: Vec::new(),
+            decoded_size: 0,
+            depth: 0,
+        }
+    }
+
+    fn add_decoded_size(&mut self, size: usize) -> Result<(), Amf0ReadError> {
+        self.decoded_size += size;
+        if self.decoded_size > MAX_DECODED_SIZE {
+            return Err(Amf0ReadError {
+                value: Amf0ReadErrorValue::DecodedSizeExceeded,
+            });
+        }
+        Ok(())
     }
     pub fn read_all(&mut self) -> Result<Vec<Amf0ValueType>, Amf0ReadError> {
         let mut results = vec![];
@@ -42,6 +72,42 @@ impl Amf0Reader {
             return Ok(Amf0ValueType::END);
         }
 
+        self.add_decoded_size(mem::size_of::<Amf0ValueType>())?;
+        if self.depth >= MAX_NESTING_DEPTH {
+            return Err(Amf0ReadError {
+                value: Amf0ReadErrorValue::NestingTooDeep,
+            });
+        }
+        self.depth += 1;
+        let value = self.read_marked_value(markers);
+        self.depth -= 1;
+
+        value
+    }
+
+    /* The values inside an object or an array, the input may not end, or
+    an object end marker come, before them. */
+    fn read_element(&mut self) -> Result<Amf0ValueType, Amf0ReadError> {
+        match self.read_any()? {
+            Amf0ValueType::END => Err(Amf0ReadError {
+                value: Amf0ReadErrorValue::Truncated,
+            }),
+            value => Ok(value),
+        }
+    }
+
+    /* The count of an array is checked against the remaining input before
+    anything is read, each element takes at least element_size bytes. */
+    fn check_count(&mut self, count: u32, element_size: usize) -> Result<(), Amf0ReadError> {
+        if count as usize > self.reader.len() / element_size {
+            return Err(Amf0ReadError {
+                value: Amf0ReadErrorValue::Truncated,
+            });
+        }
+        Ok(())
+    }
+
+    fn read_marked_value(&mut self, markers: u8) -> Result<Amf0ValueType, Amf0ReadError> {
         match markers {
             amf0_markers::NUMBER => self.read_number(),
             amf0_markers::BOOLEAN => self.read_bool(),
@@ -50,6 +116,13 @@ impl Amf0Reader {
             amf0_markers::NULL => self.read_null(),
             amf0_markers::ECMA_ARRAY => self.read_ecma_array(),
             amf0_markers::LONG_STRING => self.read_long_string(),
+            amf0_markers::UNDEFINED => self.read_undefined(),
+            amf0_markers::REFERENCE => self.read_reference(),
+            amf0_markers::STRICT_ARRAY => self.read_strict_array(),
+            amf0_markers::DATE => self.read_date(),
+            amf0_markers::XML_DOCUMENT => self.read_xml_document(),
+            amf0_markers::TYPED_OBJECT => self.read_typed_object(),
+            amf0_markers::AVMPLUS_OBJECT => self.read_avmplus_object(),
             _ => Err(Amf0ReadError {
                 value: Amf0ReadErrorValue::UnknownMarker { marker: markers },
             }),
@@ -87,6 +160,7 @@ impl Amf0Reader {
 
         let bytes = self.reader.read_bytes(l as usize)?;
         let val = String::from_utf8(bytes.to_vec())?;
+        self.add_decoded_size(val.len())?;
 
         Ok(val)
     }
@@ -109,7 +183,20 @@ impl Amf0Reader {
         Ok(false)
     }
 
-    pub fn read_object(&mut self) -> Result<Amf0ValueType, Amf0ReadError> {
+    /*the complex value is added to the reference table before
+    its members are read, the indexes follow the order of appearance.*/
+    fn reserve_reference(&mut self) -> usize {
+        self.reference_table.push((Amf0ValueType::Null, 0));
+        self.reference_starts.push(self.decoded_size);
+        self.reference_table.len() - 1
+    }
+
+    fn save_reference(&mut self, index: usize, value: &Amf0ValueType) {
+        let size = self.decoded_size - self.reference_starts[index];
+        self.reference_table[index] = (value.clone(), size);
+    }
+
+    fn read_object_properties(&mut self) -> Result<HashMap<String, Amf0ValueType>, Amf0ReadError> {
         let mut properties = HashMap::new();
 
         loop {
@@ -120,28 +207,44 @@ impl Amf0Reader {
             }
 
             let key = self.read_raw_string()?;
-            let val = self.read_any()?;
+            let val = self.read_element()?;
 
             properties.insert(key, val);
         }
 
-        Ok(Amf0ValueType::Object(properties))
+        Ok(properties)
+    }
+
+    pub fn read_object(&mut self) -> Result<Amf0ValueType, Amf0ReadError> {
+        let index = self.reserve_reference();
+        let properties = self.read_object_properties()?;
+
+        let value = Amf0ValueType::Object(properties);
+        self.save_reference(index, &value);
+
+        Ok(value)
     }
 
     pub fn read_ecma_array(&mut self) -> Result<Amf0ValueType, Amf0ReadError> {
+        let index = self.reserve_reference();
         let len = self.reader.read_u32::<BigEndian>()?;
+        /*a key length and a value marker at least*/
+        self.check_count(len, 3)?;
 
         let mut properties = HashMap::new();
 
         for _ in 0..len {
             let key = self.read_raw_string()?;
-            let val = self.read_any()?;
+            let val = self.read_element()?;
             properties.insert(key, val);
         }
 
         self.is_read_object_eof()?;
 
-        Ok(Amf0ValueType::Object(properties))
+        let value = Amf0ValueType::EcmaArray(properties);
+        self.save_reference(index, &value);
+
+        Ok(value)
     }
 
     pub fn read_long_string(&mut self) -> Result<Amf0ValueType, Amf0ReadError> {
@@ -150,9 +253,130 @@ impl Amf0Reader {
         let buff = self.reader.read_bytes(l as usize)?;
 
         let val = String::from_utf8(buff.to_vec())?;
+        self.add_decoded_size(val.len())?;
         Ok(Amf0ValueType::LongUTF8String(val))
     }
 
+    pub fn read_undefined(&mut self) -> Result<Amf0ValueType, Amf0ReadError> {
+        Ok(Amf0ValueType::Undefined)
+    }
+
+    /*a reference is resolved to a copy of the value it points to*/
+    pub fn read_reference(&mut self) -> Result<Amf0ValueType, Amf0ReadError> {
+        let index = self.reader.read_u16::<BigEndian>()?;
+
+        let (val, size) = match self.reference_table.get(index as usize) {
+            Some((val, size)) => (val.clone(), *size),
+            None => {
+                return Err(Amf0ReadError {
+                    value: Amf0ReadErrorValue::InvalidReference { index },
+                })
+            }
+        };
+        self.add_decoded_size(size)?;
+
+        Ok(val)
+    }
+
+    pub fn read_strict_array(&mut self) -> Result<Amf0ValueType, Amf0ReadError> {
+        let index = self.reserve_reference();
+        let len = self.reader.read_u32::<BigEndian>()?;
+        self.check_count(len, 1)?;
+
+        let mut values = Vec::new();
+        for _ in 0..len {
+            values.push(self.read_element()?);
+        }
+
+        let value = Amf0ValueType::StrictArray(values);
+        self.save_reference(index, &value);
+
+        Ok(value)
+    }
+
+    pub fn read_date(&mut self) -> Result<Amf0ValueType, Amf0ReadError> {
+        let unix_time = self.reader.read_f64::<BigEndian>()?;
+        let time_zone = self.reader.read_u16::<BigEndian>()? as i16;
+
+        Ok(Amf0ValueType::Date {
+            unix_time,
+            time_zone,
+        })
+    }
+
+    pub fn read_xml_document(&mut self) -> Result<Amf0ValueType, Amf0ReadError> {
+        let l = self.reader.read_u32::<BigEndian>()?;
+
+        let buff = self.reader.read_bytes(l as usize)?;
+
+        let val = String::from_utf8(buff.to_vec())?;
+        self.add_decoded_size(val.len())?;
+        Ok(Amf0ValueType::XmlDocument(val))
+    }
+
+    pub fn read_typed_object(&mut self) -> Result<Amf0ValueType, Amf0ReadError> {
+        let index = self.reserve_reference();
+        let class_name = self.read_raw_string()?;
+        let properties = self.read_object_properties()?;
+
+        let value = Amf0ValueType::TypedObject {
+            class_name,
+            properties,
+        };
+        self.save_reference(index, &value);
+
+        Ok(value)
+    }
+
+    /*
+     2.3.1 AVM+ Type Marker: the following value is AMF3 encoded, each
+     switch starts with new reference tables. Values which have an AMF0
+     equivalent are converted, so that the callers need not care.
+    */
+    pub fn read_avmplus_object(&mut self) -> Result<Amf0ValueType, Amf0ReadError> {
+        let reader = mem::replace(&mut self.reader, BytesReader::new(BytesMut::new()));
+        let mut amf3_reader = Amf3Reader::new(reader);
+        let result = amf3_reader.read_any();
+        let decoded_size = amf3_reader.decoded_size();
+        self.reader = amf3_reader.into_reader();
+
+        let value = result?;
+        self.add_decoded_size(decoded_size)?;
+        Ok(Self::convert_amf3_value(value))
+    }
+
+    fn convert_amf3_value(value: Amf3ValueType) -> Amf0ValueType {
+        match value {
+            Amf3ValueType::Undefined | Amf3ValueType::Null => Amf0ValueType::Null,
+            Amf3ValueType::Boolean(val) => Amf0ValueType::Boolean(val),
+            Amf3ValueType::Integer(val) => Amf0ValueType::Number(val as f64),
+            Amf3ValueType::Double(val) => Amf0ValueType::Number(val),
+            Amf3ValueType::UTF8String(val) => Amf0ValueType::UTF8String(val),
+            Amf3ValueType::Object {
+                traits,
+                sealed,
+                dynamic,
+            } if !traits.is_externalizable => {
+                let mut properties = HashMap::new();
+                for (key, val) in traits.sealed_names.into_iter().zip(sealed) {
+                    properties.insert(key, Self::convert_amf3_value(val));
+                }
+                for (key, val) in dynamic {
+                    properties.insert(key, Self::convert_amf3_value(val));
+                }
+                Amf0ValueType::Object(properties)
+            }
+            Amf3ValueType::Array { assoc, dense } if dense.is_empty() => {
+                let properties = assoc
+                    .into_iter()
+                    .map(|(key, val)| (key, Self::convert_amf3_value(val)))
+                    .collect();
+                Amf0ValueType::EcmaArray(properties)
+            }
+            _ => Amf0ValueType::AVMPlusObject(Box::new(value)),
+        }
+    }
+
     // pub fn get_remaining_bytes(&mut self) -> BytesMut {
     //     return self.reader.get_remaining_bytes();
     // }
@@ -172,12 +396,15 @@ mod tests {
         println!("tsetstt")
     }
 
+    use super::super::amf0_writer::Amf0Writer;
     use super::amf0_markers;
     use super::Amf0Reader;
     use super::Amf0ValueType;
+    use crate::amf3::Amf3ValueType;
 
     use bytes::BytesMut;
     use bytesio::bytes_reader::BytesReader;
+    use bytesio::bytes_writer::BytesWriter;
 
     use std::collections::HashMap;
 
@@ -318,4 +545,188 @@ mod tests {
 
         assert_eq!(command_obj_raw.unwrap(), Amf0ValueType::Object(properties));
     }
+
+    #[test]
+    fn test_avmplus_object() {
+        //"onMetaData" followed by an AMF3 anonymous object {width: 1280} and a byte array
+        let data = [
+            2, 0, 10, b'o', b'n', b'M', b'e', b't', b'a', b'D', b'a', b't', b'a', //string
+            0x11, 0x0a, 0x0b, 0x01, 0x0b, b'w', b'i', b'd', b't', b'h', 0x04, 0x8a, 0x00, 0x01,
+            0x11, 0x0c, 0x05, 0xaa, 0xbb, //byte array
+            0, 0x3f, 0xf0, 0, 0, 0, 0, 0, 0, //number 1.0 after the switches
+        ];
+
+        let mut bytes_reader = BytesReader::new(BytesMut::new());
+        bytes_reader.extend_from_slice(&data);
+        let mut amf_reader = Amf0Reader::new(bytes_reader);
+
+        let mut properties = HashMap::new();
+        properties.insert(String::from("width"), Amf0ValueType::Number(1280.0));
+
+        assert_eq!(
+            amf_reader.read_all().unwrap(),
+            vec![
+                Amf0ValueType::UTF8String(String::from("onMetaData")),
+                Amf0ValueType::Object(properties),
+                Amf0ValueType::AVMPlusObject(Box::new(Amf3ValueType::ByteArray(vec![0xaa, 0xbb]))),
+                Amf0ValueType::Number(1.0),
+            ]
+        );
+    }
+
+    fn round_trip(values: &Vec<Amf0ValueType>) -> Vec<Amf0ValueType> {
+        let mut amf_writer = Amf0Writer::new(BytesWriter::new());
+        amf_writer.write_anys(values).unwrap();
+
+        let mut bytes_reader = BytesReader::new(BytesMut::new());
+        bytes_reader.extend_from_slice(&amf_writer.extract_current_bytes()[..]);
+        Amf0Reader::new(bytes_reader).read_all().unwrap()
+    }
+
+    #[test]
+    fn test_extended_types_round_trip() {
+        let mut properties = HashMap::new();
+        properties.insert(String::from("x"), Amf0ValueType::Number(1.0));
+        properties.insert(String::from("y"), Amf0ValueType::Undefined);
+
+        let values = vec![
+            Amf0ValueType::Undefined,
+            Amf0ValueType::StrictArray(vec![
+                Amf0ValueType::Number(0.0),
+                Amf0ValueType::UTF8String(String::from("keyframes")),
+                Amf0ValueType::Null,
+            ]),
+            Amf0ValueType::Date {
+                unix_time: 1_676_000_000_000.0,
+                time_zone: -60,
+            },
+            Amf0ValueType::XmlDocument(String::from("<x:xmpmeta/>")),
+            Amf0ValueType::LongUTF8String(String::from("long string")),
+            Amf0ValueType::TypedObject {
+                class_name: String::from("com.example.Point"),
+                properties: properties.clone(),
+            },
+            Amf0ValueType::EcmaArray(properties),
+        ];
+
+        assert_eq!(round_trip(&values), values);
+    }
+
+    #[test]
+    fn test_reference() {
+        let mut properties = HashMap::new();
+        properties.insert(String::from("a"), Amf0ValueType::Boolean(true));
+        let object = Amf0ValueType::Object(properties);
+        let array = Amf0ValueType::StrictArray(vec![object.clone()]);
+
+        //the strict array is index 0, the object inside it index 1
+        let values = vec![
+            array.clone(),
+            Amf0ValueType::Reference(1),
+            Amf0ValueType::Reference(0),
+        ];
+        assert_eq!(round_trip(&values), vec![array.clone(), object, array]);
+
+        //a reference to an unknown index is an error
+        let mut bytes_reader = BytesReader::new(BytesMut::new());
+        bytes_reader.extend_from_slice(&[amf0_markers::REFERENCE, 0, 3]);
+        assert!(Amf0Reader::new(bytes_reader).read_any().is_err());
+    }
+
+    #[test]
+    fn test_ecma_array_with_strict_array() {
+        //onMetaData as sent by hardware encoders, with keyframe positions in a strict array
+        let data = [
+            2, 0, 10, b'o', b'n', b'M', b'e', b't', b'a', b'D', b'a', b't', b'a', //string
+            8, 0, 0, 0, 1, //ecma array, 1 entry
+            0, 5, b't', b'i', b'm', b'e', b's', //key
+            10, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, //strict array [0.0]
+            0, 0, 9, //object end
+        ];
+
+        let mut bytes_reader = BytesReader::new(BytesMut::new());
+        bytes_reader.extend_from_slice(&data);
+        let values = Amf0Reader::new(bytes_reader).read_all().unwrap();
+
+        let mut properties = HashMap::new();
+        properties.insert(
+            String::from("times"),
+            Amf0ValueType::StrictArray(vec![Amf0ValueType::Number(0.0)]),
+        );
+        assert_eq!(
+            values,
+            vec![
+                Amf0ValueType::UTF8String(String::from("onMetaData")),
+                Amf0ValueType::EcmaArray(properties),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_decoded_size_limit() {
+        use super::super::errors::Amf0ReadErrorValue;
+
+        /*each strict array holds two references to the previous one, which
+        doubles the decoded size of every level*/
+        let mut data = vec![amf0_markers::STRICT_ARRAY, 0, 0, 0, 0];
+        for index in 0..60u8 {
+            data.extend_from_slice(&[amf0_markers::STRICT_ARRAY, 0, 0, 0, 2]);
+            data.extend_from_slice(&[amf0_markers::REFERENCE, 0, index]);
+            data.extend_from_slice(&[amf0_markers::REFERENCE, 0, index]);
+        }
+        let mut bytes_reader = BytesReader::new(BytesMut::new());
+        bytes_reader.extend_from_slice(&data);
+        assert!(matches!(
+            Amf0Reader::new(bytes_reader).read_all(),
+            Err(super::Amf0ReadError {
+                value: Amf0ReadErrorValue::DecodedSizeExceeded
+            })
+        ));
+
+        /*strict arrays nested in each other*/
+        let data = [amf0_markers::STRICT_ARRAY, 0, 0, 0, 1].repeat(100);
+        let mut bytes_reader = BytesReader::new(BytesMut::new());
+        bytes_reader.extend_from_slice(&data);
+        assert!(matches!(
+            Amf0Reader::new(bytes_reader).read_any(),
+            Err(super::Amf0ReadError {
+                value: Amf0ReadErrorValue::NestingTooDeep
+            })
+        ));
+    }
+
+    #[test]
+    fn test_truncated_containers() {
+        use super::super::errors::Amf0ReadErrorValue;
+
+        let inputs: [&[u8]; 5] = [
+            /*a strict array of 2^32 - 1 values without any*/
+            &[amf0_markers::STRICT_ARRAY, 0xff, 0xff, 0xff, 0xff],
+            /*a strict array of two values with one*/
+            &[amf0_markers::STRICT_ARRAY, 0, 0, 0, 2, amf0_markers::NULL],
+            /*a strict array of one value ended by an object end marker*/
+            &[
+                amf0_markers::STRICT_ARRAY,
+                0,
+                0,
+                0,
+                1,
+                amf0_markers::OBJECT_END,
+            ],
+            /*an ecma array of 2^32 - 1 entries without any*/
+            &[amf0_markers::ECMA_ARRAY, 0xff, 0xff, 0xff, 0xff],
+            /*an object with a key and no value*/
+            &[amf0_markers::OBJECT, 0, 1, b'a'],
+        ];
+        for data in inputs {
+            let mut bytes_reader = BytesReader::new(BytesMut::new());
+            bytes_reader.extend_from_slice(data);
+            assert!(matches!(
+                Amf0Reader::new(bytes_reader).read_any(),
+                Err(super::Amf0ReadError {
+                    value: Amf0ReadErrorValue::Truncated
+                })
+            ));
+        }
+    }
 }
@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use {crate::amf3::Amf3ValueType, std::collections::HashMap};
 #[derive(PartialEq, Clone, Debug)]
 pub enum Amf0ValueType {
     Number(f64),
@@ -8,6 +8,22 @@ pub enum Amf0ValueType {
     Null,
     EcmaArray(HashMap<String, Amf0ValueType>),
     LongUTF8String(String),
+    Undefined,
+    // index of a previous object, ecma array, strict array or typed object
+    Reference(u16),
+    StrictArray(Vec<Amf0ValueType>),
+    // milliseconds since the epoch, the time zone is reserved and should be 0
+    Date {
+        unix_time: f64,
+        time_zone: i16,
+    },
+    XmlDocument(String),
+    TypedObject {
+        class_name: String,
+        properties: HashMap<String, Amf0ValueType>,
+    },
+    // a value switched to AMF3, kept when it has no AMF0 equivalent
+    AVMPlusObject(Box<Amf3ValueType>),
     END,
 }
 
@@ -1,4 +1,5 @@
 use {
+    crate::amf3::{Amf3ReadError, Amf3WriteError},
     bytesio::bytes_errors::{BytesReadError, BytesWriteError},
     failure::{Backtrace, Fail},
     std::{
@@ -16,6 +17,16 @@ pub enum Amf0ReadErrorValue {
     BytesReadError(BytesReadError),
     #[fail(display = "wrong type")]
     WrongType,
+    #[fail(display = "invalid reference index: {}\n", index)]
+    InvalidReference { index: u16 },
+    #[fail(display = "amf3 read error: {}\n", _0)]
+    Amf3ReadError(Amf3ReadError),
+    #[fail(display = "decoded values exceed the size limit\n")]
+    DecodedSizeExceeded,
+    #[fail(display = "values are nested too deep\n")]
+    NestingTooDeep,
+    #[fail(display = "the input ends inside a value\n")]
+    Truncated,
 }
 
 #[derive(Debug)]
@@ -39,6 +50,14 @@ impl From<BytesReadError> for Amf0ReadError {
     }
 }
 
+impl From<Amf3ReadError> for Amf0ReadError {
+    fn from(error: Amf3ReadError) -> Self {
+        Amf0ReadError {
+            value: Amf0ReadErrorValue::Amf3ReadError(error),
+        }
+    }
+}
+
 #[derive(Debug, Fail)]
 pub enum Amf0WriteErrorValue {
     #[fail(display = "normal string too long")]
@@ -47,6 +66,8 @@ pub enum Amf0WriteErrorValue {
     BufferWriteError(io::Error),
     #[fail(display = "bytes write error\n")]
     BytesWriteError(BytesWriteError),
+    #[fail(display = "amf3 write error: {}\n", _0)]
+    Amf3WriteError(Amf3WriteError),
 }
 
 #[derive(Debug)]
@@ -70,6 +91,14 @@ impl From<BytesWriteError> for Amf0WriteError {
     }
 }
 
+impl From<Amf3WriteError> for Amf0WriteError {
+    fn from(error: Amf3WriteError) -> Self {
+        Amf0WriteError {
+            value: Amf0WriteErrorValue::Amf3WriteError(error),
+        }
+    }
+}
+
 impl fmt::Display for Amf0ReadError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         fmt::Display::fmt(&self.value, f)
@@ -0,0 +1,783 @@
+use {
+    super::{
+        amf3_markers,
+        define::{self, Amf3Trait},
+        errors::Amf3ReadErrorValue,
+        Amf3ReadError, Amf3ValueType,
+    },
+    byteorder::BigEndian,
+    bytes::BytesMut,
+    bytesio::bytes_reader::BytesReader,
+    std::{collections::HashMap, mem},
+};
+
+/*
+ AMF3 keeps three reference tables while decoding, complex values
+ which appear again are sent as an index into the corresponding table:
+   - strings (empty strings are never referenced)
+   - objects (date, array, object, xml, byte array, vector and dictionary)
+   - traits (the class descriptions of objects)
+ A referenced object is returned as a copy of the value in the table,
+ which counts towards the decoded size like the original.
+*/
+pub struct Amf3Reader {
+    reader: BytesReader,
+    string_table: Vec<String>,
+    //an object and the decoded size of its values
+    object_table: Vec<(Amf3ValueType, usize)>,
+    //the decoded size when each object was reserved
+    object_starts: Vec<usize>,
+    trait_table: Vec<Amf3Trait>,
+    decoded_size: usize,
+    depth: usize,
+}
+
+impl Amf3Reader {
+    pub fn new(reader: BytesReader) -> Self {
+        Self {
+            reader,
+            string_table: Vec::new(),
+            object_table: Vec::new(),
+            object_starts: Vec::new(),
+            trait_table: Vec::new(),
+            decoded_size: 0,
+            depth: 0,
+        }
+    }
+
+    /* The size of the values decoded so far, references included. */
+    pub fn decoded_size(&self) -> usize {
+        self.decoded_size
+    }
+
+    fn add_decoded_size(&mut self, size: usize) -> Result<(), Amf3ReadError> {
+        self.decoded_size += size;
+        if self.decoded_size > define::MAX_DECODED_SIZE {
+            return Err(Amf3ReadError {
+                value: Amf3ReadErrorValue::DecodedSizeExceeded,
+            });
+        }
+        Ok(())
+    }
+
+    //give back the underlying reader, used when AMF3 is embedded in AMF0
+    pub fn into_reader(self) -> BytesReader {
+        self.reader
+    }
+
+    pub fn read_all(&mut self) -> Result<Vec<Amf3ValueType>, Amf3ReadError> {
+        let mut results = vec![];
+
+        loop {
+            let result = self.read_any()?;
+
+            match result {
+                Amf3ValueType::END => {
+                    break;
+                }
+                _ => {
+                    results.push(result);
+                }
+            }
+        }
+
+        Ok(results)
+    }
+
+    pub fn read_any(&mut self) -> Result<Amf3ValueType, Amf3ReadError> {
+        if self.reader.is_empty() {
+            return Ok(Amf3ValueType::END);
+        }
+        let marker = self.reader.read_u8()?;
+
+        self.add_decoded_size(mem::size_of::<Amf3ValueType>())?;
+        if self.depth >= define::MAX_NESTING_DEPTH {
+            return Err(Amf3ReadError {
+                value: Amf3ReadErrorValue::NestingTooDeep,
+            });
+        }
+        self.depth += 1;
+        let value = self.read_marked_value(marker);
+        self.depth -= 1;
+
+        value
+    }
+
+    /* The values inside an array, an object, a vector or a dictionary, the
+    input may not end before them. */
+    fn read_element(&mut self) -> Result<Amf3ValueType, Amf3ReadError> {
+        if self.reader.is_empty() {
+            return Err(Amf3ReadError {
+                value: Amf3ReadErrorValue::Truncated,
+            });
+        }
+        self.read_any()
+    }
+
+    /* The count of a container is checked against the remaining input before
+    anything is read, each element takes at least element_size bytes. */
+    fn check_count(&mut self, count: usize, element_size: usize) -> Result<(), Amf3ReadError> {
+        if count > self.reader.len() / element_size {
+            return Err(Amf3ReadError {
+                value: Amf3ReadErrorValue::Truncated,
+            });
+        }
+        Ok(())
+    }
+
+    fn read_marked_value(&mut self, marker: u8) -> Result<Amf3ValueType, Amf3ReadError> {
+        match marker {
+            amf3_markers::UNDEFINED => Ok(Amf3ValueType::Undefined),
+            amf3_markers::NULL => Ok(Amf3ValueType::Null),
+            amf3_markers::FALSE => Ok(Amf3ValueType::Boolean(false)),
+            amf3_markers::TRUE => Ok(Amf3ValueType::Boolean(true)),
+            amf3_markers::INTEGER => self.read_integer(),
+            amf3_markers::DOUBLE => self.read_double(),
+            amf3_markers::STRING => Ok(Amf3ValueType::UTF8String(self.read_raw_string()?)),
+            amf3_markers::XML_DOC => self.read_xml(true),
+            amf3_markers::DATE => self.read_date(),
+            amf3_markers::ARRAY => self.read_array(),
+            amf3_markers::OBJECT => self.read_object(),
+            amf3_markers::XML => self.read_xml(false),
+            amf3_markers::BYTE_ARRAY => self.read_byte_array(),
+            amf3_markers::VECTOR_INT
+            | amf3_markers::VECTOR_UINT
+            | amf3_markers::VECTOR_DOUBLE
+            | amf3_markers::VECTOR_OBJECT => self.read_vector(marker),
+            amf3_markers::DICTIONARY => self.read_dictionary(),
+            _ => Err(Amf3ReadError {
+                value: Amf3ReadErrorValue::UnknownMarker { marker },
+            }),
+        }
+    }
+
+    /*
+     1.3.1 Variable Length Unsigned 29-bit Integer Encoding
+     The first 3 bytes use the high bit as a continuation flag,
+     the fourth byte contributes all of its 8 bits.
+    */
+    pub fn read_u29(&mut self) -> Result<u32, Amf3ReadError> {
+        let mut result: u32 = 0;
+
+        for index in 0..4 {
+            let byte = self.reader.read_u8()?;
+
+            if index == 3 {
+                result = (result << 8) | byte as u32;
+                break;
+            }
+
+            result = (result << 7) | (byte & 0x7f) as u32;
+            if byte & 0x80 == 0 {
+                break;
+            }
+        }
+
+        Ok(result)
+    }
+
+    pub fn read_integer(&mut self) -> Result<Amf3ValueType, Amf3ReadError> {
+        let mut value = self.read_u29()? as i32;
+        //sign extend the 29 bits value
+        if value > define::INTEGER_MAX {
+            value -= 1 << 29;
+        }
+        Ok(Amf3ValueType::Integer(value))
+    }
+
+    pub fn read_double(&mut self) -> Result<Amf3ValueType, Amf3ReadError> {
+        let number = self.reader.read_f64::<BigEndian>()?;
+        Ok(Amf3ValueType::Double(number))
+    }
+
+    pub fn read_raw_string(&mut self) -> Result<String, Amf3ReadError> {
+        let header = self.read_u29()? as usize;
+
+        if header & 0x01 == 0 {
+            let index = header >> 1;
+            let val = match self.string_table.get(index) {
+                Some(val) => val.clone(),
+                None => {
+                    return Err(Amf3ReadError {
+                        value: Amf3ReadErrorValue::InvalidReference { index },
+                    })
+                }
+            };
+            self.add_decoded_size(val.len())?;
+            return Ok(val);
+        }
+
+        let length = header >> 1;
+        let bytes = self.reader.read_bytes(length)?;
+        let val = String::from_utf8(bytes.to_vec())?;
+        self.add_decoded_size(val.len())?;
+
+        if !val.is_empty() {
+            self.string_table.push(val.clone());
+        }
+
+        Ok(val)
+    }
+
+    /*read the U29 header of a complex value, return the value directly if
+    it is a reference, or the length/flags bits otherwise.*/
+    fn read_object_header(&mut self) -> Result<Result<usize, Amf3ValueType>, Amf3ReadError> {
+        let header = self.read_u29()? as usize;
+
+        if header & 0x01 == 0 {
+            let index = header >> 1;
+            let (val, size) = match self.object_table.get(index) {
+                Some((val, size)) => (val.clone(), *size),
+                None => {
+                    return Err(Amf3ReadError {
+                        value: Amf3ReadErrorValue::InvalidReference { index },
+                    })
+                }
+            };
+            self.add_decoded_size(size)?;
+            return Ok(Err(val));
+        }
+
+        Ok(Ok(header >> 1))
+    }
+
+    /*the object is added to the table before its members are read,
+    so that the reference indexes match the sender's.*/
+    fn reserve_object(&mut self) -> usize {
+        self.object_table.push((Amf3ValueType::Undefined, 0));
+        self.object_starts.push(self.decoded_size);
+        self.object_table.len() - 1
+    }
+
+    fn save_object(&mut self, index: usize, value: &Amf3ValueType) {
+        let size = self.decoded_size - self.object_starts[index];
+        self.object_table[index] = (value.clone(), size);
+    }
+
+    pub fn read_xml(&mut self, is_document: bool) -> Result<Amf3ValueType, Amf3ReadError> {
+        let length = match self.read_object_header()? {
+            Ok(length) => length,
+            Err(reference) => return Ok(reference),
+        };
+
+        let bytes = self.reader.read_bytes(length)?;
+        let content = String::from_utf8(bytes.to_vec())?;
+
+        let value = if is_document {
+            Amf3ValueType::XmlDocument(content)
+        } else {
+            Amf3ValueType::Xml(content)
+        };
+        let index = self.reserve_object();
+        self.add_decoded_size(length)?;
+        self.save_object(index, &value);
+
+        Ok(value)
+    }
+
+    pub fn read_date(&mut self) -> Result<Amf3ValueType, Amf3ReadError> {
+        if let Err(reference) = self.read_object_header()? {
+            return Ok(reference);
+        }
+
+        let value = Amf3ValueType::Date(self.reader.read_f64::<BigEndian>()?);
+        let index = self.reserve_object();
+        self.save_object(index, &value);
+
+        Ok(value)
+    }
+
+    pub fn read_array(&mut self) -> Result<Amf3ValueType, Amf3ReadError> {
+        let dense_count = match self.read_object_header()? {
+            Ok(count) => count,
+            Err(reference) => return Ok(reference),
+        };
+        let index = self.reserve_object();
+
+        let mut assoc = HashMap::new();
+        loop {
+            let key = self.read_raw_string()?;
+            if key.is_empty() {
+                break;
+            }
+            let val = self.read_element()?;
+            assoc.insert(key, val);
+        }
+
+        self.check_count(dense_count, 1)?;
+        let mut dense = Vec::new();
+        for _ in 0..dense_count {
+            dense.push(self.read_element()?);
+        }
+
+        let value = Amf3ValueType::Array { assoc, dense };
+        self.save_object(index, &value);
+
+        Ok(value)
+    }
+
+    fn read_traits(&mut self, header: usize) -> Result<Amf3Trait, Amf3ReadError> {
+        //U29O-traits-ref
+        if header & 0x01 == 0 {
+            let index = header >> 1;
+            let traits = match self.trait_table.get(index) {
+                Some(val) => val.clone(),
+                None => {
+                    return Err(Amf3ReadError {
+                        value: Amf3ReadErrorValue::InvalidReference { index },
+                    })
+                }
+            };
+            let size = traits.class_name.len()
+                + traits.sealed_names.iter().map(String::len).sum::<usize>();
+            self.add_decoded_size(size)?;
+            return Ok(traits);
+        }
+
+        let is_externalizable = header & 0x02 != 0;
+        let is_dynamic = header & 0x04 != 0;
+        let sealed_count = header >> 3;
+
+        let class_name = self.read_raw_string()?;
+        self.check_count(sealed_count, 1)?;
+        let mut sealed_names = Vec::new();
+        for _ in 0..sealed_count {
+            sealed_names.push(self.read_raw_string()?);
+        }
+
+        let traits = Amf3Trait {
+            class_name,
+            is_dynamic,
+            is_externalizable,
+            sealed_names,
+        };
+        self.trait_table.push(traits.clone());
+
+        Ok(traits)
+    }
+
+    pub fn read_object(&mut self) -> Result<Amf3ValueType, Amf3ReadError> {
+        let header = match self.read_object_header()? {
+            Ok(header) => header,
+            Err(reference) => return Ok(reference),
+        };
+        let index = self.reserve_object();
+        let traits = self.read_traits(header)?;
+
+        let mut sealed = Vec::new();
+        let mut dynamic = HashMap::new();
+
+        if traits.is_externalizable {
+            match traits.class_name.as_str() {
+                define::FLEX_ARRAY_COLLECTION
+                | define::FLEX_ARRAY_LIST
+                | define::FLEX_OBJECT_PROXY => {
+                    sealed.push(self.read_element()?);
+                }
+                _ => {
+                    return Err(Amf3ReadError {
+                        value: Amf3ReadErrorValue::UnsupportedExternalizable {
+                            class_name: traits.class_name,
+                        },
+                    });
+                }
+            }
+        } else {
+            for _ in 0..traits.sealed_names.len() {
+                sealed.push(self.read_element()?);
+            }
+
+            if traits.is_dynamic {
+                loop {
+                    let key = self.read_raw_string()?;
+                    if key.is_empty() {
+                        break;
+                    }
+                    let val = self.read_element()?;
+                    dynamic.insert(key, val);
+                }
+            }
+        }
+
+        let value = Amf3ValueType::Object {
+            traits,
+            sealed,
+            dynamic,
+        };
+        self.save_object(index, &value);
+
+        Ok(value)
+    }
+
+    pub fn read_byte_array(&mut self) -> Result<Amf3ValueType, Amf3ReadError> {
+        let length = match self.read_object_header()? {
+            Ok(length) => length,
+            Err(reference) => return Ok(reference),
+        };
+
+        let bytes = self.reader.read_bytes(length)?;
+        let value = Amf3ValueType::ByteArray(bytes.to_vec());
+        let index = self.reserve_object();
+        self.add_decoded_size(length)?;
+        self.save_object(index, &value);
+
+        Ok(value)
+    }
+
+    pub fn read_vector(&mut self, marker: u8) -> Result<Amf3ValueType, Amf3ReadError> {
+        let count = match self.read_object_header()? {
+            Ok(count) => count,
+            Err(reference) => return Ok(reference),
+        };
+        let index = self.reserve_object();
+        let fixed = self.reader.read_u8()? != 0;
+        let element_size = match marker {
+            amf3_markers::VECTOR_INT | amf3_markers::VECTOR_UINT => 4,
+            amf3_markers::VECTOR_DOUBLE => 8,
+            _ => 1,
+        };
+        self.check_count(count, element_size)?;
+        if marker != amf3_markers::VECTOR_OBJECT {
+            /*the elements are 4 or 8 bytes long*/
+            self.add_decoded_size(count.saturating_mul(8))?;
+        }
+
+        let value = match marker {
+            amf3_markers::VECTOR_INT => {
+                let mut values = Vec::new();
+                for _ in 0..count {
+                    values.push(self.reader.read_u32::<BigEndian>()? as i32);
+                }
+                Amf3ValueType::VectorInt { fixed, values }
+            }
+            amf3_markers::VECTOR_UINT => {
+                let mut values = Vec::new();
+                for _ in 0..count {
+                    values.push(self.reader.read_u32::<BigEndian>()?);
+                }
+                Amf3ValueType::VectorUInt { fixed, values }
+            }
+            amf3_markers::VECTOR_DOUBLE => {
+                let mut values = Vec::new();
+                for _ in 0..count {
+                    values.push(self.reader.read_f64::<BigEndian>()?);
+                }
+                Amf3ValueType::VectorDouble { fixed, values }
+            }
+            _ => {
+                let type_name = self.read_raw_string()?;
+                let mut values = Vec::new();
+                for _ in 0..count {
+                    values.push(self.read_element()?);
+                }
+                Amf3ValueType::VectorObject {
+                    fixed,
+                    type_name,
+                    values,
+                }
+            }
+        };
+        self.save_object(index, &value);
+
+        Ok(value)
+    }
+
+    pub fn read_dictionary(&mut self) -> Result<Amf3ValueType, Amf3ReadError> {
+        let count = match self.read_object_header()? {
+            Ok(count) => count,
+            Err(reference) => return Ok(reference),
+        };
+        let index = self.reserve_object();
+        let weak_keys = self.reader.read_u8()? != 0;
+
+        self.check_count(count, 2)?;
+        let mut entries = Vec::new();
+        for _ in 0..count {
+            let key = self.read_element()?;
+            let val = self.read_element()?;
+            entries.push((key, val));
+        }
+
+        let value = Amf3ValueType::Dictionary { weak_keys, entries };
+        self.save_object(index, &value);
+
+        Ok(value)
+    }
+
+    pub fn get_remaining_bytes(&mut self) -> BytesMut {
+        self.reader.get_remaining_bytes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::super::amf3_writer::Amf3Writer;
+    use super::super::define::Amf3Trait;
+    use super::Amf3Reader;
+    use super::Amf3ValueType;
+
+    use bytes::BytesMut;
+    use bytesio::bytes_reader::BytesReader;
+    use bytesio::bytes_writer::BytesWriter;
+
+    use std::collections::HashMap;
+
+    fn read_from(data: &[u8]) -> Amf3Reader {
+        let mut bytes_reader = BytesReader::new(BytesMut::new());
+        bytes_reader.extend_from_slice(data);
+        Amf3Reader::new(bytes_reader)
+    }
+
+    fn round_trip(value: Amf3ValueType) -> Amf3ValueType {
+        let mut amf_writer = Amf3Writer::new(BytesWriter::new());
+        amf_writer.write_any(&value).unwrap();
+        let data = amf_writer.extract_current_bytes();
+
+        read_from(&data[..]).read_any().unwrap()
+    }
+
+    #[test]
+    fn test_u29() {
+        let mut amf_reader = read_from(&[0x7f, 0x81, 0x00, 0xff, 0xff, 0xff, 0xff]);
+        assert_eq!(amf_reader.read_u29().unwrap(), 0x7f);
+        assert_eq!(amf_reader.read_u29().unwrap(), 0x80);
+        assert_eq!(amf_reader.read_u29().unwrap(), 0x1fff_ffff);
+
+        for value in [
+            0,
+            0x7f,
+            0x80,
+            0x3fff,
+            0x4000,
+            0x1f_ffff,
+            0x20_0000,
+            0x1fff_ffff,
+        ] {
+            let mut amf_writer = Amf3Writer::new(BytesWriter::new());
+            amf_writer.write_u29(value).unwrap();
+            let data = amf_writer.extract_current_bytes();
+            assert_eq!(read_from(&data[..]).read_u29().unwrap(), value);
+        }
+    }
+
+    #[test]
+    fn test_integer() {
+        //0x04 marker, -1 is encoded as 0x1fffffff
+        let mut amf_reader = read_from(&[0x04, 0xff, 0xff, 0xff, 0xff]);
+        assert_eq!(amf_reader.read_any().unwrap(), Amf3ValueType::Integer(-1));
+
+        for value in [0, 1, -1, 0x0fff_ffff, -0x1000_0000] {
+            assert_eq!(
+                round_trip(Amf3ValueType::Integer(value)),
+                Amf3ValueType::Integer(value)
+            );
+        }
+        //out of the 29 bits range, it is written as a double
+        assert_eq!(
+            round_trip(Amf3ValueType::Integer(i32::MAX)),
+            Amf3ValueType::Double(i32::MAX as f64)
+        );
+    }
+
+    #[test]
+    fn test_string_reference() {
+        //"live", then a reference to string 0
+        let data = [0x06, 0x09, b'l', b'i', b'v', b'e', 0x06, 0x00];
+        let mut amf_reader = read_from(&data);
+        let values = amf_reader.read_all().unwrap();
+
+        assert_eq!(
+            values,
+            vec![
+                Amf3ValueType::UTF8String(String::from("live")),
+                Amf3ValueType::UTF8String(String::from("live"))
+            ]
+        );
+    }
+
+    #[test]
+    fn test_anonymous_object() {
+        //{app: "live"} as an anonymous dynamic object
+        let data = [
+            0x0a, 0x0b, 0x01, 0x07, b'a', b'p', b'p', 0x06, 0x09, b'l', b'i', b'v', b'e', 0x01,
+        ];
+        let mut amf_reader = read_from(&data);
+
+        let mut properties = HashMap::new();
+        properties.insert(
+            String::from("app"),
+            Amf3ValueType::UTF8String(String::from("live")),
+        );
+        assert_eq!(
+            amf_reader.read_any().unwrap(),
+            Amf3ValueType::new_object(properties)
+        );
+    }
+
+    #[test]
+    fn test_typed_object_round_trip() {
+        let traits = Amf3Trait {
+            class_name: String::from("com.example.Point"),
+            is_dynamic: false,
+            is_externalizable: false,
+            sealed_names: vec![String::from("x"), String::from("y")],
+        };
+        let point = Amf3ValueType::Object {
+            traits,
+            sealed: vec![Amf3ValueType::Integer(1), Amf3ValueType::Double(2.5)],
+            dynamic: HashMap::new(),
+        };
+
+        //the second point is written with string and traits references
+        let mut amf_writer = Amf3Writer::new(BytesWriter::new());
+        amf_writer.write_any(&point).unwrap();
+        let first_len = amf_writer.len();
+        amf_writer.write_any(&point).unwrap();
+        assert!(amf_writer.len() - first_len < first_len);
+
+        let data = amf_writer.extract_current_bytes();
+        let values = read_from(&data[..]).read_all().unwrap();
+        assert_eq!(values, vec![point.clone(), point]);
+    }
+
+    #[test]
+    fn test_object_reference() {
+        //[d, ref 0] where d is a date, the second value references the date.
+        let mut data = vec![0x09, 0x05, 0x01, 0x08, 0x01];
+        data.extend_from_slice(&1_000.0_f64.to_be_bytes());
+        data.extend_from_slice(&[0x08, 0x02]);
+
+        let mut amf_reader = read_from(&data[..]);
+        assert_eq!(
+            amf_reader.read_any().unwrap(),
+            Amf3ValueType::Array {
+                assoc: HashMap::new(),
+                dense: vec![Amf3ValueType::Date(1_000.0), Amf3ValueType::Date(1_000.0)],
+            }
+        );
+    }
+
+    #[test]
+    fn test_complex_round_trip() {
+        let mut assoc = HashMap::new();
+        assoc.insert(String::from("name"), Amf3ValueType::Null);
+
+        let values = vec![
+            Amf3ValueType::Undefined,
+            Amf3ValueType::Boolean(true),
+            Amf3ValueType::UTF8String(String::new()),
+            Amf3ValueType::XmlDocument(String::from("<a/>")),
+            Amf3ValueType::Xml(String::from("<b/>")),
+            Amf3ValueType::Date(1_676_000_000_000.0),
+            Amf3ValueType::Array {
+                assoc,
+                dense: vec![Amf3ValueType::Integer(3)],
+            },
+            Amf3ValueType::ByteArray(vec![0, 1, 2, 3]),
+            Amf3ValueType::VectorInt {
+                fixed: true,
+                values: vec![-1, 2],
+            },
+            Amf3ValueType::VectorUInt {
+                fixed: false,
+                values: vec![u32::MAX],
+            },
+            Amf3ValueType::VectorDouble {
+                fixed: false,
+                values: vec![0.5],
+            },
+            Amf3ValueType::VectorObject {
+                fixed: false,
+                type_name: String::from("String"),
+                values: vec![Amf3ValueType::UTF8String(String::from("a"))],
+            },
+            Amf3ValueType::Dictionary {
+                weak_keys: false,
+                entries: vec![(
+                    Amf3ValueType::UTF8String(String::from("k")),
+                    Amf3ValueType::Integer(7),
+                )],
+            },
+        ];
+
+        for value in values {
+            assert_eq!(round_trip(value.clone()), value);
+        }
+    }
+
+    #[test]
+    fn test_externalizable_array_collection() {
+        let collection = Amf3ValueType::Object {
+            traits: Amf3Trait {
+                class_name: String::from(super::define::FLEX_ARRAY_COLLECTION),
+                is_dynamic: false,
+                is_externalizable: true,
+                sealed_names: Vec::new(),
+            },
+            sealed: vec![Amf3ValueType::Array {
+                assoc: HashMap::new(),
+                dense: vec![Amf3ValueType::Integer(1)],
+            }],
+            dynamic: HashMap::new(),
+        };
+
+        assert_eq!(round_trip(collection.clone()), collection);
+    }
+
+    #[test]
+    fn test_decoded_size_limit() {
+        use super::super::errors::Amf3ReadErrorValue;
+
+        /*each array holds two references to the previous one, which doubles
+        the decoded size of every level*/
+        let levels = 60;
+        let mut data = vec![0x09, ((levels << 1) | 0x01) as u8, 0x01];
+        data.extend_from_slice(&[0x09, 0x01, 0x01]);
+        for index in 1..levels {
+            data.extend_from_slice(&[0x09, 0x05, 0x01]);
+            data.extend_from_slice(&[0x09, (index << 1) as u8, 0x09, (index << 1) as u8]);
+        }
+        assert!(matches!(
+            read_from(&data[..]).read_any(),
+            Err(super::Amf3ReadError {
+                value: Amf3ReadErrorValue::DecodedSizeExceeded
+            })
+        ));
+
+        /*arrays nested in each other*/
+        let data = [0x09, 0x03, 0x01].repeat(100);
+        assert!(matches!(
+            read_from(&data[..]).read_any(),
+            Err(super::Amf3ReadError {
+                value: Amf3ReadErrorValue::NestingTooDeep
+            })
+        ));
+    }
+
+    #[test]
+    fn test_truncated_containers() {
+        use super::super::errors::Amf3ReadErrorValue;
+
+        let inputs: [&[u8]; 5] = [
+            /*an array of 2^28 - 1 values without any*/
+            &[0x09, 0xbf, 0xff, 0xff, 0xff, 0x01],
+            /*an array of two values with one*/
+            &[0x09, 0x05, 0x01, 0x01],
+            /*a vector of 2^28 - 1 ints*/
+            &[0x0d, 0xbf, 0xff, 0xff, 0xff, 0x00],
+            /*a dictionary of one entry without a value*/
+            &[0x11, 0x03, 0x00, 0x01],
+            /*an object with a sealed member and no value*/
+            &[0x0a, 0x13, 0x01, 0x03, 0x61],
+        ];
+        for data in inputs {
+            assert!(matches!(
+                read_from(data).read_any(),
+                Err(super::Amf3ReadError {
+                    value: Amf3ReadErrorValue::Truncated
+                })
+            ));
+        }
+    }
+}
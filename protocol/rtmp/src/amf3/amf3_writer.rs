@@ -0,0 +1,303 @@
+use {
+    super::{
+        amf3_markers,
+        define::{self, Amf3Trait},
+        errors::Amf3WriteErrorValue,
+        Amf3ValueType, Amf3WriteError,
+    },
+    byteorder::BigEndian,
+    bytes::BytesMut,
+    bytesio::bytes_writer::BytesWriter,
+    std::collections::HashMap,
+};
+
+/*
+ The writer references repeated strings and traits, complex objects
+ are always written inline.
+*/
+pub struct Amf3Writer {
+    writer: BytesWriter,
+    string_table: HashMap<String, usize>,
+    trait_table: Vec<Amf3Trait>,
+}
+
+impl Amf3Writer {
+    pub fn new(writer: BytesWriter) -> Self {
+        Self {
+            writer,
+            string_table: HashMap::new(),
+            trait_table: Vec::new(),
+        }
+    }
+
+    pub fn write_anys(&mut self, values: &Vec<Amf3ValueType>) -> Result<(), Amf3WriteError> {
+        for val in values {
+            self.write_any(val)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn write_any(&mut self, value: &Amf3ValueType) -> Result<(), Amf3WriteError> {
+        match value {
+            Amf3ValueType::Undefined => self.write_marker(amf3_markers::UNDEFINED),
+            Amf3ValueType::Null => self.write_marker(amf3_markers::NULL),
+            Amf3ValueType::Boolean(val) => self.write_bool(*val),
+            Amf3ValueType::Integer(val) => self.write_integer(*val),
+            Amf3ValueType::Double(val) => self.write_double(*val),
+            Amf3ValueType::UTF8String(val) => {
+                self.write_marker(amf3_markers::STRING)?;
+                self.write_raw_string(val)
+            }
+            Amf3ValueType::XmlDocument(val) => self.write_xml(amf3_markers::XML_DOC, val),
+            Amf3ValueType::Date(val) => self.write_date(*val),
+            Amf3ValueType::Array { assoc, dense } => self.write_array(assoc, dense),
+            Amf3ValueType::Object {
+                traits,
+                sealed,
+                dynamic,
+            } => self.write_object(traits, sealed, dynamic),
+            Amf3ValueType::Xml(val) => self.write_xml(amf3_markers::XML, val),
+            Amf3ValueType::ByteArray(val) => self.write_byte_array(val),
+            Amf3ValueType::VectorInt { fixed, values } => {
+                self.write_vector_header(amf3_markers::VECTOR_INT, values.len(), *fixed)?;
+                for val in values {
+                    self.writer.write_u32::<BigEndian>(*val as u32)?;
+                }
+                Ok(())
+            }
+            Amf3ValueType::VectorUInt { fixed, values } => {
+                self.write_vector_header(amf3_markers::VECTOR_UINT, values.len(), *fixed)?;
+                for val in values {
+                    self.writer.write_u32::<BigEndian>(*val)?;
+                }
+                Ok(())
+            }
+            Amf3ValueType::VectorDouble { fixed, values } => {
+                self.write_vector_header(amf3_markers::VECTOR_DOUBLE, values.len(), *fixed)?;
+                for val in values {
+                    self.writer.write_f64::<BigEndian>(*val)?;
+                }
+                Ok(())
+            }
+            Amf3ValueType::VectorObject {
+                fixed,
+                type_name,
+                values,
+            } => {
+                self.write_vector_header(amf3_markers::VECTOR_OBJECT, values.len(), *fixed)?;
+                self.write_raw_string(type_name)?;
+                self.write_anys(values)
+            }
+            Amf3ValueType::Dictionary { weak_keys, entries } => {
+                self.write_marker(amf3_markers::DICTIONARY)?;
+                self.write_inline_header(entries.len())?;
+                self.writer.write_u8(*weak_keys as u8)?;
+                for (key, val) in entries {
+                    self.write_any(key)?;
+                    self.write_any(val)?;
+                }
+                Ok(())
+            }
+            Amf3ValueType::END => Ok(()),
+        }
+    }
+
+    fn write_marker(&mut self, marker: u8) -> Result<(), Amf3WriteError> {
+        self.writer.write_u8(marker)?;
+        Ok(())
+    }
+
+    pub fn write_u29(&mut self, value: u32) -> Result<(), Amf3WriteError> {
+        if value > define::U29_MAX {
+            return Err(Amf3WriteError {
+                value: Amf3WriteErrorValue::U29OutOfRange { value },
+            });
+        }
+
+        if value < 0x80 {
+            self.writer.write_u8(value as u8)?;
+        } else if value < 0x4000 {
+            self.writer.write_u8(((value >> 7) | 0x80) as u8)?;
+            self.writer.write_u8((value & 0x7f) as u8)?;
+        } else if value < 0x20_0000 {
+            self.writer.write_u8(((value >> 14) | 0x80) as u8)?;
+            self.writer.write_u8((((value >> 7) & 0x7f) | 0x80) as u8)?;
+            self.writer.write_u8((value & 0x7f) as u8)?;
+        } else {
+            self.writer.write_u8(((value >> 22) | 0x80) as u8)?;
+            self.writer
+                .write_u8((((value >> 15) & 0x7f) | 0x80) as u8)?;
+            self.writer.write_u8((((value >> 8) & 0x7f) | 0x80) as u8)?;
+            self.writer.write_u8((value & 0xff) as u8)?;
+        }
+
+        Ok(())
+    }
+
+    /*U29 value with the low bit set, marking an inline value instead of a reference*/
+    fn write_inline_header(&mut self, value: usize) -> Result<(), Amf3WriteError> {
+        self.write_header(value, 1, 0x01)
+    }
+
+    /*the value is shifted left above the flag bits, it has to fit in the remaining bits*/
+    fn write_header(&mut self, value: usize, shift: u32, flags: u32) -> Result<(), Amf3WriteError> {
+        if value > (define::U29_MAX >> shift) as usize {
+            return Err(Amf3WriteError {
+                value: Amf3WriteErrorValue::LengthOutOfRange { length: value },
+            });
+        }
+        self.write_u29(((value as u32) << shift) | flags)
+    }
+
+    pub fn write_bool(&mut self, value: bool) -> Result<(), Amf3WriteError> {
+        if value {
+            self.write_marker(amf3_markers::TRUE)
+        } else {
+            self.write_marker(amf3_markers::FALSE)
+        }
+    }
+
+    pub fn write_integer(&mut self, value: i32) -> Result<(), Amf3WriteError> {
+        if !(define::INTEGER_MIN..=define::INTEGER_MAX).contains(&value) {
+            return self.write_double(value as f64);
+        }
+
+        self.write_marker(amf3_markers::INTEGER)?;
+        self.write_u29((value as u32) & define::U29_MAX)
+    }
+
+    pub fn write_double(&mut self, value: f64) -> Result<(), Amf3WriteError> {
+        self.write_marker(amf3_markers::DOUBLE)?;
+        self.writer.write_f64::<BigEndian>(value)?;
+        Ok(())
+    }
+
+    pub fn write_raw_string(&mut self, value: &str) -> Result<(), Amf3WriteError> {
+        if value.is_empty() {
+            return self.write_inline_header(0);
+        }
+
+        if let Some(index) = self.string_table.get(value) {
+            return self.write_header(*index, 1, 0x00);
+        }
+
+        let index = self.string_table.len();
+        self.string_table.insert(String::from(value), index);
+
+        self.write_inline_header(value.len())?;
+        self.writer.write(value.as_bytes())?;
+        Ok(())
+    }
+
+    pub fn write_xml(&mut self, marker: u8, value: &str) -> Result<(), Amf3WriteError> {
+        self.write_marker(marker)?;
+        self.write_inline_header(value.len())?;
+        self.writer.write(value.as_bytes())?;
+        Ok(())
+    }
+
+    pub fn write_date(&mut self, value: f64) -> Result<(), Amf3WriteError> {
+        self.write_marker(amf3_markers::DATE)?;
+        self.write_inline_header(0)?;
+        self.writer.write_f64::<BigEndian>(value)?;
+        Ok(())
+    }
+
+    pub fn write_array(
+        &mut self,
+        assoc: &HashMap<String, Amf3ValueType>,
+        dense: &Vec<Amf3ValueType>,
+    ) -> Result<(), Amf3WriteError> {
+        self.write_marker(amf3_markers::ARRAY)?;
+        self.write_inline_header(dense.len())?;
+
+        for (key, value) in assoc {
+            self.write_raw_string(key)?;
+            self.write_any(value)?;
+        }
+        self.write_raw_string("")?;
+
+        self.write_anys(dense)
+    }
+
+    fn write_traits(&mut self, traits: &Amf3Trait) -> Result<(), Amf3WriteError> {
+        if let Some(index) = self.trait_table.iter().position(|t| t == traits) {
+            //U29O-traits-ref
+            return self.write_header(index, 2, 0x01);
+        }
+        self.trait_table.push(traits.clone());
+
+        let mut flags = 0x03;
+        if traits.is_externalizable {
+            flags |= 0x04;
+        }
+        if traits.is_dynamic {
+            flags |= 0x08;
+        }
+        self.write_header(traits.sealed_names.len(), 4, flags)?;
+
+        self.write_raw_string(&traits.class_name)?;
+        for name in &traits.sealed_names {
+            self.write_raw_string(name)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn write_object(
+        &mut self,
+        traits: &Amf3Trait,
+        sealed: &Vec<Amf3ValueType>,
+        dynamic: &HashMap<String, Amf3ValueType>,
+    ) -> Result<(), Amf3WriteError> {
+        self.write_marker(amf3_markers::OBJECT)?;
+        self.write_traits(traits)?;
+
+        self.write_anys(sealed)?;
+
+        if traits.is_dynamic && !traits.is_externalizable {
+            for (key, value) in dynamic {
+                self.write_raw_string(key)?;
+                self.write_any(value)?;
+            }
+            self.write_raw_string("")?;
+        }
+
+        Ok(())
+    }
+
+    pub fn write_byte_array(&mut self, value: &[u8]) -> Result<(), Amf3WriteError> {
+        self.write_marker(amf3_markers::BYTE_ARRAY)?;
+        self.write_inline_header(value.len())?;
+        self.writer.write(value)?;
+        Ok(())
+    }
+
+    fn write_vector_header(
+        &mut self,
+        marker: u8,
+        count: usize,
+        fixed: bool,
+    ) -> Result<(), Amf3WriteError> {
+        self.write_marker(marker)?;
+        self.write_inline_header(count)?;
+        self.writer.write_u8(fixed as u8)?;
+        Ok(())
+    }
+
+    pub fn extract_current_bytes(&mut self) -> BytesMut {
+        self.writer.extract_current_bytes()
+    }
+
+    pub fn get_current_bytes(&mut self) -> BytesMut {
+        self.writer.get_current_bytes()
+    }
+
+    pub fn len(&self) -> usize {
+        self.writer.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
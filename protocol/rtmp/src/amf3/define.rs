@@ -0,0 +1,92 @@
+use std::collections::HashMap;
+
+/*integers are encoded with U29, so only 29 bits signed values can be
+represented, the others must be written as double.*/
+pub const INTEGER_MAX: i32 = 0x0fff_ffff;
+pub const INTEGER_MIN: i32 = -0x1000_0000;
+/*the max value can be encoded using U29*/
+pub const U29_MAX: u32 = 0x1fff_ffff;
+
+/*A reference is decoded as a copy of the value it points to, values which
+reference each other grow exponentially. The decoded values of a message are
+limited to about MAX_DECODED_SIZE bytes of memory, and their nesting to
+MAX_NESTING_DEPTH.*/
+pub const MAX_DECODED_SIZE: usize = 16 * 1024 * 1024;
+pub const MAX_NESTING_DEPTH: usize = 64;
+
+/*Flex classes which are externalizable and simply wrap another value.*/
+pub const FLEX_ARRAY_COLLECTION: &str = "flex.messaging.io.ArrayCollection";
+pub const FLEX_ARRAY_LIST: &str = "mx.collections.ArrayList";
+pub const FLEX_OBJECT_PROXY: &str = "flex.messaging.io.ObjectProxy";
+
+// 3.12 Object Type: the traits describe the class of an object
+#[derive(PartialEq, Clone, Debug, Default)]
+pub struct Amf3Trait {
+    // empty for anonymous objects
+    pub class_name: String,
+    pub is_dynamic: bool,
+    pub is_externalizable: bool,
+    pub sealed_names: Vec<String>,
+}
+
+#[derive(PartialEq, Clone, Debug)]
+pub enum Amf3ValueType {
+    Undefined,
+    Null,
+    Boolean(bool),
+    Integer(i32),
+    Double(f64),
+    UTF8String(String),
+    XmlDocument(String),
+    // milliseconds since the epoch, UTC
+    Date(f64),
+    Array {
+        assoc: HashMap<String, Amf3ValueType>,
+        dense: Vec<Amf3ValueType>,
+    },
+    Object {
+        traits: Amf3Trait,
+        // values of the sealed members, in the order of traits.sealed_names;
+        // for an externalizable object it holds the wrapped value.
+        sealed: Vec<Amf3ValueType>,
+        dynamic: HashMap<String, Amf3ValueType>,
+    },
+    Xml(String),
+    ByteArray(Vec<u8>),
+    VectorInt {
+        fixed: bool,
+        values: Vec<i32>,
+    },
+    VectorUInt {
+        fixed: bool,
+        values: Vec<u32>,
+    },
+    VectorDouble {
+        fixed: bool,
+        values: Vec<f64>,
+    },
+    VectorObject {
+        fixed: bool,
+        type_name: String,
+        values: Vec<Amf3ValueType>,
+    },
+    Dictionary {
+        weak_keys: bool,
+        entries: Vec<(Amf3ValueType, Amf3ValueType)>,
+    },
+    END,
+}
+
+impl Amf3ValueType {
+    /*build an anonymous dynamic object from the properties*/
+    pub fn new_object(properties: HashMap<String, Amf3ValueType>) -> Self {
+        Amf3ValueType::Object {
+            traits: Amf3Trait {
+                is_dynamic: true,
+                ..Default::default()
+            },
+            sealed: Vec::new(),
+            dynamic: properties,
+        }
+    }
+}
@@ -0,0 +1,101 @@
+use {
+    bytesio::bytes_errors::{BytesReadError, BytesWriteError},
+    failure::{Backtrace, Fail},
+    std::{fmt, string},
+};
+
+#[derive(Debug, Fail)]
+pub enum Amf3ReadErrorValue {
+    #[fail(display = "Encountered unknown marker: {}\n", marker)]
+    UnknownMarker { marker: u8 },
+    #[fail(display = "parser string error: {}\n", _0)]
+    StringParseError(#[cause] string::FromUtf8Error),
+    #[fail(display = "bytes read error :{}\n", _0)]
+    BytesReadError(BytesReadError),
+    #[fail(display = "invalid reference index: {}\n", index)]
+    InvalidReference { index: usize },
+    #[fail(display = "unsupported externalizable class: {}\n", class_name)]
+    UnsupportedExternalizable { class_name: String },
+    #[fail(display = "decoded values exceed the size limit\n")]
+    DecodedSizeExceeded,
+    #[fail(display = "values are nested too deep\n")]
+    NestingTooDeep,
+    #[fail(display = "the input ends inside a value\n")]
+    Truncated,
+}
+
+#[derive(Debug)]
+pub struct Amf3ReadError {
+    pub value: Amf3ReadErrorValue,
+}
+
+impl From<string::FromUtf8Error> for Amf3ReadError {
+    fn from(error: string::FromUtf8Error) -> Self {
+        Amf3ReadError {
+            value: Amf3ReadErrorValue::StringParseError(error),
+        }
+    }
+}
+
+impl From<BytesReadError> for Amf3ReadError {
+    fn from(error: BytesReadError) -> Self {
+        Amf3ReadError {
+            value: Amf3ReadErrorValue::BytesReadError(error),
+        }
+    }
+}
+
+#[derive(Debug, Fail)]
+pub enum Amf3WriteErrorValue {
+    #[fail(display = "value is too large to be encoded as U29: {}\n", value)]
+    U29OutOfRange { value: u32 },
+    #[fail(display = "length or index is too large to be encoded: {}\n", length)]
+    LengthOutOfRange { length: usize },
+    #[fail(display = "bytes write error\n")]
+    BytesWriteError(BytesWriteError),
+}
+
+#[derive(Debug)]
+pub struct Amf3WriteError {
+    pub value: Amf3WriteErrorValue,
+}
+
+impl From<BytesWriteError> for Amf3WriteError {
+    fn from(error: BytesWriteError) -> Self {
+        Amf3WriteError {
+            value: Amf3WriteErrorValue::BytesWriteError(error),
+        }
+    }
+}
+
+impl fmt::Display for Amf3ReadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&self.value, f)
+    }
+}
+
+impl Fail for Amf3ReadError {
+    fn cause(&self) -> Option<&dyn Fail> {
+        self.value.cause()
+    }
+
+    fn backtrace(&self) -> Option<&Backtrace> {
+        self.value.backtrace()
+    }
+}
+
+impl fmt::Display for Amf3WriteError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&self.value, f)
+    }
+}
+
+impl Fail for Amf3WriteError {
+    fn cause(&self) -> Option<&dyn Fail> {
+        self.value.cause()
+    }
+
+    fn backtrace(&self) -> Option<&Backtrace> {
+        self.value.backtrace()
+    }
+}
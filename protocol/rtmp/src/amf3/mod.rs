@@ -0,0 +1,8 @@
+pub mod amf3_markers;
+pub mod amf3_reader;
+pub mod amf3_writer;
+pub mod define;
+pub mod errors;
+
+pub use self::define::{Amf3Trait, Amf3ValueType};
+pub use self::errors::{Amf3ReadError, Amf3WriteError};
@@ -1,8 +1,35 @@
-use {crate::channels::define::ChannelData, std::collections::VecDeque};
+use {
+    crate::channels::define::ChannelData,
+    std::{collections::VecDeque, time::Duration},
+};
+
+/* Older GOPs are dropped once the cache holds more than num GOPs, spans more
+than max_duration or takes more than max_bytes. A zero num disables the cache,
+a zero max_duration or max_bytes disables that limit. */
+#[derive(Debug, Clone, Copy)]
+pub struct GopLimits {
+    pub num: usize,
+    pub max_duration: Duration,
+    pub max_bytes: usize,
+}
+
+impl Default for GopLimits {
+    fn default() -> Self {
+        Self {
+            num: 1,
+            max_duration: Duration::ZERO,
+            max_bytes: 0,
+        }
+    }
+}
 
 #[derive(Clone)]
 pub struct Gop {
-    datas: Vec<ChannelData>,
+    datas: VecDeque<ChannelData>,
+    /* The frames before the first key frame of a stream, or of an audio only
+    stream, are kept in a GOP which does not start with a key frame. */
+    starts_with_key_frame: bool,
+    bytes: usize,
 }
 
 impl Default for Gop {
@@ -13,15 +40,34 @@ impl Default for Gop {
 
 impl Gop {
     pub fn new() -> Self {
-        Self { datas: Vec::new() }
+        Self {
+            datas: VecDeque::new(),
+            starts_with_key_frame: false,
+            bytes: 0,
+        }
     }
 
     fn save_frame_data(&mut self, data: ChannelData) {
-        self.datas.push(data);
+        self.bytes += data.len();
+        self.datas.push_back(data);
+    }
+
+    fn remove_first_frame(&mut self) {
+        if let Some(data) = self.datas.pop_front() {
+            self.bytes -= data.len();
+        }
     }
 
     pub fn get_frame_data(self) -> Vec<ChannelData> {
-        self.datas
+        self.datas.into()
+    }
+
+    pub fn starts_with_key_frame(&self) -> bool {
+        self.starts_with_key_frame
+    }
+
+    pub fn first_timestamp(&self) -> Option<u32> {
+        self.datas.front().map(|data| data.timestamp())
     }
 
     pub fn len(&self) -> usize {
@@ -36,46 +82,205 @@ impl Gop {
 #[derive(Clone)]
 pub struct Gops {
     gops: VecDeque<Gop>,
-    size: usize,
+    limits: GopLimits,
+    bytes: usize,
+    /* A GOP larger than max_bytes was dropped, the frames up to the next key
+    frame are not cached. */
+    wait_key_frame: bool,
 }
 
 impl Default for Gops {
     fn default() -> Self {
-        Self::new(1)
+        Self::new(GopLimits::default())
     }
 }
 
 impl Gops {
-    pub fn new(size: usize) -> Self {
+    pub fn new(limits: GopLimits) -> Self {
         Self {
             gops: VecDeque::from([Gop::new()]),
-            size,
+            limits,
+            bytes: 0,
+            wait_key_frame: false,
         }
     }
 
     pub fn save_frame_data(&mut self, data: ChannelData, is_key_frame: bool) {
-        if self.size == 0 {
+        if self.limits.num == 0 || (self.wait_key_frame && !is_key_frame) {
             return;
         }
 
-        if is_key_frame && self.gops.len() >= self.size {
-            //todo It may be possible to optimize here
-            self.gops.pop_front();
-            self.gops.push_back(Gop::new());
+        if is_key_frame {
+            self.wait_key_frame = false;
+            /*nothing was cached since the stream started or the last GOP was dropped*/
+            if self.gops.back().is_some_and(|gop| gop.is_empty()) {
+                self.gops.pop_back();
+            }
+            let mut gop = Gop::new();
+            gop.starts_with_key_frame = true;
+            self.gops.push_back(gop);
         }
 
+        self.bytes += data.len();
         if let Some(gop) = self.gops.back_mut() {
             gop.save_frame_data(data);
         } else {
             tracing::error!("should not be here!");
         }
+
+        self.apply_limits();
+    }
+
+    /* The newest key frame is kept as long as it fits in max_bytes, the frames
+    of a GOP without a key frame are dropped one by one. */
+    fn apply_limits(&mut self) {
+        while self.gops.len() > self.limits.num || self.exceeds_limits() {
+            if self.gops.len() > 1 {
+                if let Some(gop) = self.gops.pop_front() {
+                    self.bytes -= gop.bytes;
+                }
+                continue;
+            }
+
+            let max_bytes = self.limits.max_bytes;
+            let gop = match self.gops.front_mut() {
+                Some(gop) => gop,
+                None => break,
+            };
+            if !gop.starts_with_key_frame {
+                self.bytes -= gop.bytes;
+                gop.remove_first_frame();
+                self.bytes += gop.bytes;
+                if gop.is_empty() {
+                    break;
+                }
+            } else if max_bytes > 0 && gop.bytes > max_bytes {
+                /*the players would start on frames they cannot decode*/
+                self.bytes -= gop.bytes;
+                self.gops.clear();
+                self.wait_key_frame = true;
+                break;
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn exceeds_limits(&self) -> bool {
+        if self.limits.max_bytes > 0 && self.bytes > self.limits.max_bytes {
+            return true;
+        }
+
+        if !self.limits.max_duration.is_zero() {
+            let first = self.gops.iter().find_map(|gop| gop.first_timestamp());
+            let last = self
+                .gops
+                .back()
+                .and_then(|gop| gop.datas.back())
+                .map(|data| data.timestamp());
+            if let (Some(first), Some(last)) = (first, last) {
+                let duration = last.wrapping_sub(first) as u128;
+                return duration > self.limits.max_duration.as_millis();
+            }
+        }
+
+        false
     }
 
     pub fn setted(&self) -> bool {
-        self.size != 0
+        self.limits.num != 0
     }
 
     pub fn get_gops(&self) -> VecDeque<Gop> {
         self.gops.clone()
     }
+
+    pub fn bytes(&self) -> usize {
+        self.bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{GopLimits, Gops},
+        crate::channels::define::ChannelData,
+        bytes::Bytes,
+        std::time::Duration,
+    };
+
+    fn video(timestamp: u32, size: usize) -> ChannelData {
+        ChannelData::Video {
+            timestamp,
+            data: Bytes::from(vec![0; size]),
+        }
+    }
+
+    fn save_gop(gops: &mut Gops, timestamp: u32, frames: u32, size: usize) {
+        for idx in 0..frames {
+            gops.save_frame_data(video(timestamp + idx * 40, size), idx == 0);
+        }
+    }
+
+    #[test]
+    fn test_gop_limits() {
+        let mut gops = Gops::new(GopLimits {
+            num: 3,
+            max_duration: Duration::ZERO,
+            max_bytes: 0,
+        });
+        for idx in 0..5 {
+            save_gop(&mut gops, idx * 1000, 25, 100);
+        }
+        assert_eq!(gops.get_gops().len(), 3);
+        assert_eq!(gops.bytes(), 3 * 25 * 100);
+
+        /*the oldest GOPs are dropped once the cache spans more than 2s*/
+        let mut gops = Gops::new(GopLimits {
+            num: 10,
+            max_duration: Duration::from_secs(2),
+            max_bytes: 0,
+        });
+        for idx in 0..5 {
+            save_gop(&mut gops, idx * 1000, 25, 100);
+        }
+        let cached = gops.get_gops();
+        assert_eq!(cached.len(), 2);
+        assert_eq!(cached[0].first_timestamp(), Some(3000));
+        assert!(cached[0].starts_with_key_frame());
+
+        /*a GOP larger than max_bytes is not cached*/
+        let mut gops = Gops::new(GopLimits {
+            num: 10,
+            max_duration: Duration::ZERO,
+            max_bytes: 5000,
+        });
+        for idx in 0..3 {
+            save_gop(&mut gops, idx * 1000, 25, 100);
+        }
+        assert_eq!(gops.get_gops().len(), 2);
+        assert_eq!(gops.bytes(), 5000);
+        save_gop(&mut gops, 3000, 60, 100);
+        assert!(gops.get_gops().is_empty());
+        assert_eq!(gops.bytes(), 0);
+        /*the caching goes on at the next key frame*/
+        save_gop(&mut gops, 6000, 25, 100);
+        let cached = gops.get_gops();
+        assert_eq!(cached.len(), 1);
+        assert!(cached[0].starts_with_key_frame());
+        assert_eq!(gops.bytes(), 2500);
+
+        /*an audio only stream is trimmed frame by frame*/
+        let mut gops = Gops::new(GopLimits {
+            num: 1,
+            max_duration: Duration::from_secs(1),
+            max_bytes: 0,
+        });
+        for idx in 0..100 {
+            gops.save_frame_data(video(idx * 20, 10), false);
+        }
+        let cached = gops.get_gops();
+        assert_eq!(cached[0].first_timestamp(), Some(980));
+        assert_eq!(cached[0].len(), 51);
+    }
 }
@@ -3,23 +3,26 @@ pub mod gop;
 pub mod metadata;
 
 use {
-    self::gop::Gops,
+    self::gop::{GopLimits, Gops},
     super::statistics::avstatistics::AvStatistics,
     crate::channels::define::ChannelData,
-    bytes::BytesMut,
+    bytes::{Bytes, BytesMut},
     errors::CacheError,
     gop::Gop,
     std::collections::VecDeque,
     xflv::{define, demuxer_tag, mpeg4_aac::Mpeg4AacProcessor, mpeg4_avc::Mpeg4AvcProcessor},
 };
 
+/* Longer than the legacy and the enhanced audio/video tag headers. */
+const TAG_HEADER_MAX_SIZE: usize = 16;
+
 // #[derive(Clone)]
 pub struct Cache {
     metadata: metadata::MetaData,
     metadata_timestamp: u32,
-    video_seq: BytesMut,
+    video_seq: Bytes,
     video_timestamp: u32,
-    audio_seq: BytesMut,
+    audio_seq: Bytes,
     audio_timestamp: u32,
     gops: Gops,
     pub av_statistics: AvStatistics,
@@ -33,15 +36,15 @@ impl Drop for Cache {
 }
 
 impl Cache {
-    pub fn new(app_name: String, stream_name: String, gop_num: usize) -> Self {
+    pub fn new(app_name: String, stream_name: String, gop_limits: GopLimits) -> Self {
         let mut cache = Cache {
             metadata: metadata::MetaData::new(),
             metadata_timestamp: 0,
-            video_seq: BytesMut::new(),
+            video_seq: Bytes::new(),
             video_timestamp: 0,
-            audio_seq: BytesMut::new(),
+            audio_seq: Bytes::new(),
             audio_timestamp: 0,
-            gops: Gops::new(gop_num),
+            gops: Gops::new(gop_limits),
             av_statistics: AvStatistics::new(app_name, stream_name),
         };
         cache.av_statistics.start();
@@ -49,8 +52,8 @@ impl Cache {
     }
 
     //, values: Vec<Amf0ValueType>
-    pub fn save_metadata(&mut self, chunk_body: BytesMut, timestamp: u32) {
-        self.metadata.save(chunk_body);
+    pub fn save_metadata(&mut self, chunk_body: Bytes, timestamp: u32) {
+        self.metadata.save(BytesMut::from(&chunk_body[..]));
         self.metadata_timestamp = timestamp;
     }
 
@@ -59,7 +62,7 @@ impl Cache {
         if !data.is_empty() {
             Some(ChannelData::MetaData {
                 timestamp: self.metadata_timestamp,
-                data,
+                data: data.freeze(),
             })
         } else {
             None
@@ -68,7 +71,7 @@ impl Cache {
     //save audio gops and sequence header information
     pub async fn save_audio_data(
         &mut self,
-        chunk_body: BytesMut,
+        chunk_body: Bytes,
         timestamp: u32,
     ) -> Result<(), CacheError> {
         let channel_data = ChannelData::Audio {
@@ -77,12 +80,15 @@ impl Cache {
         };
         self.gops.save_frame_data(channel_data, false);
 
-        let mut parser = demuxer_tag::AudioTagHeaderDemuxer::new(chunk_body.clone());
+        let mut parser = demuxer_tag::AudioTagHeaderDemuxer::new(tag_header(&chunk_body));
         let tag = parser.parse_tag_header()?;
 
         if tag.sound_format == define::SoundFormat::AAC as u8
             && tag.aac_packet_type == define::aac_packet_type::AAC_SEQHDR
         {
+            let mut parser =
+                demuxer_tag::AudioTagHeaderDemuxer::new(BytesMut::from(&chunk_body[..]));
+            parser.parse_tag_header()?;
             self.audio_seq = chunk_body.clone();
             self.audio_timestamp = timestamp;
 
@@ -124,28 +130,45 @@ impl Cache {
     //save video gops and sequence header information
     pub async fn save_video_data(
         &mut self,
-        chunk_body: BytesMut,
+        chunk_body: Bytes,
         timestamp: u32,
     ) -> Result<(), CacheError> {
-        let mut parser = demuxer_tag::VideoTagHeaderDemuxer::new(chunk_body.clone());
+        let mut parser = demuxer_tag::VideoTagHeaderDemuxer::new(tag_header(&chunk_body));
         let tag = parser.parse_tag_header()?;
 
         let channel_data = ChannelData::Video {
             timestamp,
             data: chunk_body.clone(),
         };
-        let is_key_frame = tag.frame_type == define::frame_type::KEY_FRAME;
+        let is_key_frame = tag.is_key_frame();
         self.gops.save_frame_data(channel_data, is_key_frame);
 
-        if is_key_frame && tag.avc_packet_type == define::avc_packet_type::AVC_SEQHDR {
-            let mut avc_processor = Mpeg4AvcProcessor::default();
-            avc_processor
-                .extend_data(parser.get_remaining_bytes())
-                .decoder_configuration_record_load()?;
-
-            self.av_statistics
-                .notify_video_codec_info(&avc_processor.mpeg4_avc)
-                .await;
+        if is_key_frame && tag.is_sequence_header() {
+            let codec_id = if tag.is_ex_header {
+                define::fourcc_2_avc_codec_id(&tag.fourcc)
+            } else {
+                define::u8_2_avc_codec_id(tag.codec_id)
+            };
+
+            /*only the AVC decoder configuration record is parsed, the sequence
+            headers of the other codecs are cached as they are.*/
+            if matches!(codec_id, define::AvcCodecId::H264) {
+                let mut parser =
+                    demuxer_tag::VideoTagHeaderDemuxer::new(BytesMut::from(&chunk_body[..]));
+                parser.parse_tag_header()?;
+                let mut avc_processor = Mpeg4AvcProcessor::default();
+                avc_processor
+                    .extend_data(parser.get_remaining_bytes())
+                    .decoder_configuration_record_load()?;
+
+                self.av_statistics
+                    .notify_video_codec_info(codec_id, Some(&avc_processor.mpeg4_avc))
+                    .await;
+            } else {
+                self.av_statistics
+                    .notify_video_codec_info(codec_id, None)
+                    .await;
+            }
 
             self.video_seq = chunk_body.clone();
             self.video_timestamp = timestamp;
@@ -158,6 +181,12 @@ impl Cache {
         Ok(())
     }
 
+    /* Kept in the GOP of the frames before it. */
+    pub fn save_discontinuity(&mut self, timestamp: u32) {
+        self.gops
+            .save_frame_data(ChannelData::Discontinuity { timestamp }, false);
+    }
+
     pub fn get_gops_data(&self) -> Option<VecDeque<Gop>> {
         if self.gops.setted() {
             Some(self.gops.get_gops())
@@ -166,3 +195,9 @@ impl Cache {
         }
     }
 }
+
+/* The frames are parsed from a copy of their tag header, only the sequence
+headers are copied whole. */
+fn tag_header(chunk_body: &Bytes) -> BytesMut {
+    BytesMut::from(&chunk_body[..chunk_body.len().min(TAG_HEADER_MAX_SIZE)])
+}
@@ -1,20 +1,67 @@
 use {
-    crate::session::common::SubscriberInfo,
+    super::{errors::ChannelError, queue::SubscriberQueue},
+    crate::file_source::define::FileSource,
+    crate::session::common::{PublisherInfo, SubscriberInfo},
     crate::statistics::StreamStatistics,
-    bytes::BytesMut,
-    std::fmt,
+    bytes::Bytes,
+    serde_derive::Deserialize,
+    std::{fmt, time::Duration},
     tokio::sync::{broadcast, mpsc, oneshot},
+    uuid::Uuid,
 };
+/* The payloads are shared by the cache and all the subscribers, cloning
+a ChannelData does not copy them. A Discontinuity is sent to the subscribers
+before the first frame after the publisher's timestamps jumped, see
+TimestampNormalizer. */
 #[derive(Clone)]
 pub enum ChannelData {
-    Video { timestamp: u32, data: BytesMut },
-    Audio { timestamp: u32, data: BytesMut },
-    MetaData { timestamp: u32, data: BytesMut },
+    Video { timestamp: u32, data: Bytes },
+    Audio { timestamp: u32, data: Bytes },
+    MetaData { timestamp: u32, data: Bytes },
+    Discontinuity { timestamp: u32 },
+}
+
+impl ChannelData {
+    pub fn timestamp(&self) -> u32 {
+        match self {
+            ChannelData::Video { timestamp, .. }
+            | ChannelData::Audio { timestamp, .. }
+            | ChannelData::MetaData { timestamp, .. }
+            | ChannelData::Discontinuity { timestamp } => *timestamp,
+        }
+    }
+
+    pub fn set_timestamp(&mut self, value: u32) {
+        match self {
+            ChannelData::Video { timestamp, .. }
+            | ChannelData::Audio { timestamp, .. }
+            | ChannelData::MetaData { timestamp, .. }
+            | ChannelData::Discontinuity { timestamp } => *timestamp = value,
+        }
+    }
+
+    /* The size of the payload. */
+    pub fn len(&self) -> usize {
+        match self {
+            ChannelData::Video { data, .. }
+            | ChannelData::Audio { data, .. }
+            | ChannelData::MetaData { data, .. } => data.len(),
+            ChannelData::Discontinuity { .. } => 0,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
 }
 
 pub type ChannelDataProducer = mpsc::UnboundedSender<ChannelData>;
 pub type ChannelDataConsumer = mpsc::UnboundedReceiver<ChannelData>;
 
+/* A subscriber's queue is bounded, see BackpressurePolicy. */
+pub type SubscriberDataProducer = mpsc::Sender<ChannelData>;
+pub type SubscriberDataConsumer = mpsc::Receiver<ChannelData>;
+
 pub type ChannelEventProducer = mpsc::UnboundedSender<ChannelEvent>;
 pub type ChannelEventConsumer = mpsc::UnboundedReceiver<ChannelEvent>;
 
@@ -31,32 +78,176 @@ pub type StreamStatisticSizeSender = oneshot::Sender<usize>;
 pub type StreamStatisticSizeReceiver = oneshot::Sender<usize>;
 
 type ChannelResponder<T> = oneshot::Sender<T>;
+pub type PublishResponder = ChannelResponder<Result<ChannelDataProducer, ChannelError>>;
+pub type SubscribeResponder = ChannelResponder<Result<SubscriberDataConsumer, ChannelError>>;
+pub type SubscribeReceiver = oneshot::Receiver<Result<SubscriberDataConsumer, ChannelError>>;
+pub type KickResponder = ChannelResponder<Result<(), ChannelError>>;
+pub type FileSourceResponder = ChannelResponder<Result<(), ChannelError>>;
+pub type FileSourcesResponder = ChannelResponder<Vec<FileSource>>;
+
+/* How long a subscriber waits for a stream which is not published yet. */
+pub const SUBSCRIBE_WAIT_TIMEOUT_SECS: u64 = 10;
+
+/* How long a publisher may send no media and a subscriber may leave its queue
+unread before it is considered gone. */
+pub const PUBLISH_IDLE_TIMEOUT_SECS: u64 = 30;
+pub const SUBSCRIBER_IDLE_TIMEOUT_SECS: u64 = 30;
+
+/* Where a new player starts in the cached GOPs. The timestamps a player
+receives are rebased to its start, so a burst of cached frames is not
+played out behind the live edge. */
+#[derive(Debug, Deserialize, Clone, Copy, Default, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum StartMode {
+    /* The newest cached GOP only, the player starts as close to live as
+    the key frame interval allows. */
+    LatestKeyframe,
+    /* All the cached GOPs, the player fills its buffer at once. */
+    #[default]
+    FullCache,
+}
+
+impl StartMode {
+    /* The value of a start_mode query parameter. */
+    pub fn parse(value: &str) -> Option<StartMode> {
+        match value {
+            "latest_keyframe" => Some(StartMode::LatestKeyframe),
+            "full_cache" => Some(StartMode::FullCache),
+            _ => None,
+        }
+    }
+}
+
+/* A zero timeout disables the check. */
+#[derive(Debug, Clone, Copy)]
+pub struct IdleTimeouts {
+    pub publish: Duration,
+    pub subscriber: Duration,
+}
+
+impl Default for IdleTimeouts {
+    fn default() -> Self {
+        Self {
+            publish: Duration::from_secs(PUBLISH_IDLE_TIMEOUT_SECS),
+            subscriber: Duration::from_secs(SUBSCRIBER_IDLE_TIMEOUT_SECS),
+        }
+    }
+}
+
+/* How a subscriber which does not keep up with the stream is treated. Once
+non_key_drop_threshold frames are queued its non-key video frames are
+dropped, once the queue is full everything up to the next key frame is
+dropped, and after max_dropped_gops GOPs dropped in a row it is disconnected. */
+#[derive(Debug, Deserialize, Clone, Copy)]
+#[serde(default)]
+pub struct BackpressurePolicy {
+    pub queue_size: usize,
+    pub non_key_drop_threshold: usize,
+    pub max_dropped_gops: usize,
+}
+
+impl Default for BackpressurePolicy {
+    fn default() -> Self {
+        Self {
+            queue_size: 1024,
+            non_key_drop_threshold: 512,
+            max_dropped_gops: 3,
+        }
+    }
+}
+
+/* What happens when a stream is published while it already has a publisher. */
+#[derive(Debug, Deserialize, Clone, Copy, Default, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum PublishConflictPolicy {
+    /* The new publish fails with ChannelErrorValue::Exists. */
+    #[default]
+    Reject,
+    /* The new publisher replaces the existing one, the subscribers stay. */
+    KickExisting,
+    /* The new publisher waits until the existing one unpublishes. */
+    Queue,
+}
+
+/* The settings of an app name which override the global ones of the
+channels, the unset ones fall back to them. */
+#[derive(Debug, Clone, Default)]
+pub struct AppProfile {
+    pub gop_num: Option<usize>,
+    pub gop_max_duration: Option<Duration>,
+    pub gop_max_bytes: Option<usize>,
+    /* How many streams of the app may be published and how many players
+    (rtmp and http-flv) may play them, a zero or unset one is unlimited. */
+    pub max_publishers: Option<usize>,
+    pub max_subscribers: Option<usize>,
+    pub httpflv_enabled: Option<bool>,
+    pub publish_conflict: Option<PublishConflictPolicy>,
+}
 #[derive(Debug)]
 pub enum ChannelEvent {
+    /* A subscribe to a stream which is not published yet is answered
+    once it is published, or with an error after the wait timeout. */
     Subscribe {
         app_name: String,
         stream_name: String,
         info: SubscriberInfo,
-        responder: ChannelResponder<ChannelDataConsumer>,
+        responder: SubscribeResponder,
     },
     UnSubscribe {
         app_name: String,
         stream_name: String,
         info: SubscriberInfo,
     },
+    /* A publish to a stream which has a publisher is handled by the
+    app's PublishConflictPolicy. */
     Publish {
         app_name: String,
         stream_name: String,
-        responder: ChannelResponder<ChannelDataProducer>,
+        info: PublisherInfo,
+        responder: PublishResponder,
     },
     UnPublish {
         app_name: String,
         stream_name: String,
+        publisher_id: Uuid,
     },
     Api {
         data_sender: AvStatisticSender,
         size_sender: StreamStatisticSizeSender,
     },
+    /* The round trip time measured by a session's keepalive pings. */
+    UpdateRtt {
+        app_name: String,
+        stream_name: String,
+        id: Uuid,
+        is_publisher: bool,
+        rtt: u32,
+    },
+    /* Cuts off the publisher and frees the stream name at once. */
+    KickPublisher {
+        app_name: String,
+        stream_name: String,
+        responder: KickResponder,
+    },
+    /* Cuts off a player, an http-flv subscriber or a relay push session. */
+    KickSubscriber {
+        id: Uuid,
+        responder: KickResponder,
+    },
+    /* Publishes an FLV file as app_name/stream_name of the source. */
+    StartFileSource {
+        source: FileSource,
+        responder: FileSourceResponder,
+    },
+    StopFileSource {
+        app_name: String,
+        stream_name: String,
+        responder: FileSourceResponder,
+    },
+    /* The file sources which are still publishing. */
+    ListFileSources {
+        responder: FileSourcesResponder,
+    },
 }
 
 impl fmt::Display for ChannelEvent {
@@ -88,20 +279,23 @@ impl fmt::Display for ChannelEvent {
             ChannelEvent::Publish {
                 app_name,
                 stream_name,
+                info,
                 responder: _,
             } => {
                 write!(
                     f,
-                    "receive event, event_name: Publish, app_name: {app_name},stream_name: {stream_name}",
+                    "receive event, event_name: Publish, app_name: {app_name},stream_name: {stream_name}, publisher id: {}",
+                    info.id,
                 )
             }
             ChannelEvent::UnPublish {
                 app_name,
                 stream_name,
+                publisher_id,
             } => {
                 write!(
                     f,
-                    "receive event, event_name: UnPublish, app_name: {app_name},stream_name: {stream_name}",
+                    "receive event, event_name: UnPublish, app_name: {app_name},stream_name: {stream_name}, publisher id: {publisher_id}",
                 )
             }
             ChannelEvent::Api {
@@ -110,6 +304,57 @@ impl fmt::Display for ChannelEvent {
             } => {
                 write!(f, "receive event, event_name: Api",)
             }
+            ChannelEvent::UpdateRtt {
+                app_name,
+                stream_name,
+                id,
+                is_publisher: _,
+                rtt,
+            } => {
+                write!(
+                    f,
+                    "receive event, event_name: UpdateRtt, app_name: {app_name},stream_name: {stream_name}, id: {id}, rtt: {rtt}ms",
+                )
+            }
+            ChannelEvent::KickPublisher {
+                app_name,
+                stream_name,
+                responder: _,
+            } => {
+                write!(
+                    f,
+                    "receive event, event_name: KickPublisher, app_name: {app_name},stream_name: {stream_name}",
+                )
+            }
+            ChannelEvent::KickSubscriber { id, responder: _ } => {
+                write!(
+                    f,
+                    "receive event, event_name: KickSubscriber, subscriber id: {id}",
+                )
+            }
+            ChannelEvent::StartFileSource {
+                source,
+                responder: _,
+            } => {
+                write!(
+                    f,
+                    "receive event, event_name: StartFileSource, app_name: {},stream_name: {}, path: {}",
+                    source.app_name, source.stream_name, source.path,
+                )
+            }
+            ChannelEvent::StopFileSource {
+                app_name,
+                stream_name,
+                responder: _,
+            } => {
+                write!(
+                    f,
+                    "receive event, event_name: StopFileSource, app_name: {app_name},stream_name: {stream_name}",
+                )
+            }
+            ChannelEvent::ListFileSources { responder: _ } => {
+                write!(f, "receive event, event_name: ListFileSources")
+            }
         }
     }
 }
@@ -117,17 +362,38 @@ impl fmt::Display for ChannelEvent {
 #[derive(Debug)]
 pub enum TransmitterEvent {
     Subscribe {
-        producer: ChannelDataProducer,
+        producer: SubscriberDataProducer,
         info: SubscriberInfo,
+        start_mode: StartMode,
     },
     UnSubscribe {
         info: SubscriberInfo,
     },
     UnPublish {},
+    /* The stream is taken over by another publisher. */
+    ReplacePublisher {
+        data_consumer: ChannelDataConsumer,
+        info: PublisherInfo,
+    },
 
     Api {
         sender: AvStatisticSender,
     },
+    UpdateRtt {
+        id: Uuid,
+        is_publisher: bool,
+        rtt: u32,
+    },
+    /* Hands the subscribers over to the Transmitter of another stream, their
+    queues stay open. */
+    MoveSubscribers {
+        ids: Vec<Uuid>,
+        target: TransmitterEventProducer,
+    },
+    /* The subscribers moved from another stream. */
+    AdoptSubscribers {
+        subscribers: Vec<(SubscriberInfo, SubscriberQueue)>,
+    },
 }
 
 impl fmt::Display for TransmitterEvent {
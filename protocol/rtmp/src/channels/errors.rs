@@ -15,6 +15,22 @@ pub enum ChannelErrorValue {
     SendVideoError,
     #[fail(display = "send audio error\n")]
     SendAudioError,
+    #[fail(display = "subscriber lags behind\n")]
+    SubscriberLagging,
+    #[fail(display = "no subscriber\n")]
+    NoSubscriber,
+    #[fail(display = "kicked\n")]
+    Kicked,
+    #[fail(display = "too many publishers of the app\n")]
+    TooManyPublishers,
+    #[fail(display = "too many subscribers of the app\n")]
+    TooManySubscribers,
+    #[fail(display = "http-flv is disabled for the app\n")]
+    HttpFlvDisabled,
+    #[fail(display = "no file source\n")]
+    NoFileSource,
+    #[fail(display = "the file is not in the file sources directory\n")]
+    FileSourceNotAllowed,
     #[fail(display = "cache error name: {}\n", _0)]
     CacheError(CacheError),
 }
@@ -2,19 +2,41 @@
 
 pub mod define;
 pub mod errors;
+pub mod normalizer;
+pub mod queue;
 
 use {
-    crate::cache::Cache,
-    crate::session::{common::SubscriberInfo, define::SubscribeType},
+    crate::cache::{
+        gop::{Gop, GopLimits},
+        Cache,
+    },
+    crate::file_source::{define::FileSource, publisher::FilePublisher},
+    crate::session::{
+        common::{PublisherInfo, SubscriberInfo},
+        define::SubscribeType,
+    },
+    crate::statistics::{PublisherStatistics, StreamStatistics, SubscriberStatistics},
     define::{
-        AvStatisticSender, ChannelData, ChannelDataConsumer, ChannelDataProducer, ChannelEvent,
-        ChannelEventConsumer, ChannelEventProducer, ClientEvent, ClientEventConsumer,
-        ClientEventProducer, StreamStatisticSizeSender, TransmitterEvent, TransmitterEventConsumer,
-        TransmitterEventProducer,
+        AppProfile, AvStatisticSender, BackpressurePolicy, ChannelData, ChannelDataConsumer,
+        ChannelDataProducer, ChannelEvent, ChannelEventConsumer, ChannelEventProducer, ClientEvent,
+        ClientEventConsumer, ClientEventProducer, IdleTimeouts, PublishConflictPolicy,
+        PublishResponder, StartMode, StreamStatisticSizeSender, SubscribeResponder,
+        SubscriberDataConsumer, TransmitterEvent, TransmitterEventConsumer,
+        TransmitterEventProducer, SUBSCRIBE_WAIT_TIMEOUT_SECS,
     },
     errors::{ChannelError, ChannelErrorValue},
-    std::collections::HashMap,
-    tokio::sync::{broadcast, mpsc, mpsc::UnboundedReceiver},
+    normalizer::TimestampNormalizer,
+    queue::SubscriberQueue,
+    std::{
+        collections::{HashMap, VecDeque},
+        path::PathBuf,
+        time::Duration,
+    },
+    tokio::{
+        sync::{broadcast, mpsc, mpsc::UnboundedReceiver},
+        time::{interval, sleep_until, Instant, MissedTickBehavior},
+    },
+    tokio_util::sync::CancellationToken,
     uuid::Uuid,
 };
 
@@ -40,31 +62,74 @@ use {
 *
 *************************************************************************************/
 
+/* A player of the stream. The round trip time is reported by the rtmp sessions. */
+struct StreamSubscriber {
+    info: SubscriberInfo,
+    queue: SubscriberQueue,
+    rtt: Option<u32>,
+}
+
+/* How often the Transmitter looks for an idle publisher or subscribers. */
+const IDLE_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
 //receive data from ChannelsManager and send to players
 pub struct Transmitter {
+    app_name: String,
+    stream_name: String,
     data_consumer: ChannelDataConsumer, //used for publisher to produce AV data
     event_consumer: TransmitterEventConsumer,
-    subscriberid_to_producer: HashMap<Uuid, ChannelDataProducer>,
+    //used to unpublish the stream once the publisher is idle
+    channel_event_producer: ChannelEventProducer,
+    subscriberid_to_producer: HashMap<Uuid, StreamSubscriber>,
+    backpressure_policy: BackpressurePolicy,
+    idle_timeouts: IdleTimeouts,
     cache: Cache,
+    publisher: PublisherInfo,
+    publisher_bytes_received: u64,
+    publisher_rtt: Option<u32>,
+    //the last time media was received from the publisher
+    publisher_last_data: Instant,
+    //the timestamp of the last media, a player without cached frames starts there
+    last_timestamp: Option<u32>,
+    normalizer: TimestampNormalizer,
 }
 
 impl Transmitter {
+    #[allow(clippy::too_many_arguments)]
     fn new(
         app_name: String,
         stream_name: String,
         data_consumer: UnboundedReceiver<ChannelData>,
         event_consumer: UnboundedReceiver<TransmitterEvent>,
-        gop_num: usize,
+        channel_event_producer: ChannelEventProducer,
+        gop_limits: GopLimits,
+        backpressure_policy: BackpressurePolicy,
+        idle_timeouts: IdleTimeouts,
+        publisher: PublisherInfo,
     ) -> Self {
         Self {
+            app_name: app_name.clone(),
+            stream_name: stream_name.clone(),
             data_consumer,
             event_consumer,
+            channel_event_producer,
             subscriberid_to_producer: HashMap::new(),
-            cache: Cache::new(app_name, stream_name, gop_num),
+            backpressure_policy,
+            idle_timeouts,
+            cache: Cache::new(app_name, stream_name, gop_limits),
+            publisher,
+            publisher_bytes_received: 0,
+            publisher_rtt: None,
+            publisher_last_data: Instant::now(),
+            last_timestamp: None,
+            normalizer: TimestampNormalizer::new(),
         }
     }
 
     pub async fn run(&mut self) -> Result<(), ChannelError> {
+        let mut idle_check = interval(IDLE_CHECK_INTERVAL);
+        idle_check.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
         loop {
             tokio::select! {
                 data = self.event_consumer.recv() =>{
@@ -73,40 +138,15 @@ impl Transmitter {
                             TransmitterEvent::Subscribe {
                                 producer,
                                 info,
+                                start_mode,
                             } => {
-                                match info.sub_type {
-                                    SubscribeType::PlayerRtmp
-                                    | SubscribeType::PlayerHttpFlv
-                                    | SubscribeType::PlayerHls => {
-                                        if let Some(meta_body_data) = self.cache.get_metadata() {
-                                            producer.send(meta_body_data).map_err(|_| ChannelError {
-                                                value: ChannelErrorValue::SendError,
-                                            })?;
-                                        }
-                                        if let Some(audio_seq_data) = self.cache.get_audio_seq() {
-                                            producer.send(audio_seq_data).map_err(|_| ChannelError {
-                                                value: ChannelErrorValue::SendError,
-                                            })?;
-                                        }
-                                        if let Some(video_seq_data) = self.cache.get_video_seq() {
-                                            producer.send(video_seq_data).map_err(|_| ChannelError {
-                                                value: ChannelErrorValue::SendError,
-                                            })?;
-                                        }
-                                        if let Some(gops_data) = self.cache.get_gops_data() {
-                                            for gop in gops_data {
-                                                for channel_data in gop.get_frame_data() {
-                                                    producer.send(channel_data).map_err(|_| ChannelError {
-                                                        value: ChannelErrorValue::SendError,
-                                                    })?;
-                                                }
-                                            }
-                                        }
-                                    }
-                                    SubscribeType::PublisherRtmp => {}
+                                let mut queue = SubscriberQueue::new(producer, self.backpressure_policy);
+                                if let Err(err) = self.send_cache_data(&mut queue, &info, start_mode) {
+                                    drop_subscriber(&info, &err);
+                                    continue;
                                 }
                                 self.subscriberid_to_producer
-                                    .insert(info.id, producer);
+                                    .insert(info.id, StreamSubscriber { info, queue, rtt: None });
                             }
                             TransmitterEvent::UnSubscribe { info } => {
                                 self.subscriberid_to_producer
@@ -115,75 +155,351 @@ impl Transmitter {
                             TransmitterEvent::UnPublish {} => {
                                 return Ok(());
                             }
+                            TransmitterEvent::ReplacePublisher { data_consumer, info } => {
+                                self.data_consumer = data_consumer;
+                                self.publisher = info;
+                                self.publisher_bytes_received = 0;
+                                self.publisher_rtt = None;
+                                self.publisher_last_data = Instant::now();
+                            }
                             TransmitterEvent::Api { sender } => {
-                                let avstatistic_data = self.cache.av_statistics.get_avstatistic_data().await;
+                                let mut avstatistic_data = self.cache.av_statistics.get_avstatistic_data().await;
+                                self.fill_session_statistics(&mut avstatistic_data);
                                 if let Err(err) = sender.send(avstatistic_data){
                                     tracing::info!("Transmitter send avstatistic data err: {}",err);
                                 }
                             }
+                            TransmitterEvent::UpdateRtt { id, is_publisher, rtt } => {
+                                if is_publisher {
+                                    self.publisher_rtt = Some(rtt);
+                                } else if let Some(subscriber) = self.subscriberid_to_producer.get_mut(&id) {
+                                    subscriber.rtt = Some(rtt);
+                                }
+                            }
+                            TransmitterEvent::MoveSubscribers { ids, target } => {
+                                let subscribers: Vec<(SubscriberInfo, SubscriberQueue)> = ids
+                                    .iter()
+                                    .filter_map(|id| self.subscriberid_to_producer.remove(id))
+                                    .map(|subscriber| (subscriber.info, subscriber.queue))
+                                    .collect();
+                                if let Err(err) = target.send(TransmitterEvent::AdoptSubscribers { subscribers }) {
+                                    tracing::error!("Transmitter move subscribers err: {}", err);
+                                }
+                            }
+                            TransmitterEvent::AdoptSubscribers { subscribers } => {
+                                for (info, mut queue) in subscribers {
+                                    if let Err(err) = self.send_switch_data(&mut queue) {
+                                        drop_subscriber(&info, &err);
+                                        continue;
+                                    }
+                                    self.subscriberid_to_producer
+                                        .insert(info.id, StreamSubscriber { info, queue, rtt: None });
+                                }
+                            }
                         }
                     }
                 }
 
                 data = self.data_consumer.recv() =>{
                     if let Some(val) = data {
+                        self.publisher_last_data = Instant::now();
+                        self.publisher_bytes_received += val.len() as u64;
                         match val {
                             ChannelData::MetaData { timestamp, data } => {
+                                let timestamp = self.normalizer.normalize_metadata(timestamp);
                                 self.cache.save_metadata(data, timestamp);
                             }
                             ChannelData::Audio { timestamp, data } => {
+                                let timestamp = self.normalize_timestamp(timestamp, false);
                                 self.cache.save_audio_data(data.clone(), timestamp).await?;
-
-                                let data = ChannelData::Audio {
-                                    timestamp,
-                                    data: data.clone(),
-                                };
-
-                                for (_, v) in self.subscriberid_to_producer.iter() {
-                                    if let Err(audio_err) = v.send(data.clone()).map_err(|_| ChannelError {
-                                            value: ChannelErrorValue::SendAudioError,
-                                    }){
-                                        tracing::error!("Transmitter send error: {}",audio_err);
-                                    }
-                                }
+                                self.send_to_subscribers(ChannelData::Audio { timestamp, data });
                             }
                             ChannelData::Video { timestamp, data } => {
+                                let timestamp = self.normalize_timestamp(timestamp, true);
                                 self.cache.save_video_data(data.clone(), timestamp).await?;
-
-                                let data = ChannelData::Video {
-                                    timestamp,
-                                    data: data.clone(),
-                                };
-                                for (_, v) in self.subscriberid_to_producer.iter() {
-                                    if let Err(video_err) = v.send(data.clone()).map_err(|_| ChannelError {
-                                        value: ChannelErrorValue::SendVideoError,
-                                    }){
-                                        tracing::error!("Transmitter send error: {}",video_err);
-                                    }
-                                }
+                                self.send_to_subscribers(ChannelData::Video { timestamp, data });
                             }
+                            ChannelData::Discontinuity { .. } => {}
                         }
                     }
                 }
 
+                _ = idle_check.tick() => {
+                    self.check_idle_publisher();
+                    self.reap_idle_subscribers();
+                }
             }
         }
 
         //Ok(())
     }
+
+    /* A publisher whose connection went half-open sends nothing but is never
+    closed, the stream is unpublished so its name can be published again. */
+    fn check_idle_publisher(&mut self) {
+        let timeout = self.idle_timeouts.publish;
+        if timeout.is_zero() || self.publisher_last_data.elapsed() < timeout {
+            return;
+        }
+
+        tracing::info!(
+            "Transmitter publisher {} is idle, app_name: {}, stream_name: {}",
+            self.publisher.id,
+            self.app_name,
+            self.stream_name
+        );
+        self.publisher.session_info.kick_token.cancel();
+        let event = ChannelEvent::UnPublish {
+            app_name: self.app_name.clone(),
+            stream_name: self.stream_name.clone(),
+            publisher_id: self.publisher.id,
+        };
+        if let Err(err) = self.channel_event_producer.send(event) {
+            tracing::error!("Transmitter send unpublish event err: {}", err);
+        }
+        /*the channels answer with TransmitterEvent::UnPublish*/
+        self.publisher_last_data = Instant::now();
+    }
+
+    /* The subscribers which stopped reading their queues are kicked. */
+    fn reap_idle_subscribers(&mut self) {
+        let timeout = self.idle_timeouts.subscriber;
+        if timeout.is_zero() {
+            return;
+        }
+
+        self.subscriberid_to_producer.retain(|id, subscriber| {
+            if !subscriber.queue.is_idle(timeout) {
+                return true;
+            }
+            tracing::info!("Transmitter subscriber {} is idle", id);
+            subscriber.info.session_info.kick_token.cancel();
+            false
+        });
+    }
+
+    /* A new player starts with the metadata, the sequence headers and the
+    cached gops its start mode asks for. */
+    fn send_cache_data(
+        &self,
+        queue: &mut SubscriberQueue,
+        info: &SubscriberInfo,
+        start_mode: StartMode,
+    ) -> Result<(), ChannelError> {
+        match info.sub_type {
+            SubscribeType::PlayerRtmp | SubscribeType::PlayerHttpFlv | SubscribeType::PlayerHls => {
+            }
+            SubscribeType::PublisherRtmp => return Ok(()),
+        }
+
+        let gops = self.get_cached_gops(start_mode);
+        queue.set_timestamp_base(self.get_first_timestamp(&gops).unwrap_or(0));
+        self.send_gops(queue, gops)
+    }
+
+    /* A subscriber moved from another stream gets the sequence headers of
+    this one and the newest cached gop, following the frames it already got. */
+    fn send_switch_data(&self, queue: &mut SubscriberQueue) -> Result<(), ChannelError> {
+        let gops = self.get_cached_gops(StartMode::LatestKeyframe);
+        queue.continue_timestamps(self.get_first_timestamp(&gops));
+        self.send_gops(queue, gops)
+    }
+
+    fn get_cached_gops(&self, start_mode: StartMode) -> VecDeque<Gop> {
+        let mut gops = self.cache.get_gops_data().unwrap_or_default();
+        if start_mode == StartMode::LatestKeyframe {
+            /*an audio only stream has no key frame to start at*/
+            let latest = match gops.iter().rposition(|gop| gop.starts_with_key_frame()) {
+                Some(idx) => gops.remove(idx),
+                None => gops.pop_back(),
+            };
+            gops = latest.into_iter().collect();
+        }
+        gops
+    }
+
+    /* The player starts at the first cached frame, or at the live edge
+    without a cache, unknown before the publisher sent any media. */
+    fn get_first_timestamp(&self, gops: &VecDeque<Gop>) -> Option<u32> {
+        gops.iter()
+            .find_map(|gop| gop.first_timestamp())
+            .or(self.last_timestamp)
+    }
+
+    fn send_gops(
+        &self,
+        queue: &mut SubscriberQueue,
+        mut gops: VecDeque<Gop>,
+    ) -> Result<(), ChannelError> {
+        let headers: Vec<ChannelData> = [
+            self.cache.get_metadata(),
+            self.cache.get_audio_seq(),
+            self.cache.get_video_seq(),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+
+        /*the oldest gops are left out of a cache too large for the queue,
+        the newest one is sent anyway*/
+        let mut frames = headers.len() + gops.iter().map(|gop| gop.len()).sum::<usize>();
+        while gops.len() > 1 && frames > queue.cache_capacity() {
+            if let Some(gop) = gops.pop_front() {
+                frames -= gop.len();
+            }
+        }
+
+        for data in headers {
+            queue.send_cached(data)?;
+        }
+        for gop in gops {
+            for channel_data in gop.get_frame_data() {
+                queue.send_cached(channel_data)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /* The publisher and the players of the stream. */
+    fn fill_session_statistics(&self, statistics: &mut StreamStatistics) {
+        statistics.publisher = Some(PublisherStatistics {
+            id: self.publisher.id.to_string(),
+            session_info: self.publisher.session_info.clone(),
+            bytes_received: self.publisher_bytes_received,
+            rtt: self.publisher_rtt,
+        });
+        statistics.subscribers = self
+            .subscriberid_to_producer
+            .values()
+            .map(|subscriber| SubscriberStatistics {
+                id: subscriber.info.id.to_string(),
+                sub_type: subscriber.info.sub_type.clone(),
+                session_info: subscriber.info.session_info.clone(),
+                bytes_sent: subscriber.queue.bytes_sent,
+                dropped_frames: subscriber.queue.dropped_frames,
+                rtt: subscriber.rtt,
+            })
+            .collect();
+    }
+
+    /* The subscribers are told about a discontinuity before the frame which
+    follows it, the remuxers start a new segment there. It is cached too, for
+    the players which start before it. */
+    fn normalize_timestamp(&mut self, timestamp: u32, is_video: bool) -> u32 {
+        let normalized = self.normalizer.normalize(timestamp, is_video);
+        if normalized.discontinuity {
+            tracing::warn!(
+                "timestamp discontinuity, app_name: {}, stream_name: {}, publisher timestamp: {}, continues at: {}",
+                self.app_name,
+                self.stream_name,
+                timestamp,
+                normalized.timestamp
+            );
+            self.cache.save_discontinuity(normalized.timestamp);
+            self.send_to_subscribers(ChannelData::Discontinuity {
+                timestamp: normalized.timestamp,
+            });
+        }
+        self.last_timestamp = Some(normalized.timestamp);
+        normalized.timestamp
+    }
+
+    /* The subscribers which are gone or lag too far behind are dropped, which
+    closes their queues. */
+    fn send_to_subscribers(&mut self, data: ChannelData) {
+        self.subscriberid_to_producer.retain(|_, subscriber| {
+            match subscriber.queue.send(data.clone()) {
+                Ok(()) => true,
+                Err(err) => {
+                    drop_subscriber(&subscriber.info, &err);
+                    false
+                }
+            }
+        });
+    }
+}
+
+/* Dropping the queue closes it, a player dropped for lagging behind is told
+so instead of being told the stream is unpublished. */
+fn drop_subscriber(info: &SubscriberInfo, err: &ChannelError) {
+    tracing::error!("Transmitter drops subscriber {}, error: {}", info.id, err);
+    if matches!(err.value, ChannelErrorValue::SubscriberLagging) {
+        info.session_info.lag_token.cancel();
+    }
+}
+
+/* The viewers of a stream, the hls remuxer and the relays are not. */
+fn is_player(info: &SubscriberInfo) -> bool {
+    matches!(
+        info.sub_type,
+        SubscribeType::PlayerRtmp | SubscribeType::PlayerHttpFlv
+    )
+}
+
+/* The session publishing a stream and the ones queued to publish it next. */
+struct StreamPublisher {
+    id: Uuid,
+    kick_token: CancellationToken,
+    queue: VecDeque<(PublisherInfo, PublishResponder)>,
+}
+
+impl StreamPublisher {
+    fn new(info: &PublisherInfo) -> Self {
+        Self {
+            id: info.id,
+            kick_token: info.session_info.kick_token.clone(),
+            queue: VecDeque::new(),
+        }
+    }
+}
+
+/* A subscriber waiting for the stream to be published. */
+struct PendingSubscriber {
+    info: SubscriberInfo,
+    responder: SubscribeResponder,
+    deadline: Instant,
 }
 
 pub struct ChannelsManager {
     //app_name to stream_name to producer
     channels: HashMap<String, HashMap<String, TransmitterEventProducer>>,
+    //app_name to stream_name to publisher
+    publishers: HashMap<String, HashMap<String, StreamPublisher>>,
+    //the policy for a publish to a stream which already has a publisher,
+    //configurable per app
+    publish_conflict_policy: PublishConflictPolicy,
+    //app_name to stream_name to the subscribers waiting for a publisher
+    pending_subscribers: HashMap<String, HashMap<String, Vec<PendingSubscriber>>>,
+    subscribe_wait_timeout: Duration,
+    //subscriber id to the app_name and stream_name it plays, used to kick it
+    subscribers: HashMap<Uuid, (String, String, SubscriberInfo)>,
+    //app_name to stream_name to the app_name and stream_name of the stream its
+    //players are moved to when it is unpublished
+    failover_streams: HashMap<String, HashMap<String, (String, String)>>,
+    //subscriber id to the app_name and stream_name it was moved away from,
+    //it is moved back once that stream is published again
+    failed_over_subscribers: HashMap<Uuid, (String, String)>,
+    //how the subscribers which do not keep up are treated
+    backpressure_policy: BackpressurePolicy,
+    //when an idle publisher or subscriber is dropped
+    idle_timeouts: IdleTimeouts,
     //event is consumed in Channels, produced from other rtmp sessions
     channel_event_consumer: ChannelEventConsumer,
     //event is produced from other rtmp sessions
     channel_event_producer: ChannelEventProducer,
     //client_event_producer: client_event_producer
     client_event_producer: ClientEventProducer,
-    // configure how many gops will be cached.
-    rtmp_gop_num: usize,
+    // configure how many gops will be cached, how long and how large they may be.
+    rtmp_gop_limits: GopLimits,
+    // where new players start in the cache, unless they ask for another mode
+    start_mode: StartMode,
+    //app_name to the settings overriding the ones above
+    app_profiles: HashMap<String, AppProfile>,
+    //app_name to stream_name to the flv file published and the token stopping it
+    file_sources: HashMap<String, HashMap<String, (FileSource, CancellationToken)>>,
+    //the directory of the files the api may publish, none forbids it
+    file_sources_dir: Option<PathBuf>,
     //The rtmp static push/pull and the hls transfer is triggered actively,
     //add a control switches separately.
     rtmp_push_enabled: bool,
@@ -205,12 +521,25 @@ impl ChannelsManager {
 
         Self {
             channels: HashMap::new(),
+            publishers: HashMap::new(),
+            publish_conflict_policy: PublishConflictPolicy::default(),
+            pending_subscribers: HashMap::new(),
+            subscribe_wait_timeout: Duration::from_secs(SUBSCRIBE_WAIT_TIMEOUT_SECS),
+            subscribers: HashMap::new(),
+            failover_streams: HashMap::new(),
+            failed_over_subscribers: HashMap::new(),
+            backpressure_policy: BackpressurePolicy::default(),
+            idle_timeouts: IdleTimeouts::default(),
             channel_event_consumer: event_consumer,
             channel_event_producer: event_producer,
             client_event_producer: client_producer,
             rtmp_push_enabled: false,
             rtmp_pull_enabled: false,
-            rtmp_gop_num: 1,
+            rtmp_gop_limits: GopLimits::default(),
+            start_mode: StartMode::default(),
+            app_profiles: HashMap::new(),
+            file_sources: HashMap::new(),
+            file_sources_dir: None,
             hls_enabled: false,
             //subscriber_statistics: HashMap::new(),
         }
@@ -228,13 +557,130 @@ impl ChannelsManager {
     }
 
     pub fn set_rtmp_gop_num(&mut self, gop_num: usize) {
-        self.rtmp_gop_num = gop_num;
+        self.rtmp_gop_limits.num = gop_num;
+    }
+
+    /* A zero duration does not limit how long the cached gops span. */
+    pub fn set_rtmp_gop_max_duration(&mut self, max_duration: Duration) {
+        self.rtmp_gop_limits.max_duration = max_duration;
+    }
+
+    /* Zero does not limit the memory taken by the cached gops. */
+    pub fn set_rtmp_gop_max_bytes(&mut self, max_bytes: usize) {
+        self.rtmp_gop_limits.max_bytes = max_bytes;
+    }
+
+    pub fn set_start_mode(&mut self, start_mode: StartMode) {
+        self.start_mode = start_mode;
     }
 
     pub fn set_hls_enabled(&mut self, enabled: bool) {
         self.hls_enabled = enabled;
     }
 
+    pub fn set_publish_conflict_policy(&mut self, policy: PublishConflictPolicy) {
+        self.publish_conflict_policy = policy;
+    }
+
+    pub fn set_app_profile(&mut self, app_name: String, profile: AppProfile) {
+        self.app_profiles.insert(app_name, profile);
+    }
+
+    pub fn set_failover_stream(
+        &mut self,
+        app_name: String,
+        stream_name: String,
+        backup_app_name: String,
+        backup_stream_name: String,
+    ) {
+        self.failover_streams
+            .entry(app_name)
+            .or_default()
+            .insert(stream_name, (backup_app_name, backup_stream_name));
+    }
+
+    /* A zero timeout fails the subscribes to unpublished streams at once. */
+    pub fn set_subscribe_wait_timeout(&mut self, timeout: Duration) {
+        self.subscribe_wait_timeout = timeout;
+    }
+
+    pub fn set_backpressure_policy(&mut self, policy: BackpressurePolicy) {
+        self.backpressure_policy = policy;
+    }
+
+    pub fn set_file_sources_dir(&mut self, dir: PathBuf) {
+        self.file_sources_dir = Some(dir);
+    }
+
+    /* A zero timeout keeps an idle publisher's stream published. */
+    pub fn set_publish_idle_timeout(&mut self, timeout: Duration) {
+        self.idle_timeouts.publish = timeout;
+    }
+
+    /* A zero timeout keeps the subscribers which stopped reading. */
+    pub fn set_subscriber_idle_timeout(&mut self, timeout: Duration) {
+        self.idle_timeouts.subscriber = timeout;
+    }
+
+    fn get_publish_conflict_policy(&self, app_name: &String) -> PublishConflictPolicy {
+        self.app_profiles
+            .get(app_name)
+            .and_then(|profile| profile.publish_conflict)
+            .unwrap_or(self.publish_conflict_policy)
+    }
+
+    fn get_gop_limits(&self, app_name: &String) -> GopLimits {
+        let mut limits = self.rtmp_gop_limits;
+        if let Some(profile) = self.app_profiles.get(app_name) {
+            if let Some(num) = profile.gop_num {
+                limits.num = num;
+            }
+            if let Some(max_duration) = profile.gop_max_duration {
+                limits.max_duration = max_duration;
+            }
+            if let Some(max_bytes) = profile.gop_max_bytes {
+                limits.max_bytes = max_bytes;
+            }
+        }
+        limits
+    }
+
+    /* Checks the limits of the app's profile before a player is added. */
+    fn check_subscribe_allowed(
+        &self,
+        app_name: &String,
+        sub_info: &SubscriberInfo,
+    ) -> Result<(), ChannelError> {
+        let profile = match self.app_profiles.get(app_name) {
+            Some(profile) => profile,
+            None => return Ok(()),
+        };
+
+        if matches!(sub_info.sub_type, SubscribeType::PlayerHttpFlv)
+            && profile.httpflv_enabled == Some(false)
+        {
+            return Err(ChannelError {
+                value: ChannelErrorValue::HttpFlvDisabled,
+            });
+        }
+
+        /*the hls remuxer and the relays are not counted*/
+        if let Some(max_subscribers) = profile.max_subscribers.filter(|max| *max > 0) {
+            let players = self
+                .subscribers
+                .values()
+                .filter(|(app, _, info)| app == app_name && is_player(info))
+                .count();
+            if is_player(sub_info) && players >= max_subscribers {
+                return Err(ChannelError {
+                    value: ChannelErrorValue::TooManySubscribers,
+                });
+            }
+        }
+
+        Ok(())
+    }
+
     pub fn get_channel_event_producer(&mut self) -> ChannelEventProducer {
         self.channel_event_producer.clone()
     }
@@ -244,33 +690,38 @@ impl ChannelsManager {
     }
 
     pub async fn event_loop(&mut self) {
-        while let Some(message) = self.channel_event_consumer.recv().await {
+        loop {
+            let deadline = self.next_pending_deadline();
+            let message = tokio::select! {
+                message = self.channel_event_consumer.recv() => message,
+                _ = Self::wait_until(deadline) => {
+                    self.expire_pending_subscribers();
+                    continue;
+                }
+            };
+            let message = match message {
+                Some(message) => message,
+                None => break,
+            };
+
             tracing::info!("{}", message);
             match message {
                 ChannelEvent::Publish {
                     app_name,
                     stream_name,
+                    info,
                     responder,
                 } => {
-                    let rv = self.publish(&app_name, &stream_name);
-                    match rv {
-                        Ok(producer) => {
-                            if responder.send(producer).is_err() {
-                                tracing::error!("event_loop responder send err");
-                            }
-                        }
-                        Err(err) => {
-                            tracing::error!("event_loop Publish err: {}\n", err);
-                            continue;
-                        }
-                    }
+                    self.handle_publish(&app_name, &stream_name, info, responder)
+                        .await;
                 }
 
                 ChannelEvent::UnPublish {
                     app_name,
                     stream_name,
+                    publisher_id,
                 } => {
-                    if let Err(err) = self.unpublish(&app_name, &stream_name) {
+                    if let Err(err) = self.handle_unpublish(&app_name, &stream_name, publisher_id) {
                         tracing::error!(
                             "event_loop Unpublish err: {} with app name: {} stream name :{}\n",
                             err,
@@ -285,25 +736,21 @@ impl ChannelsManager {
                     info,
                     responder,
                 } => {
-                    let rv = self.subscribe(&app_name, &stream_name, info).await;
-                    match rv {
-                        Ok(consumer) => {
-                            if responder.send(consumer).is_err() {
-                                tracing::error!("event_loop Subscribe err");
-                            }
-                        }
-                        Err(err) => {
-                            tracing::error!("event_loop Subscribe error: {}", err);
-                            continue;
-                        }
-                    }
+                    self.handle_subscribe(&app_name, &stream_name, info, responder)
+                        .await;
                 }
                 ChannelEvent::UnSubscribe {
                     app_name,
                     stream_name,
                     info,
                 } => {
-                    let _ = self.unsubscribe(&app_name, &stream_name, info);
+                    self.failed_over_subscribers.remove(&info.id);
+                    /*a subscriber moved to a backup stream is removed from there*/
+                    if let Some((app_name, stream_name, _)) = self.subscribers.remove(&info.id) {
+                        let _ = self.unsubscribe(&app_name, &stream_name, info);
+                    } else if !self.remove_pending_subscriber(&app_name, &stream_name, &info.id) {
+                        let _ = self.unsubscribe(&app_name, &stream_name, info);
+                    }
                 }
 
                 ChannelEvent::Api {
@@ -314,8 +761,105 @@ impl ChannelsManager {
                         tracing::error!("event_loop api error: {}", err);
                     }
                 }
+                ChannelEvent::UpdateRtt {
+                    app_name,
+                    stream_name,
+                    id,
+                    is_publisher,
+                    rtt,
+                } => {
+                    if let Err(err) =
+                        self.update_rtt(&app_name, &stream_name, id, is_publisher, rtt)
+                    {
+                        tracing::error!("event_loop update rtt error: {}", err);
+                    }
+                }
+                ChannelEvent::KickPublisher {
+                    app_name,
+                    stream_name,
+                    responder,
+                } => {
+                    let rv = self.kick_publisher(&app_name, &stream_name);
+                    if let Err(err) = &rv {
+                        tracing::error!("event_loop kick publisher error: {}", err);
+                    }
+                    if responder.send(rv).is_err() {
+                        tracing::error!("event_loop kick publisher responder send err");
+                    }
+                }
+                ChannelEvent::KickSubscriber { id, responder } => {
+                    let rv = self.kick_subscriber(id);
+                    if let Err(err) = &rv {
+                        tracing::error!("event_loop kick subscriber error: {}", err);
+                    }
+                    if responder.send(rv).is_err() {
+                        tracing::error!("event_loop kick subscriber responder send err");
+                    }
+                }
+                ChannelEvent::StartFileSource { source, responder } => {
+                    let rv = self
+                        .resolve_file_source(source)
+                        .and_then(|source| self.start_file_source(source));
+                    if let Err(err) = &rv {
+                        tracing::error!("event_loop start file source error: {}", err);
+                    }
+                    if responder.send(rv).is_err() {
+                        tracing::error!("event_loop start file source responder send err");
+                    }
+                }
+                ChannelEvent::StopFileSource {
+                    app_name,
+                    stream_name,
+                    responder,
+                } => {
+                    let rv = self.stop_file_source(&app_name, &stream_name);
+                    if responder.send(rv).is_err() {
+                        tracing::error!("event_loop stop file source responder send err");
+                    }
+                }
+                ChannelEvent::ListFileSources { responder } => {
+                    if responder.send(self.list_file_sources()).is_err() {
+                        tracing::error!("event_loop list file sources responder send err");
+                    }
+                }
+            }
+        }
+    }
+
+    fn update_rtt(
+        &mut self,
+        app_name: &String,
+        stream_name: &String,
+        id: Uuid,
+        is_publisher: bool,
+        rtt: u32,
+    ) -> Result<(), ChannelError> {
+        match self.channels.get(app_name) {
+            Some(app) => match app.get(stream_name) {
+                Some(event_sender) => {
+                    let event = TransmitterEvent::UpdateRtt {
+                        id,
+                        is_publisher,
+                        rtt,
+                    };
+                    event_sender.send(event).map_err(|_| ChannelError {
+                        value: ChannelErrorValue::SendError,
+                    })?;
+                }
+                None => {
+                    return Err(ChannelError {
+                        value: ChannelErrorValue::NoStreamName,
+                    })
+                }
+            },
+            None => {
+                return Err(ChannelError {
+                    value: ChannelErrorValue::NoAppName,
+                })
             }
         }
+
+        Ok(())
     }
 
     fn api(
@@ -348,29 +892,183 @@ impl ChannelsManager {
         Ok(())
     }
 
+    async fn handle_subscribe(
+        &mut self,
+        app_name: &String,
+        stream_name: &String,
+        sub_info: SubscriberInfo,
+        responder: SubscribeResponder,
+    ) {
+        let exists = self
+            .channels
+            .get(app_name)
+            .is_some_and(|streams| streams.contains_key(stream_name));
+
+        /*a player the app does not allow is not kept waiting*/
+        let allowed = self.check_subscribe_allowed(app_name, &sub_info);
+        if exists || self.subscribe_wait_timeout.is_zero() || allowed.is_err() {
+            let rv = self.subscribe(app_name, stream_name, sub_info).await;
+            if let Err(err) = &rv {
+                tracing::error!("event_loop Subscribe error: {}", err);
+            }
+            if responder.send(rv).is_err() {
+                tracing::error!("event_loop Subscribe err");
+            }
+            return;
+        }
+
+        if let Err(err) = self.request_pull(app_name, stream_name) {
+            tracing::error!("event_loop Subscribe pull error: {}", err);
+        }
+
+        tracing::info!(
+            "subscriber {} waits for app_name: {}, stream_name: {}",
+            sub_info.id,
+            app_name,
+            stream_name
+        );
+        sub_info.session_info.wait_token.cancel();
+        self.pending_subscribers
+            .entry(app_name.clone())
+            .or_default()
+            .entry(stream_name.clone())
+            .or_default()
+            .push(PendingSubscriber {
+                info: sub_info,
+                responder,
+                deadline: Instant::now() + self.subscribe_wait_timeout,
+            });
+    }
+
+    /* Subscribes the players which were waiting for the stream. */
+    async fn attach_pending_subscribers(&mut self, app_name: &String, stream_name: &String) {
+        let pending = match self.pending_subscribers.get_mut(app_name) {
+            Some(streams) => streams.remove(stream_name).unwrap_or_default(),
+            None => return,
+        };
+
+        for subscriber in pending {
+            if subscriber.responder.is_closed() {
+                continue;
+            }
+            let info = subscriber.info.clone();
+            let rv = self.subscribe(app_name, stream_name, subscriber.info).await;
+            /*the subscriber left while the stream was being attached*/
+            if let Err(Ok(_)) = subscriber.responder.send(rv) {
+                tracing::error!("attach pending subscriber {} err", info.id);
+                let _ = self.unsubscribe(app_name, stream_name, info);
+            }
+        }
+    }
+
+    fn remove_pending_subscriber(
+        &mut self,
+        app_name: &String,
+        stream_name: &String,
+        id: &Uuid,
+    ) -> bool {
+        match self
+            .pending_subscribers
+            .get_mut(app_name)
+            .and_then(|streams| streams.get_mut(stream_name))
+        {
+            Some(pending) => {
+                let len = pending.len();
+                pending.retain(|subscriber| subscriber.info.id != *id);
+                len != pending.len()
+            }
+            None => false,
+        }
+    }
+
+    fn next_pending_deadline(&self) -> Option<Instant> {
+        self.pending_subscribers
+            .values()
+            .flat_map(|streams| streams.values())
+            .flatten()
+            .map(|subscriber| subscriber.deadline)
+            .min()
+    }
+
+    async fn wait_until(deadline: Option<Instant>) {
+        match deadline {
+            Some(deadline) => sleep_until(deadline).await,
+            None => std::future::pending::<()>().await,
+        }
+    }
+
+    /* Fails the subscribers which waited longer than the wait timeout. */
+    fn expire_pending_subscribers(&mut self) {
+        let now = Instant::now();
+
+        for (app_name, streams) in self.pending_subscribers.iter_mut() {
+            for (stream_name, pending) in streams.iter_mut() {
+                let (expired, waiting) = std::mem::take(pending)
+                    .into_iter()
+                    .partition(|subscriber| subscriber.deadline <= now);
+                *pending = waiting;
+
+                for subscriber in expired {
+                    tracing::info!(
+                        "subscriber {} timed out waiting for app_name: {}, stream_name: {}",
+                        subscriber.info.id,
+                        app_name,
+                        stream_name
+                    );
+                    let _ = subscriber.responder.send(Err(ChannelError {
+                        value: ChannelErrorValue::NoAppOrStreamName,
+                    }));
+                }
+            }
+            streams.retain(|_, pending| !pending.is_empty());
+        }
+        self.pending_subscribers
+            .retain(|_, streams| !streams.is_empty());
+    }
+
     //player subscribe a stream
     pub async fn subscribe(
         &mut self,
         app_name: &String,
         stream_name: &String,
         sub_info: SubscriberInfo,
-    ) -> Result<mpsc::UnboundedReceiver<ChannelData>, ChannelError> {
+    ) -> Result<SubscriberDataConsumer, ChannelError> {
+        self.check_subscribe_allowed(app_name, &sub_info)?;
+
         if let Some(val) = self.channels.get_mut(app_name) {
             if let Some(producer) = val.get_mut(stream_name) {
-                let (channel_data_producer, channel_data_consumer) = mpsc::unbounded_channel();
+                let (channel_data_producer, channel_data_consumer) =
+                    mpsc::channel(self.backpressure_policy.queue_size.max(1));
                 let event = TransmitterEvent::Subscribe {
                     producer: channel_data_producer,
-                    info: sub_info,
+                    start_mode: sub_info.start_mode.unwrap_or(self.start_mode),
+                    info: sub_info.clone(),
                 };
 
                 producer.send(event).map_err(|_| ChannelError {
                     value: ChannelErrorValue::SendError,
                 })?;
 
+                self.subscribers.insert(
+                    sub_info.id,
+                    (app_name.clone(), stream_name.clone(), sub_info),
+                );
                 return Ok(channel_data_consumer);
             }
         }
 
+        self.request_pull(app_name, stream_name)?;
+
+        Err(ChannelError {
+            value: ChannelErrorValue::NoAppOrStreamName,
+        })
+    }
+
+    fn request_pull(
+        &mut self,
+        app_name: &String,
+        stream_name: &String,
+    ) -> Result<(), ChannelError> {
         if self.rtmp_pull_enabled {
             tracing::info!(
                 "subscribe: try to pull stream, app_name: {}, stream_name: {}",
@@ -391,9 +1089,7 @@ impl ChannelsManager {
                 })?;
         }
 
-        Err(ChannelError {
-            value: ChannelErrorValue::NoAppOrStreamName,
-        })
+        Ok(())
     }
 
     pub fn unsubscribe(
@@ -427,10 +1123,330 @@ impl ChannelsManager {
     }
 
     //publish a stream
+    fn get_stream_publisher(
+        &mut self,
+        app_name: &String,
+        stream_name: &String,
+    ) -> Option<&mut StreamPublisher> {
+        self.publishers
+            .get_mut(app_name)
+            .and_then(|streams| streams.get_mut(stream_name))
+    }
+
+    async fn handle_publish(
+        &mut self,
+        app_name: &String,
+        stream_name: &String,
+        info: PublisherInfo,
+        responder: PublishResponder,
+    ) {
+        let exists = self
+            .channels
+            .get(app_name)
+            .is_some_and(|streams| streams.contains_key(stream_name));
+
+        let rv = if !exists {
+            let publisher = StreamPublisher::new(&info);
+            let rv = self.publish(app_name, stream_name, info);
+            if rv.is_ok() {
+                self.publishers
+                    .entry(app_name.clone())
+                    .or_default()
+                    .insert(stream_name.clone(), publisher);
+                self.attach_pending_subscribers(app_name, stream_name).await;
+            }
+            rv
+        } else {
+            match self.get_publish_conflict_policy(app_name) {
+                PublishConflictPolicy::Reject => Err(ChannelError {
+                    value: ChannelErrorValue::Exists,
+                }),
+                PublishConflictPolicy::KickExisting => {
+                    tracing::info!(
+                        "publisher {} takes over app_name: {}, stream_name: {}",
+                        info.id,
+                        app_name,
+                        stream_name
+                    );
+                    self.replace_publisher(app_name, stream_name, info)
+                }
+                PublishConflictPolicy::Queue => {
+                    match self.get_stream_publisher(app_name, stream_name) {
+                        Some(publisher) => {
+                            tracing::info!(
+                                "publisher {} is queued, app_name: {}, stream_name: {}",
+                                info.id,
+                                app_name,
+                                stream_name
+                            );
+                            publisher.queue.push_back((info, responder));
+                            return;
+                        }
+                        None => Err(ChannelError {
+                            value: ChannelErrorValue::Exists,
+                        }),
+                    }
+                }
+            }
+        };
+
+        if let Err(err) = &rv {
+            tracing::error!("event_loop Publish err: {}\n", err);
+        }
+        if responder.send(rv).is_err() {
+            tracing::error!("event_loop responder send err");
+        }
+    }
+
+    /*Hands the stream's Transmitter to another publisher, the subscribers
+    keep receiving from the same Transmitter.*/
+    fn replace_publisher(
+        &mut self,
+        app_name: &String,
+        stream_name: &String,
+        info: PublisherInfo,
+    ) -> Result<ChannelDataProducer, ChannelError> {
+        let publisher = StreamPublisher::new(&info);
+        let event_sender = self
+            .channels
+            .get(app_name)
+            .and_then(|streams| streams.get(stream_name))
+            .ok_or(ChannelError {
+                value: ChannelErrorValue::NoStreamName,
+            })?;
+
+        let (data_publisher, data_consumer) = mpsc::unbounded_channel();
+        event_sender
+            .send(TransmitterEvent::ReplacePublisher {
+                data_consumer,
+                info,
+            })
+            .map_err(|_| ChannelError {
+                value: ChannelErrorValue::SendError,
+            })?;
+
+        match self.get_stream_publisher(app_name, stream_name) {
+            Some(current) => {
+                /*the replaced publisher's session is closed*/
+                current.kick_token.cancel();
+                current.id = publisher.id;
+                current.kick_token = publisher.kick_token;
+            }
+            None => {
+                self.publishers
+                    .entry(app_name.clone())
+                    .or_default()
+                    .insert(stream_name.clone(), publisher);
+            }
+        }
+
+        Ok(data_publisher)
+    }
+
+    fn handle_unpublish(
+        &mut self,
+        app_name: &String,
+        stream_name: &String,
+        publisher_id: Uuid,
+    ) -> Result<(), ChannelError> {
+        match self.get_stream_publisher(app_name, stream_name) {
+            /*a queued or a replaced publisher leaves*/
+            Some(publisher) if publisher.id != publisher_id => {
+                publisher.queue.retain(|(info, _)| info.id != publisher_id);
+                return Ok(());
+            }
+            Some(_) => {}
+            None => return self.unpublish(app_name, stream_name),
+        }
+
+        while let Some((next_info, responder)) = self
+            .get_stream_publisher(app_name, stream_name)
+            .and_then(|publisher| publisher.queue.pop_front())
+        {
+            if responder.is_closed() {
+                continue;
+            }
+            let next_id = next_info.id;
+            let rv = self.replace_publisher(app_name, stream_name, next_info);
+            if responder.send(rv).is_ok() {
+                tracing::info!(
+                    "queued publisher {} takes over app_name: {}, stream_name: {}",
+                    next_id,
+                    app_name,
+                    stream_name
+                );
+                return Ok(());
+            }
+        }
+
+        if let Some(streams) = self.publishers.get_mut(app_name) {
+            streams.remove(stream_name);
+        }
+
+        self.unpublish(app_name, stream_name)
+    }
+
+    /*The kicked session stops on its own, the stream is unpublished right
+    away so its name can be published again.*/
+    fn kick_publisher(
+        &mut self,
+        app_name: &String,
+        stream_name: &String,
+    ) -> Result<(), ChannelError> {
+        match self.get_stream_publisher(app_name, stream_name) {
+            Some(publisher) => {
+                tracing::info!(
+                    "kick publisher {}, app_name: {}, stream_name: {}",
+                    publisher.id,
+                    app_name,
+                    stream_name
+                );
+                publisher.kick_token.cancel();
+                let publisher_id = publisher.id;
+                self.handle_unpublish(app_name, stream_name, publisher_id)
+            }
+            None => self.unpublish(app_name, stream_name),
+        }
+    }
+
+    /*A waiting subscriber is failed, a playing one is removed from the
+    stream's Transmitter.*/
+    fn kick_subscriber(&mut self, id: Uuid) -> Result<(), ChannelError> {
+        if let Some((app_name, stream_name, info)) = self.subscribers.remove(&id) {
+            self.failed_over_subscribers.remove(&id);
+            tracing::info!(
+                "kick subscriber {}, app_name: {}, stream_name: {}",
+                id,
+                app_name,
+                stream_name
+            );
+            info.session_info.kick_token.cancel();
+            /*the stream may be unpublished already*/
+            let _ = self.unsubscribe(&app_name, &stream_name, info);
+            return Ok(());
+        }
+
+        for pending in self
+            .pending_subscribers
+            .values_mut()
+            .flat_map(|streams| streams.values_mut())
+        {
+            if let Some(idx) = pending
+                .iter()
+                .position(|subscriber| subscriber.info.id == id)
+            {
+                tracing::info!("kick waiting subscriber {}", id);
+                let subscriber = pending.remove(idx);
+                subscriber.info.session_info.kick_token.cancel();
+                let _ = subscriber.responder.send(Err(ChannelError {
+                    value: ChannelErrorValue::Kicked,
+                }));
+                return Ok(());
+            }
+        }
+
+        Err(ChannelError {
+            value: ChannelErrorValue::NoSubscriber,
+        })
+    }
+
+    /* The publisher cancels its token once it stops, a finished file or a
+    failed publish frees the stream name of its source. */
+    fn remove_stopped_file_sources(&mut self) {
+        for streams in self.file_sources.values_mut() {
+            streams.retain(|_, (_, token)| !token.is_cancelled());
+        }
+        self.file_sources.retain(|_, streams| !streams.is_empty());
+    }
+
+    /* The sources requested through the api may only publish the files inside
+    the file sources directory, a relative path is relative to it. */
+    fn resolve_file_source(&self, mut source: FileSource) -> Result<FileSource, ChannelError> {
+        let not_allowed = || ChannelError {
+            value: ChannelErrorValue::FileSourceNotAllowed,
+        };
+        let dir = self.file_sources_dir.as_ref().ok_or_else(not_allowed)?;
+        let dir = dir.canonicalize().map_err(|_| not_allowed())?;
+        let path = dir
+            .join(&source.path)
+            .canonicalize()
+            .map_err(|_| not_allowed())?;
+        if !path.starts_with(&dir) || !path.is_file() {
+            return Err(not_allowed());
+        }
+
+        source.path = path.to_string_lossy().to_string();
+        Ok(source)
+    }
+
+    pub fn start_file_source(&mut self, source: FileSource) -> Result<(), ChannelError> {
+        self.remove_stopped_file_sources();
+        let streams = self
+            .file_sources
+            .entry(source.app_name.clone())
+            .or_default();
+        if streams.contains_key(&source.stream_name) {
+            return Err(ChannelError {
+                value: ChannelErrorValue::Exists,
+            });
+        }
+
+        let mut publisher = FilePublisher::new(source.clone(), self.channel_event_producer.clone());
+        streams.insert(
+            source.stream_name.clone(),
+            (source.clone(), publisher.kick_token()),
+        );
+
+        tokio::spawn(async move {
+            if let Err(err) = publisher.run().await {
+                tracing::error!(
+                    "file source error, path: {}, app_name: {}, stream_name: {}, error: {}",
+                    source.path,
+                    source.app_name,
+                    source.stream_name,
+                    err
+                );
+            }
+        });
+
+        Ok(())
+    }
+
+    fn stop_file_source(
+        &mut self,
+        app_name: &String,
+        stream_name: &String,
+    ) -> Result<(), ChannelError> {
+        self.remove_stopped_file_sources();
+        match self
+            .file_sources
+            .get_mut(app_name)
+            .and_then(|streams| streams.remove(stream_name))
+        {
+            Some((_, token)) => {
+                token.cancel();
+                Ok(())
+            }
+            None => Err(ChannelError {
+                value: ChannelErrorValue::NoFileSource,
+            }),
+        }
+    }
+
+    fn list_file_sources(&mut self) -> Vec<FileSource> {
+        self.remove_stopped_file_sources();
+        self.file_sources
+            .values()
+            .flat_map(|streams| streams.values())
+            .map(|(source, _)| source.clone())
+            .collect()
+    }
+
     pub fn publish(
         &mut self,
         app_name: &String,
         stream_name: &String,
+        info: PublisherInfo,
     ) -> Result<ChannelDataProducer, ChannelError> {
         match self.channels.get_mut(app_name) {
             Some(val) => {
@@ -446,6 +1462,24 @@ impl ChannelsManager {
             }
         }
 
+        if let Some(max_publishers) = self
+            .app_profiles
+            .get(app_name)
+            .and_then(|profile| profile.max_publishers)
+            .filter(|max| *max > 0)
+        {
+            let publishers = self
+                .channels
+                .get(app_name)
+                .map_or(0, |streams| streams.len());
+            if publishers >= max_publishers {
+                return Err(ChannelError {
+                    value: ChannelErrorValue::TooManyPublishers,
+                });
+            }
+        }
+        let gop_limits = self.get_gop_limits(app_name);
+
         if let Some(stream_map) = self.channels.get_mut(app_name) {
             let (event_publisher, event_consumer) = mpsc::unbounded_channel();
             let (data_publisher, data_consumer) = mpsc::unbounded_channel();
@@ -455,7 +1489,11 @@ impl ChannelsManager {
                 stream_name.clone(),
                 data_consumer,
                 event_consumer,
-                self.rtmp_gop_num,
+                self.channel_event_producer.clone(),
+                gop_limits,
+                self.backpressure_policy,
+                self.idle_timeouts,
+                info,
             );
 
             let app_name_clone = app_name.clone();
@@ -479,6 +1517,7 @@ impl ChannelsManager {
             });
 
             stream_map.insert(stream_name.clone(), event_publisher);
+            self.fail_back(app_name, stream_name);
 
             if self.rtmp_push_enabled || self.hls_enabled {
                 let client_event = ClientEvent::Publish {
@@ -502,7 +1541,112 @@ impl ChannelsManager {
         }
     }
 
+    /* Moves the players of a stream which is unpublished to its backup
+    stream, if that one is published. */
+    fn fail_over(&mut self, app_name: &String, stream_name: &String) {
+        let (backup_app_name, backup_stream_name) = match self
+            .failover_streams
+            .get(app_name)
+            .and_then(|streams| streams.get(stream_name))
+        {
+            Some(backup) => backup.clone(),
+            None => return,
+        };
+        let (producer, target) = match (
+            self.get_transmitter(app_name, stream_name),
+            self.get_transmitter(&backup_app_name, &backup_stream_name),
+        ) {
+            (Some(producer), Some(target)) => (producer.clone(), target.clone()),
+            _ => return,
+        };
+
+        let mut ids = Vec::new();
+        for (id, (app, stream, info)) in &mut self.subscribers {
+            if app == app_name && stream == stream_name && is_player(info) {
+                *app = backup_app_name.clone();
+                *stream = backup_stream_name.clone();
+                ids.push(*id);
+            }
+        }
+        if ids.is_empty() {
+            return;
+        }
+        for id in &ids {
+            /*a subscriber goes back to the stream it played first*/
+            self.failed_over_subscribers
+                .entry(*id)
+                .or_insert_with(|| (app_name.clone(), stream_name.clone()));
+        }
+
+        tracing::info!(
+            "move {} subscribers of app_name: {}, stream_name: {} to app_name: {}, stream_name: {}",
+            ids.len(),
+            app_name,
+            stream_name,
+            backup_app_name,
+            backup_stream_name
+        );
+        if let Err(err) = producer.send(TransmitterEvent::MoveSubscribers { ids, target }) {
+            tracing::error!("fail over send err: {}", err);
+        }
+    }
+
+    /* Moves the players which were moved away from a stream back to it once
+    it is published again. */
+    fn fail_back(&mut self, app_name: &String, stream_name: &String) {
+        let target = match self.get_transmitter(app_name, stream_name) {
+            Some(target) => target.clone(),
+            None => return,
+        };
+
+        let mut moves: HashMap<(String, String), Vec<Uuid>> = HashMap::new();
+        self.failed_over_subscribers.retain(|id, (app, stream)| {
+            if app != app_name || stream != stream_name {
+                return true;
+            }
+            if let Some((current_app, current_stream, _)) = self.subscribers.get_mut(id) {
+                let current = (
+                    std::mem::replace(current_app, app_name.clone()),
+                    std::mem::replace(current_stream, stream_name.clone()),
+                );
+                moves.entry(current).or_default().push(*id);
+            }
+            false
+        });
+
+        for ((current_app, current_stream), ids) in moves {
+            tracing::info!(
+                "move {} subscribers of app_name: {}, stream_name: {} back to app_name: {}, stream_name: {}",
+                ids.len(),
+                current_app,
+                current_stream,
+                app_name,
+                stream_name
+            );
+            if let Some(producer) = self.get_transmitter(&current_app, &current_stream) {
+                let event = TransmitterEvent::MoveSubscribers {
+                    ids,
+                    target: target.clone(),
+                };
+                if let Err(err) = producer.send(event) {
+                    tracing::error!("fail back send err: {}", err);
+                }
+            }
+        }
+    }
+
+    fn get_transmitter(
+        &self,
+        app_name: &String,
+        stream_name: &String,
+    ) -> Option<&TransmitterEventProducer> {
+        self.channels
+            .get(app_name)
+            .and_then(|streams| streams.get(stream_name))
+    }
+
     fn unpublish(&mut self, app_name: &String, stream_name: &String) -> Result<(), ChannelError> {
+        self.fail_over(app_name, stream_name);
         match self.channels.get_mut(app_name) {
             Some(val) => match val.get_mut(stream_name) {
                 Some(producer) => {
@@ -535,13 +1679,36 @@ impl ChannelsManager {
 }
 
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
 
     use std::cell::RefCell;
 
-    use std::sync::Arc;
+    use {
+        crate::session::{
+            common::{PublisherInfo, SessionInfo, SubscriberInfo},
+            define::SubscribeType,
+        },
+        std::sync::Arc,
+        uuid::Uuid,
+    };
     pub struct TestFunc {}
 
+    fn publisher_info(id: Uuid) -> PublisherInfo {
+        PublisherInfo {
+            id,
+            session_info: SessionInfo::new(None),
+        }
+    }
+
+    pub(crate) fn subscriber_info(sub_type: SubscribeType) -> SubscriberInfo {
+        SubscriberInfo {
+            id: Uuid::new_v4(),
+            sub_type,
+            session_info: SessionInfo::new(None),
+            start_mode: None,
+        }
+    }
+
     impl TestFunc {
         fn new() -> Self {
             Self {}
@@ -555,4 +1722,749 @@ mod tests {
         let channel = Arc::new(RefCell::new(TestFunc::new()));
         channel.borrow_mut().aaa();
     }
+
+    #[test]
+    fn test_resolve_file_source() {
+        use {
+            super::{errors::ChannelErrorValue, ChannelsManager},
+            crate::file_source::define::FileSource,
+        };
+
+        let root = std::env::temp_dir().join(format!("file_sources_{}", Uuid::new_v4()));
+        let dir = root.join("media");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("a.flv"), b"").unwrap();
+        std::fs::write(root.join("b.flv"), b"").unwrap();
+
+        let source = |path: &str| FileSource {
+            path: path.to_string(),
+            app_name: String::from("live"),
+            stream_name: String::from("test"),
+            looped: false,
+        };
+        let not_allowed = |manager: &ChannelsManager, path: &str| {
+            matches!(
+                manager.resolve_file_source(source(path)),
+                Err(err) if matches!(err.value, ChannelErrorValue::FileSourceNotAllowed)
+            )
+        };
+
+        /*the api publishes no file without a directory*/
+        let mut manager = ChannelsManager::new();
+        assert!(not_allowed(&manager, dir.join("a.flv").to_str().unwrap()));
+
+        manager.set_file_sources_dir(dir.clone());
+        let resolved = manager.resolve_file_source(source("a.flv")).unwrap();
+        assert_eq!(
+            resolved.path,
+            dir.join("a.flv").canonicalize().unwrap().to_string_lossy()
+        );
+        assert!(manager
+            .resolve_file_source(source(dir.join("a.flv").to_str().unwrap()))
+            .is_ok());
+        assert!(not_allowed(&manager, "../b.flv"));
+        assert!(not_allowed(&manager, root.join("b.flv").to_str().unwrap()));
+        assert!(not_allowed(&manager, "/dev/zero"));
+        assert!(not_allowed(&manager, "missing.flv"));
+        assert!(not_allowed(&manager, "."));
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_publish_conflict() {
+        use {
+            super::{
+                define::{AppProfile, PublishConflictPolicy},
+                ChannelsManager,
+            },
+            tokio::sync::oneshot,
+        };
+
+        let mut manager = ChannelsManager::new();
+        for (app_name, policy) in [
+            ("kick", PublishConflictPolicy::KickExisting),
+            ("queue", PublishConflictPolicy::Queue),
+        ] {
+            manager.set_app_profile(
+                String::from(app_name),
+                AppProfile {
+                    publish_conflict: Some(policy),
+                    ..Default::default()
+                },
+            );
+        }
+        let stream_name = String::from("test");
+
+        for app_name in ["live", "kick", "queue"] {
+            let app_name = String::from(app_name);
+            let (first_id, second_id) = (Uuid::new_v4(), Uuid::new_v4());
+
+            let first_info = publisher_info(first_id);
+            let first_token = first_info.session_info.kick_token.clone();
+            let (sender, receiver) = oneshot::channel();
+            manager
+                .handle_publish(&app_name, &stream_name, first_info, sender)
+                .await;
+            let first = receiver.await.unwrap().unwrap();
+
+            let (sender, mut receiver) = oneshot::channel();
+            manager
+                .handle_publish(&app_name, &stream_name, publisher_info(second_id), sender)
+                .await;
+
+            match app_name.as_str() {
+                "live" => {
+                    assert!(receiver.await.unwrap().is_err());
+                    assert!(!first_token.is_cancelled());
+                }
+                "kick" => {
+                    let second = receiver.await.unwrap().unwrap();
+                    assert!(first_token.is_cancelled());
+                    /*the replaced publisher leaving keeps the stream*/
+                    manager
+                        .handle_unpublish(&app_name, &stream_name, first_id)
+                        .unwrap();
+                    assert!(manager.channels[&app_name].contains_key(&stream_name));
+                    drop(first);
+                    drop(second);
+                }
+                _ => {
+                    assert!(receiver.try_recv().is_err());
+                    manager
+                        .handle_unpublish(&app_name, &stream_name, first_id)
+                        .unwrap();
+                    assert!(receiver.await.unwrap().is_ok());
+                    assert_eq!(manager.publishers[&app_name][&stream_name].id, second_id);
+                }
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_pending_subscriber() {
+        use {super::ChannelsManager, std::time::Duration, tokio::sync::oneshot};
+
+        let mut manager = ChannelsManager::new();
+        manager.set_subscribe_wait_timeout(Duration::from_millis(50));
+        let app_name = String::from("live");
+        let (waiting, expiring) = (String::from("waiting"), String::from("expiring"));
+
+        /*a subscriber of a published stream does not wait*/
+        let producer = manager.publish(
+            &app_name,
+            &String::from("published"),
+            publisher_info(Uuid::new_v4()),
+        );
+        assert!(producer.is_ok());
+        let info = subscriber_info(SubscribeType::PlayerRtmp);
+        let mut wait_token = info.session_info.wait_token.clone();
+        let (sender, receiver) = oneshot::channel();
+        manager
+            .handle_subscribe(&app_name, &String::from("published"), info, sender)
+            .await;
+        assert!(receiver.await.unwrap().is_ok());
+        assert!(!wait_token.is_cancelled());
+
+        let mut receivers = Vec::new();
+        for stream_name in [&waiting, &expiring] {
+            let (sender, receiver) = oneshot::channel();
+            let info = subscriber_info(SubscribeType::PlayerRtmp);
+            wait_token = info.session_info.wait_token.clone();
+            manager
+                .handle_subscribe(&app_name, stream_name, info, sender)
+                .await;
+            receivers.push(receiver);
+        }
+        let mut expiring_receiver = receivers.pop().unwrap();
+        let mut waiting_receiver = receivers.pop().unwrap();
+        assert!(waiting_receiver.try_recv().is_err());
+        assert!(wait_token.is_cancelled());
+
+        let (sender, receiver) = oneshot::channel();
+        manager
+            .handle_publish(&app_name, &waiting, publisher_info(Uuid::new_v4()), sender)
+            .await;
+        assert!(receiver.await.unwrap().is_ok());
+        assert!(waiting_receiver.await.unwrap().is_ok());
+
+        let deadline = manager.next_pending_deadline();
+        assert!(deadline.is_some());
+        ChannelsManager::wait_until(deadline).await;
+        manager.expire_pending_subscribers();
+        assert!(expiring_receiver.try_recv().unwrap().is_err());
+        assert!(manager.next_pending_deadline().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_session_statistics() {
+        use {
+            super::{define::ChannelData, define::TransmitterEvent, ChannelsManager},
+            bytes::Bytes,
+            tokio::sync::mpsc,
+        };
+
+        let mut manager = ChannelsManager::new();
+        let (app_name, stream_name) = (String::from("live"), String::from("test"));
+
+        let mut publisher = publisher_info(Uuid::new_v4());
+        publisher.session_info.remote_addr = Some("127.0.0.1:50000".parse().unwrap());
+        publisher.session_info.tc_url = Some(String::from("rtmp://localhost/live"));
+        let producer = manager.publish(&app_name, &stream_name, publisher).unwrap();
+
+        let mut session_info = SessionInfo::new(Some("127.0.0.1:50001".parse().unwrap()));
+        session_info.user_agent = Some(String::from("player"));
+        let subscriber_id = Uuid::new_v4();
+        let info = SubscriberInfo {
+            id: subscriber_id,
+            session_info,
+            ..subscriber_info(SubscribeType::PlayerHttpFlv)
+        };
+        let mut consumer = manager
+            .subscribe(&app_name, &stream_name, info)
+            .await
+            .unwrap();
+        /*the subscribe event is handled before the frame*/
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+
+        let data = ChannelData::Audio {
+            timestamp: 0,
+            data: Bytes::from_static(&[0xaf, 0x01, 0x00, 0x00]),
+        };
+        assert!(producer.send(data).is_ok());
+        assert!(consumer.recv().await.is_some());
+
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        manager.channels[&app_name][&stream_name]
+            .send(TransmitterEvent::Api { sender })
+            .unwrap();
+        let statistics = receiver.recv().await.unwrap();
+
+        let publisher = statistics.publisher.unwrap();
+        assert_eq!(publisher.bytes_received, 4);
+        assert_eq!(
+            publisher.session_info.tc_url.as_deref(),
+            Some("rtmp://localhost/live")
+        );
+        assert_eq!(statistics.subscribers.len(), 1);
+        let subscriber = &statistics.subscribers[0];
+        assert_eq!(subscriber.id, subscriber_id.to_string());
+        assert_eq!(subscriber.bytes_sent, 4);
+        assert_eq!(
+            subscriber.session_info.user_agent.as_deref(),
+            Some("player")
+        );
+        assert_eq!(
+            subscriber.session_info.remote_addr,
+            Some("127.0.0.1:50001".parse().unwrap())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_kick() {
+        use {
+            super::{
+                errors::{ChannelError, ChannelErrorValue},
+                ChannelsManager,
+            },
+            tokio::sync::oneshot,
+        };
+
+        let mut manager = ChannelsManager::new();
+        let (app_name, stream_name) = (String::from("live"), String::from("test"));
+
+        let publisher = publisher_info(Uuid::new_v4());
+        let publisher_token = publisher.session_info.kick_token.clone();
+        let (sender, receiver) = oneshot::channel();
+        manager
+            .handle_publish(&app_name, &stream_name, publisher, sender)
+            .await;
+        assert!(receiver.await.unwrap().is_ok());
+
+        let info = subscriber_info(SubscribeType::PlayerRtmp);
+        let (subscriber_id, subscriber_token) = (info.id, info.session_info.kick_token.clone());
+        let _consumer = manager
+            .subscribe(&app_name, &stream_name, info)
+            .await
+            .unwrap();
+
+        manager.kick_subscriber(subscriber_id).unwrap();
+        assert!(subscriber_token.is_cancelled());
+        assert!(manager.kick_subscriber(subscriber_id).is_err());
+
+        /*the stream name is free again once the publisher is kicked*/
+        manager.kick_publisher(&app_name, &stream_name).unwrap();
+        assert!(publisher_token.is_cancelled());
+        assert!(!manager.channels[&app_name].contains_key(&stream_name));
+        assert!(manager.kick_publisher(&app_name, &stream_name).is_err());
+
+        /*a subscriber waiting for the stream is failed*/
+        let info = subscriber_info(SubscribeType::PlayerHttpFlv);
+        let subscriber_id = info.id;
+        let (sender, receiver) = oneshot::channel();
+        manager
+            .handle_subscribe(&app_name, &stream_name, info, sender)
+            .await;
+        manager.kick_subscriber(subscriber_id).unwrap();
+        assert!(matches!(
+            receiver.await.unwrap(),
+            Err(ChannelError {
+                value: ChannelErrorValue::Kicked
+            })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_idle_timeouts() {
+        use {
+            super::{
+                define::{ChannelData, ChannelEvent},
+                ChannelsManager,
+            },
+            bytes::Bytes,
+            std::time::Duration,
+            tokio::sync::oneshot,
+        };
+
+        let mut manager = ChannelsManager::new();
+        manager.set_publish_idle_timeout(Duration::from_millis(100));
+        manager.set_subscriber_idle_timeout(Duration::from_millis(100));
+        let (app_name, stream_name) = (String::from("live"), String::from("test"));
+
+        let publisher = publisher_info(Uuid::new_v4());
+        let (publisher_id, publisher_token) =
+            (publisher.id, publisher.session_info.kick_token.clone());
+        let (sender, receiver) = oneshot::channel();
+        manager
+            .handle_publish(&app_name, &stream_name, publisher, sender)
+            .await;
+        let producer = receiver.await.unwrap().unwrap();
+
+        /*a subscriber which never reads*/
+        let info = subscriber_info(SubscribeType::PlayerRtmp);
+        let subscriber_token = info.session_info.kick_token.clone();
+        let mut consumer = manager
+            .subscribe(&app_name, &stream_name, info)
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let data = ChannelData::Audio {
+            timestamp: 0,
+            data: Bytes::from_static(&[0xaf, 0x01, 0x00, 0x00]),
+        };
+        assert!(producer.send(data).is_ok());
+
+        /*the publisher sends nothing more, the Transmitter asks to unpublish it*/
+        match manager.channel_event_consumer.recv().await {
+            Some(ChannelEvent::UnPublish {
+                app_name: idle_app_name,
+                stream_name: idle_stream_name,
+                publisher_id: idle_publisher_id,
+            }) => {
+                assert_eq!(idle_app_name, app_name);
+                assert_eq!(idle_stream_name, stream_name);
+                assert_eq!(idle_publisher_id, publisher_id);
+            }
+            _ => panic!("expected an unpublish event"),
+        }
+        assert!(publisher_token.is_cancelled());
+
+        manager
+            .handle_unpublish(&app_name, &stream_name, publisher_id)
+            .unwrap();
+        assert!(!manager.channels[&app_name].contains_key(&stream_name));
+        /*the idle subscriber is kicked and its queue closed*/
+        while consumer.recv().await.is_some() {}
+        assert!(subscriber_token.is_cancelled());
+    }
+
+    #[tokio::test]
+    async fn test_start_mode() {
+        use {
+            super::{
+                define::{ChannelData, StartMode},
+                ChannelsManager,
+            },
+            bytes::Bytes,
+            std::time::Duration,
+        };
+
+        let mut manager = ChannelsManager::new();
+        manager.set_rtmp_gop_num(2);
+        let (app_name, stream_name) = (String::from("live"), String::from("test"));
+        let producer = manager
+            .publish(&app_name, &stream_name, publisher_info(Uuid::new_v4()))
+            .unwrap();
+
+        /*two gops of ten frames, from 10s on*/
+        for idx in 0..20u32 {
+            let flags: u8 = if idx % 10 == 0 { 0x17 } else { 0x27 };
+            let data = ChannelData::Video {
+                timestamp: 10_000 + idx * 100,
+                data: Bytes::copy_from_slice(&[flags, 0x01, 0x00, 0x00, 0x00]),
+            };
+            assert!(producer.send(data).is_ok());
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        for (start_mode, frames) in [(StartMode::LatestKeyframe, 10), (StartMode::FullCache, 20)] {
+            let info = SubscriberInfo {
+                start_mode: Some(start_mode),
+                ..subscriber_info(SubscribeType::PlayerRtmp)
+            };
+            let mut consumer = manager
+                .subscribe(&app_name, &stream_name, info)
+                .await
+                .unwrap();
+            tokio::time::sleep(Duration::from_millis(50)).await;
+
+            let mut timestamps = Vec::new();
+            while let Ok(data) = consumer.try_recv() {
+                timestamps.push(data.timestamp());
+            }
+            /*the cached frames start at 0 and keep their spacing*/
+            assert_eq!(timestamps.len(), frames);
+            assert_eq!(timestamps[0], 0);
+            assert_eq!(timestamps[frames - 1], (frames as u32 - 1) * 100);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_cache_burst() {
+        use {
+            super::{
+                define::{BackpressurePolicy, ChannelData, StartMode},
+                ChannelsManager,
+            },
+            bytes::Bytes,
+            std::time::Duration,
+        };
+
+        let mut manager = ChannelsManager::new();
+        manager.set_rtmp_gop_num(2);
+        manager.set_backpressure_policy(BackpressurePolicy {
+            queue_size: 64,
+            non_key_drop_threshold: 16,
+            max_dropped_gops: 3,
+        });
+        let (app_name, stream_name) = (String::from("live"), String::from("test"));
+        let producer = manager
+            .publish(&app_name, &stream_name, publisher_info(Uuid::new_v4()))
+            .unwrap();
+
+        /*a gop of ten frames and one of thirty, more than the threshold*/
+        for idx in 0..40u32 {
+            let flags: u8 = if idx == 0 || idx == 10 { 0x17 } else { 0x27 };
+            let data = ChannelData::Video {
+                timestamp: idx * 40,
+                data: Bytes::copy_from_slice(&[flags, 0x01, 0x00, 0x00, 0x00]),
+            };
+            assert!(producer.send(data).is_ok());
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        /*the oldest gop is left out, none of the newest one's frames is dropped*/
+        let info = SubscriberInfo {
+            start_mode: Some(StartMode::FullCache),
+            ..subscriber_info(SubscribeType::PlayerRtmp)
+        };
+        let mut consumer = manager
+            .subscribe(&app_name, &stream_name, info)
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        let mut frames = 0;
+        while consumer.try_recv().is_ok() {
+            frames += 1;
+        }
+        assert_eq!(frames, 30);
+    }
+
+    #[tokio::test]
+    async fn test_lagging_subscriber() {
+        use {
+            super::{
+                define::{BackpressurePolicy, ChannelData},
+                ChannelsManager,
+            },
+            bytes::Bytes,
+            std::time::Duration,
+        };
+
+        let mut manager = ChannelsManager::new();
+        manager.set_backpressure_policy(BackpressurePolicy {
+            queue_size: 4,
+            non_key_drop_threshold: 2,
+            max_dropped_gops: 1,
+        });
+        let (app_name, stream_name) = (String::from("live"), String::from("test"));
+        let producer = manager
+            .publish(&app_name, &stream_name, publisher_info(Uuid::new_v4()))
+            .unwrap();
+
+        let info = subscriber_info(SubscribeType::PlayerRtmp);
+        let (kick_token, lag_token) = (
+            info.session_info.kick_token.clone(),
+            info.session_info.lag_token.clone(),
+        );
+        let mut consumer = manager
+            .subscribe(&app_name, &stream_name, info)
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        /*key frames only, the queue fills up and gops are dropped*/
+        for idx in 0..10u32 {
+            let data = ChannelData::Video {
+                timestamp: idx * 40,
+                data: Bytes::from_static(&[0x17, 0x01, 0x00, 0x00, 0x00]),
+            };
+            assert!(producer.send(data).is_ok());
+        }
+
+        /*the subscriber is dropped and told it lags, it is not kicked*/
+        while consumer.recv().await.is_some() {}
+        assert!(lag_token.is_cancelled());
+        assert!(!kick_token.is_cancelled());
+    }
+
+    #[tokio::test]
+    async fn test_app_profile() {
+        use super::{
+            define::AppProfile,
+            errors::{ChannelError, ChannelErrorValue},
+            ChannelsManager,
+        };
+
+        let mut manager = ChannelsManager::new();
+        manager.set_app_profile(
+            String::from("internal"),
+            AppProfile {
+                max_publishers: Some(1),
+                max_subscribers: Some(1),
+                httpflv_enabled: Some(false),
+                ..Default::default()
+            },
+        );
+        let app_name = String::from("internal");
+
+        assert!(manager
+            .publish(
+                &app_name,
+                &String::from("a"),
+                publisher_info(Uuid::new_v4())
+            )
+            .is_ok());
+        assert!(matches!(
+            manager.publish(
+                &app_name,
+                &String::from("b"),
+                publisher_info(Uuid::new_v4())
+            ),
+            Err(ChannelError {
+                value: ChannelErrorValue::TooManyPublishers
+            })
+        ));
+        /*the other apps keep the global settings*/
+        for stream_name in ["a", "b"] {
+            assert!(manager
+                .publish(
+                    &String::from("live"),
+                    &String::from(stream_name),
+                    publisher_info(Uuid::new_v4())
+                )
+                .is_ok());
+        }
+
+        let stream_name = String::from("a");
+        assert!(matches!(
+            manager
+                .subscribe(
+                    &app_name,
+                    &stream_name,
+                    subscriber_info(SubscribeType::PlayerHttpFlv)
+                )
+                .await,
+            Err(ChannelError {
+                value: ChannelErrorValue::HttpFlvDisabled
+            })
+        ));
+        assert!(manager
+            .subscribe(
+                &app_name,
+                &stream_name,
+                subscriber_info(SubscribeType::PlayerRtmp)
+            )
+            .await
+            .is_ok());
+        assert!(matches!(
+            manager
+                .subscribe(
+                    &app_name,
+                    &stream_name,
+                    subscriber_info(SubscribeType::PlayerRtmp)
+                )
+                .await,
+            Err(ChannelError {
+                value: ChannelErrorValue::TooManySubscribers
+            })
+        ));
+        /*the hls remuxer is not a player*/
+        assert!(manager
+            .subscribe(
+                &app_name,
+                &stream_name,
+                subscriber_info(SubscribeType::PlayerHls)
+            )
+            .await
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_failover() {
+        use {
+            super::{define::ChannelData, ChannelsManager},
+            bytes::Bytes,
+            std::time::Duration,
+        };
+
+        fn video(timestamp: u32, is_key_frame: bool) -> ChannelData {
+            let flags: u8 = if is_key_frame { 0x17 } else { 0x27 };
+            ChannelData::Video {
+                timestamp,
+                data: Bytes::copy_from_slice(&[flags, 0x01, 0x00, 0x00, 0x00]),
+            }
+        }
+
+        let mut manager = ChannelsManager::new();
+        let app_name = String::from("live");
+        let (main, backup) = (String::from("main"), String::from("main_backup"));
+        manager.set_failover_stream(
+            app_name.clone(),
+            main.clone(),
+            app_name.clone(),
+            backup.clone(),
+        );
+
+        let main_producer = manager
+            .publish(&app_name, &main, publisher_info(Uuid::new_v4()))
+            .unwrap();
+        let backup_producer = manager
+            .publish(&app_name, &backup, publisher_info(Uuid::new_v4()))
+            .unwrap();
+        let backup_data = [
+            ChannelData::Audio {
+                timestamp: 0,
+                data: Bytes::from_static(&[0xaf, 0x00, 0x12, 0x10]),
+            },
+            video(50_000, true),
+            video(50_040, false),
+        ];
+        for data in backup_data {
+            assert!(backup_producer.send(data).is_ok());
+        }
+
+        let id = Uuid::new_v4();
+        let info = SubscriberInfo {
+            id,
+            ..subscriber_info(SubscribeType::PlayerRtmp)
+        };
+        let mut consumer = manager.subscribe(&app_name, &main, info).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        for timestamp in [0, 40, 80] {
+            assert!(main_producer.send(video(timestamp, timestamp == 0)).is_ok());
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        /*the player is moved to the backup and gets its sequence header and gop*/
+        manager.unpublish(&app_name, &main).unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(manager.subscribers[&id].1, backup);
+
+        /*and back to the main stream once it is published again*/
+        let main_producer = manager
+            .publish(&app_name, &main, publisher_info(Uuid::new_v4()))
+            .unwrap();
+        assert_eq!(manager.subscribers[&id].1, main);
+        assert!(manager.failed_over_subscribers.is_empty());
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        for timestamp in [0, 40] {
+            assert!(main_producer.send(video(timestamp, timestamp == 0)).is_ok());
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        let mut timestamps = Vec::new();
+        while let Ok(data) = consumer.try_recv() {
+            timestamps.push(data.timestamp());
+        }
+        assert_eq!(timestamps, vec![0, 40, 80, 81, 81, 121, 122, 162]);
+    }
+
+    #[tokio::test]
+    async fn test_timestamp_discontinuity() {
+        use {
+            super::{define::ChannelData, ChannelsManager},
+            bytes::Bytes,
+            std::time::Duration,
+        };
+
+        let mut manager = ChannelsManager::new();
+        let (app_name, stream_name) = (String::from("live"), String::from("test"));
+        let producer = manager
+            .publish(&app_name, &stream_name, publisher_info(Uuid::new_v4()))
+            .unwrap();
+        let info = subscriber_info(SubscribeType::PlayerHls);
+        let mut consumer = manager
+            .subscribe(&app_name, &stream_name, info.clone())
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        /*the encoder restarts 10s into the stream, within a gop*/
+        for timestamp in [10_000, 10_040, 10_080, 0, 40, 80] {
+            let flags: u8 = if timestamp == 10_000 { 0x17 } else { 0x27 };
+            let data = ChannelData::Video {
+                timestamp,
+                data: Bytes::copy_from_slice(&[flags, 0x01, 0x00, 0x00, 0x00]),
+            };
+            assert!(producer.send(data).is_ok());
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        let expected = vec![
+            (10_000, false),
+            (10_040, false),
+            (10_080, false),
+            (10_120, true),
+            (10_120, false),
+            (10_160, false),
+            (10_200, false),
+        ];
+        let mut received = Vec::new();
+        while let Ok(data) = consumer.try_recv() {
+            let is_discontinuity = matches!(data, ChannelData::Discontinuity { .. });
+            received.push((data.timestamp(), is_discontinuity));
+        }
+        assert_eq!(received, expected);
+
+        /*a player starting from the cache gets the discontinuity as well, its
+        timestamps start at zero*/
+        let info = SubscriberInfo {
+            id: Uuid::new_v4(),
+            ..info
+        };
+        let mut consumer = manager
+            .subscribe(&app_name, &stream_name, info)
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        let mut received = Vec::new();
+        while let Ok(data) = consumer.try_recv() {
+            let is_discontinuity = matches!(data, ChannelData::Discontinuity { .. });
+            received.push((data.timestamp() + 10_000, is_discontinuity));
+        }
+        assert_eq!(received, expected);
+    }
 }
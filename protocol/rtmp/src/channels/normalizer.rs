@@ -0,0 +1,199 @@
+/* A forward jump longer than this between two frames of a stream, or a
+regression longer than MAX_TIMESTAMP_REGRESSION, is a discontinuity: an encoder
+restart, a source switch or broken timestamps. Audio and video are interleaved
+with some drift, smaller regressions are not. */
+const MAX_TIMESTAMP_JUMP: i64 = 10_000;
+const MAX_TIMESTAMP_REGRESSION: i64 = 1_000;
+
+/* The interval assumed after a discontinuity before any frame interval is known. */
+const DEFAULT_FRAME_INTERVAL: i64 = 40;
+
+/* The timestamp of a frame after normalisation, discontinuity is set on the
+first frame after a jump or a regression of the publisher's timestamps. */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NormalizedTimestamp {
+    pub timestamp: u32,
+    pub discontinuity: bool,
+}
+
+/* Makes the timestamps of a published stream increase steadily: they are
+unwrapped past 2^32 ms, a discontinuity shifts all the following ones so they
+follow the last frame by a frame interval, which keeps the audio and the video
+at their relative offsets, and a frame older than the previous one of its kind
+gets that one's timestamp. The timestamps of a stream without discontinuities
+are passed unchanged. */
+pub struct TimestampNormalizer {
+    //added to the unwrapped timestamps of the publisher
+    offset: i64,
+    //the publisher's timestamp of the last frame, unwrapped
+    last_input: Option<i64>,
+    last_audio: Option<i64>,
+    last_video: Option<i64>,
+    //between the last two video frames, or audio frames of an audio only stream
+    frame_interval: i64,
+}
+
+impl Default for TimestampNormalizer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TimestampNormalizer {
+    pub fn new() -> Self {
+        Self {
+            offset: 0,
+            last_input: None,
+            last_audio: None,
+            last_video: None,
+            frame_interval: DEFAULT_FRAME_INTERVAL,
+        }
+    }
+
+    fn unwrap(&self, timestamp: u32) -> i64 {
+        match self.last_input {
+            /*the signed difference crosses the 2^32 ms boundary*/
+            Some(last) => last + timestamp.wrapping_sub(last as u32) as i32 as i64,
+            None => timestamp as i64,
+        }
+    }
+
+    fn is_discontinuity(&self, input: i64) -> bool {
+        self.last_input.is_some_and(|last| {
+            !(-MAX_TIMESTAMP_REGRESSION..=MAX_TIMESTAMP_JUMP).contains(&(input - last))
+        })
+    }
+
+    /* Metadata is shifted like the frames but leaves the state alone, one
+    which would be a discontinuity gets the timestamp of the newest frame. */
+    pub fn normalize_metadata(&self, timestamp: u32) -> u32 {
+        let input = self.unwrap(timestamp);
+        let output = if self.is_discontinuity(input) {
+            self.last_audio.max(self.last_video).unwrap_or(0)
+        } else {
+            input + self.offset
+        };
+        output as u32
+    }
+
+    pub fn normalize(&mut self, timestamp: u32, is_video: bool) -> NormalizedTimestamp {
+        let input = self.unwrap(timestamp);
+
+        let mut discontinuity = false;
+        if self.is_discontinuity(input) {
+            let newest = self.last_audio.max(self.last_video).unwrap_or(0);
+            self.offset = newest + self.frame_interval - input;
+            discontinuity = true;
+        }
+        self.last_input = Some(input);
+
+        let has_video = self.last_video.is_some();
+        let last = if is_video {
+            &mut self.last_video
+        } else {
+            &mut self.last_audio
+        };
+        let output = (input + self.offset).max(last.unwrap_or(0));
+        if let Some(previous) = *last {
+            let interval = output - previous;
+            if interval > 0 && (is_video || !has_video) {
+                self.frame_interval = interval.min(MAX_TIMESTAMP_REGRESSION);
+            }
+        }
+        *last = Some(output);
+
+        NormalizedTimestamp {
+            /*the output wraps past 2^32 ms like the rtmp timestamps*/
+            timestamp: output as u32,
+            discontinuity,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TimestampNormalizer;
+
+    fn normalize(normalizer: &mut TimestampNormalizer, frames: &[(u32, bool)]) -> Vec<(u32, bool)> {
+        frames
+            .iter()
+            .map(|(timestamp, is_video)| {
+                let normalized = normalizer.normalize(*timestamp, *is_video);
+                (normalized.timestamp, normalized.discontinuity)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_normalize() {
+        /*interleaved audio and video are passed unchanged*/
+        let mut normalizer = TimestampNormalizer::new();
+        let frames = [
+            (1000, true),
+            (990, false),
+            (1040, true),
+            (1013, false),
+            (1080, true),
+        ];
+        assert_eq!(
+            normalize(&mut normalizer, &frames),
+            frames
+                .iter()
+                .map(|(ts, _)| (*ts, false))
+                .collect::<Vec<_>>()
+        );
+
+        /*an encoder restart continues one frame interval after the last frame,
+        the audio keeps its offset to the video*/
+        let frames = [(0, true), (10, false), (40, true), (50, false)];
+        assert_eq!(
+            normalize(&mut normalizer, &frames),
+            vec![(1120, true), (1130, false), (1160, false), (1170, false)]
+        );
+
+        /*a jump forward*/
+        let frames = [(500_000, true), (500_040, true)];
+        assert_eq!(
+            normalize(&mut normalizer, &frames),
+            vec![(1210, true), (1250, false)]
+        );
+
+        /*a frame older than the previous one of its kind*/
+        let frames = [(500_070, false), (500_060, false), (500_080, true)];
+        assert_eq!(
+            normalize(&mut normalizer, &frames),
+            vec![(1280, false), (1280, false), (1290, false)]
+        );
+    }
+
+    #[test]
+    fn test_normalize_metadata() {
+        let mut normalizer = TimestampNormalizer::new();
+        assert_eq!(normalizer.normalize_metadata(0), 0);
+
+        normalize(&mut normalizer, &[(1000, true), (1040, true), (0, true)]);
+        /*shifted like the frames after the restart*/
+        assert_eq!(normalizer.normalize_metadata(40), 1120);
+        /*the metadata of another restart*/
+        assert_eq!(normalizer.normalize_metadata(900_000), 1080);
+        assert_eq!(normalizer.normalize(40, true).timestamp, 1120);
+    }
+
+    #[test]
+    fn test_rollover() {
+        let mut normalizer = TimestampNormalizer::new();
+        let frames = [
+            (u32::MAX - 39, true),
+            (u32::MAX - 20, false),
+            (0, true),
+            (40, true),
+        ];
+        assert_eq!(
+            normalize(&mut normalizer, &frames),
+            frames
+                .iter()
+                .map(|(ts, _)| (*ts, false))
+                .collect::<Vec<_>>()
+        );
+    }
+}
@@ -0,0 +1,357 @@
+use {
+    super::{
+        define::{BackpressurePolicy, ChannelData, SubscriberDataProducer},
+        errors::{ChannelError, ChannelErrorValue},
+    },
+    crate::statistics::DroppedFrames,
+    std::time::Duration,
+    tokio::{sync::mpsc::error::TrySendError, time::Instant},
+    xflv::define::frame_type,
+};
+
+/* The timestamps wrap past 2^32 ms, one behind another by less than half of
+that is older. min_timestamp follows the newest timestamp sent at this
+distance, so it stays behind it while the timestamps wrap. */
+const MIN_TIMESTAMP_WINDOW: u32 = 1 << 30;
+
+fn is_older(timestamp: u32, than: u32) -> bool {
+    (timestamp.wrapping_sub(than) as i32) < 0
+}
+
+/* Feeds a subscriber's bounded queue following the BackpressurePolicy. */
+#[derive(Debug)]
+pub struct SubscriberQueue {
+    producer: SubscriberDataProducer,
+    policy: BackpressurePolicy,
+    /* A frame did not fit, everything up to the next key frame is dropped. */
+    wait_key_frame: bool,
+    dropped_gops: usize,
+    /* Without video every audio frame is a point to resume from. */
+    has_video: bool,
+    /* The last time the subscriber was seen taking frames from the queue. */
+    last_read: Instant,
+    last_queued: usize,
+    /* Added to the timestamps of the frames sent to the subscriber, the ones
+    which would end up below min_timestamp are sent at it. */
+    timestamp_offset: Option<i64>,
+    min_timestamp: u32,
+    /* The newest timestamp sent, another stream continues from it. */
+    last_timestamp: Option<u32>,
+    /* The next frame sent is the one the other stream continues with. */
+    continue_at_next_frame: bool,
+    pub dropped_frames: DroppedFrames,
+    pub bytes_sent: u64,
+}
+
+impl SubscriberQueue {
+    pub fn new(producer: SubscriberDataProducer, policy: BackpressurePolicy) -> Self {
+        Self {
+            producer,
+            policy,
+            wait_key_frame: false,
+            dropped_gops: 0,
+            has_video: false,
+            last_read: Instant::now(),
+            last_queued: 0,
+            timestamp_offset: None,
+            min_timestamp: 0,
+            last_timestamp: None,
+            continue_at_next_frame: false,
+            dropped_frames: DroppedFrames::default(),
+            bytes_sent: 0,
+        }
+    }
+
+    /* The frames sent from now on start at timestamp 0, the ones older than
+    the base as well. */
+    pub fn set_timestamp_base(&mut self, base: u32) {
+        self.timestamp_offset = Some(-(base as i64));
+        self.min_timestamp = 0;
+        self.continue_at_next_frame = false;
+    }
+
+    /* The subscriber is switched to another stream, its frame at base, or its
+    next frame without a base, is sent right after the last frame sent so the
+    timestamps keep increasing. */
+    pub fn continue_timestamps(&mut self, base: Option<u32>) {
+        let next = self
+            .last_timestamp
+            .map_or(0, |timestamp| timestamp.wrapping_add(1));
+        self.timestamp_offset = base.map(|base| next as i64 - base as i64);
+        self.min_timestamp = next;
+        self.continue_at_next_frame = base.is_none();
+    }
+
+    /* Fails when the subscriber is gone or has to be disconnected. */
+    pub fn send(&mut self, data: ChannelData) -> Result<(), ChannelError> {
+        self.push(data, false)
+    }
+
+    /* The cached frames a new subscriber starts with are queued at once, they
+    are not taken for a subscriber falling behind. */
+    pub fn send_cached(&mut self, data: ChannelData) -> Result<(), ChannelError> {
+        self.push(data, true)
+    }
+
+    /* The cached frames a new subscriber may start with, more of them would
+    leave its first live frames above the threshold of the non-key frames. */
+    pub fn cache_capacity(&self) -> usize {
+        self.policy.non_key_drop_threshold
+    }
+
+    fn push(&mut self, mut data: ChannelData, is_cached: bool) -> Result<(), ChannelError> {
+        if self.continue_at_next_frame {
+            self.continue_at_next_frame = false;
+            self.timestamp_offset = Some(self.min_timestamp as i64 - data.timestamp() as i64);
+        }
+        if let Some(offset) = self.timestamp_offset {
+            let mut timestamp = data.timestamp().wrapping_add(offset as u32);
+            if is_older(timestamp, self.min_timestamp) {
+                timestamp = self.min_timestamp;
+            }
+            data.set_timestamp(timestamp);
+        }
+        let timestamp = data.timestamp();
+
+        let (is_video, is_resume_point, size) = match &data {
+            ChannelData::Video { data, .. } => {
+                self.has_video = true;
+                (true, is_key_frame(&data[..]), data.len())
+            }
+            ChannelData::Audio { data, .. } => (false, !self.has_video, data.len()),
+            ChannelData::MetaData { data, .. } => (false, false, data.len()),
+            ChannelData::Discontinuity { .. } => (false, false, 0),
+        };
+        /*a discontinuity is passed like the metadata*/
+        let is_metadata = matches!(
+            data,
+            ChannelData::MetaData { .. } | ChannelData::Discontinuity { .. }
+        );
+        self.update_last_read();
+
+        if self.wait_key_frame && !is_resume_point && !is_metadata {
+            self.on_dropped(&data);
+            return Ok(());
+        }
+
+        if is_video
+            && !is_resume_point
+            && !is_cached
+            && self.queued() >= self.policy.non_key_drop_threshold
+        {
+            self.on_dropped(&data);
+            return Ok(());
+        }
+
+        match self.producer.try_send(data) {
+            Ok(()) => {
+                let last = match self.last_timestamp {
+                    Some(last) if is_older(timestamp, last) => last,
+                    _ => timestamp,
+                };
+                self.last_timestamp = Some(last);
+                if last.wrapping_sub(self.min_timestamp) > MIN_TIMESTAMP_WINDOW {
+                    self.min_timestamp = last.wrapping_sub(MIN_TIMESTAMP_WINDOW);
+                }
+                self.last_queued = self.queued();
+                self.bytes_sent += size as u64;
+                if is_resume_point {
+                    self.wait_key_frame = false;
+                    self.dropped_gops = 0;
+                }
+                Ok(())
+            }
+            Err(TrySendError::Full(data)) => {
+                self.on_dropped(&data);
+                if is_metadata || !self.has_video {
+                    return Ok(());
+                }
+
+                self.wait_key_frame = true;
+                self.dropped_gops += 1;
+                if self.dropped_gops > self.policy.max_dropped_gops {
+                    return Err(ChannelError {
+                        value: ChannelErrorValue::SubscriberLagging,
+                    });
+                }
+                Ok(())
+            }
+            Err(TrySendError::Closed(_)) => Err(ChannelError {
+                value: ChannelErrorValue::SendError,
+            }),
+        }
+    }
+
+    /* Frames are left in the queue and none was taken for the timeout. */
+    pub fn is_idle(&mut self, timeout: Duration) -> bool {
+        self.update_last_read();
+        self.queued() > 0 && self.last_read.elapsed() >= timeout
+    }
+
+    fn update_last_read(&mut self) {
+        let queued = self.queued();
+        if queued == 0 || queued < self.last_queued {
+            self.last_read = Instant::now();
+        }
+        self.last_queued = queued;
+    }
+
+    fn queued(&self) -> usize {
+        self.producer.max_capacity() - self.producer.capacity()
+    }
+
+    fn on_dropped(&mut self, data: &ChannelData) {
+        match data {
+            ChannelData::Video { .. } => self.dropped_frames.video += 1,
+            ChannelData::Audio { .. } => self.dropped_frames.audio += 1,
+            ChannelData::MetaData { .. } | ChannelData::Discontinuity { .. } => {}
+        }
+    }
+}
+
+/* The frame type is in the high bits of the first byte of the legacy and
+the enhanced video tag headers. */
+fn is_key_frame(data: &[u8]) -> bool {
+    data.first()
+        .is_some_and(|flags| (flags >> 4) & 0x07 == frame_type::KEY_FRAME)
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::SubscriberQueue,
+        crate::channels::define::{BackpressurePolicy, ChannelData},
+        bytes::Bytes,
+        std::time::Duration,
+        tokio::sync::mpsc,
+    };
+
+    fn video(timestamp: u32, is_key_frame: bool) -> ChannelData {
+        let flags: u8 = if is_key_frame { 0x17 } else { 0x27 };
+        ChannelData::Video {
+            timestamp,
+            data: Bytes::copy_from_slice(&[flags, 0x01]),
+        }
+    }
+
+    fn audio(timestamp: u32) -> ChannelData {
+        ChannelData::Audio {
+            timestamp,
+            data: Bytes::copy_from_slice(&[0xaf, 0x01]),
+        }
+    }
+
+    #[test]
+    fn test_backpressure() {
+        let policy = BackpressurePolicy {
+            queue_size: 4,
+            non_key_drop_threshold: 2,
+            max_dropped_gops: 1,
+        };
+        let (producer, mut consumer) = mpsc::channel(policy.queue_size);
+        let mut queue = SubscriberQueue::new(producer, policy);
+
+        queue.send(video(0, true)).unwrap();
+        queue.send(audio(0)).unwrap();
+        /*two frames are queued, the non-key video frames are dropped*/
+        queue.send(video(40, false)).unwrap();
+        queue.send(audio(20)).unwrap();
+        queue.send(audio(40)).unwrap();
+        assert_eq!(queue.dropped_frames.video, 1);
+
+        /*the queue is full, the rest of the GOP is dropped*/
+        queue.send(audio(60)).unwrap();
+        assert!(queue.wait_key_frame);
+        while consumer.try_recv().is_ok() {}
+        queue.send(audio(80)).unwrap();
+        queue.send(video(80, false)).unwrap();
+        assert_eq!(queue.dropped_frames.audio, 2);
+        assert_eq!(queue.dropped_frames.video, 2);
+
+        /*the next key frame resumes the subscriber*/
+        queue.send(video(120, true)).unwrap();
+        assert!(!queue.wait_key_frame);
+        assert!(matches!(
+            consumer.try_recv(),
+            Ok(ChannelData::Video { timestamp: 120, .. })
+        ));
+
+        /*a subscriber which does not drain its queue is disconnected*/
+        for timestamp in 0..4 {
+            queue.send(audio(140 + timestamp)).unwrap();
+        }
+        queue.send(audio(160)).unwrap();
+        assert!(queue.send(video(200, true)).is_err());
+    }
+
+    #[test]
+    fn test_idle() {
+        let (producer, mut consumer) = mpsc::channel(4);
+        let mut queue = SubscriberQueue::new(producer, BackpressurePolicy::default());
+        let timeout = Duration::from_millis(20);
+
+        /*an empty queue is never idle*/
+        std::thread::sleep(timeout);
+        assert!(!queue.is_idle(timeout));
+
+        queue.send(audio(0)).unwrap();
+        queue.send(audio(20)).unwrap();
+        std::thread::sleep(timeout);
+        assert!(queue.is_idle(timeout));
+
+        assert!(consumer.try_recv().is_ok());
+        assert!(!queue.is_idle(timeout));
+    }
+
+    #[test]
+    fn test_continue_timestamps() {
+        let (producer, mut consumer) = mpsc::channel(16);
+        let mut queue = SubscriberQueue::new(producer, BackpressurePolicy::default());
+
+        queue.set_timestamp_base(1000);
+        queue.send(audio(1000)).unwrap();
+        queue.send(audio(1020)).unwrap();
+
+        /*another stream, the sequence header cached at its start included*/
+        queue.continue_timestamps(Some(50_000));
+        queue.send(audio(0)).unwrap();
+        queue.send(audio(50_000)).unwrap();
+        queue.send(audio(50_020)).unwrap();
+
+        /*a stream without media yet continues with its first frame*/
+        queue.continue_timestamps(None);
+        queue.send(audio(7000)).unwrap();
+        queue.send(audio(7020)).unwrap();
+
+        let mut timestamps = Vec::new();
+        while let Ok(data) = consumer.try_recv() {
+            timestamps.push(data.timestamp());
+        }
+        assert_eq!(timestamps, vec![0, 20, 21, 21, 41, 42, 62]);
+    }
+
+    #[test]
+    fn test_timestamp_rollover() {
+        let (producer, mut consumer) = mpsc::channel(16);
+        let mut queue = SubscriberQueue::new(producer, BackpressurePolicy::default());
+
+        /*rebased across the wrap*/
+        queue.set_timestamp_base(u32::MAX - 19);
+        for timestamp in [u32::MAX - 19, u32::MAX, 19] {
+            queue.send(audio(timestamp)).unwrap();
+        }
+        /*a stream running for more than 49 days*/
+        queue.set_timestamp_base(0);
+        for idx in 0..10u32 {
+            queue.send(audio(idx.wrapping_shl(29))).unwrap();
+        }
+
+        let mut timestamps = Vec::new();
+        while let Ok(data) = consumer.try_recv() {
+            timestamps.push(data.timestamp());
+        }
+        let mut expected = vec![0, 19, 39];
+        expected.extend((0..10u32).map(|idx| idx.wrapping_shl(29)));
+        assert_eq!(timestamps, expected);
+    }
+}
@@ -1,7 +1,8 @@
 use {
     super::{
-        define::CHUNK_SIZE, errors::PackError, ChunkBasicHeader, ChunkHeader, ChunkInfo,
-        ChunkMessageHeader,
+        define::{chunk_type, CHUNK_SIZE},
+        errors::PackError,
+        ChunkBasicHeader, ChunkHeader, ChunkInfo, ChunkMessageHeader,
     },
     byteorder::{BigEndian, LittleEndian},
     bytesio::{bytes_writer::AsyncBytesWriter, bytesio::BytesIO},
@@ -23,6 +24,8 @@ pub struct ChunkPacketizer {
     max_chunk_size: usize,
     //bytes: Cursor<Vec<u8>>,
     writer: AsyncBytesWriter,
+    /* Total bytes written, wrapping like the acknowledgement sequence number. */
+    bytes_written: u32,
 }
 
 impl ChunkPacketizer {
@@ -32,34 +35,71 @@ impl ChunkPacketizer {
             //chunk_info: ChunkInfo::new(),
             writer: AsyncBytesWriter::new(io),
             max_chunk_size: CHUNK_SIZE as usize,
+            bytes_written: 0,
         }
     }
-    fn zip_chunk_header(&mut self, chunk_info: &mut ChunkInfo) -> Result<PackResult, PackError> {
-        chunk_info.basic_header.format = 0;
-
-        let pre_header = self
-            .csid_2_chunk_header
-            .get_mut(&chunk_info.basic_header.chunk_stream_id);
-
-        if let Some(val) = pre_header {
-            let cur_msg_header = &mut chunk_info.message_header;
-            let pre_msg_header = &val.message_header;
-
-            if cur_msg_header.msg_streamd_id == pre_msg_header.msg_streamd_id {
-                chunk_info.basic_header.format = 1;
-                cur_msg_header.timestamp -= pre_msg_header.timestamp;
-
-                if cur_msg_header.msg_type_id == pre_msg_header.msg_type_id
-                    && cur_msg_header.msg_length == pre_msg_header.msg_length
-                {
-                    chunk_info.basic_header.format = 2;
-                    if chunk_info.message_header.timestamp == pre_msg_header.timestamp {
-                        chunk_info.basic_header.format = 3;
+
+    pub fn bytes_written(&self) -> u32 {
+        self.bytes_written
+    }
+    /*
+     Picks the smallest header for the next message on a chunk stream, as
+     the peer reconstructs it from the last message it got on that stream:
+     fmt 1 leaves out the message stream id, fmt 2 also the length and
+     type, fmt 3 also the timestamp delta. Returns the format and the value
+     of the timestamp field, the delta for fmt 1/2/3.
+    */
+    fn zip_chunk_header(&mut self, chunk_info: &ChunkInfo) -> (u8, u32) {
+        let csid = chunk_info.basic_header.chunk_stream_id;
+        let cur_msg_header = &chunk_info.message_header;
+
+        let zipped = match self.csid_2_chunk_header.get(&csid) {
+            Some(pre_header) => {
+                let pre_msg_header = &pre_header.message_header;
+                let timestamp_delta = cur_msg_header
+                    .timestamp
+                    .checked_sub(pre_msg_header.timestamp)
+                    .filter(|delta| *delta < 0xFFFFFF);
+
+                match timestamp_delta {
+                    /*a delta needing an extended timestamp or going backwards
+                    is sent in a full header*/
+                    Some(delta)
+                        if cur_msg_header.msg_streamd_id == pre_msg_header.msg_streamd_id =>
+                    {
+                        if cur_msg_header.msg_type_id != pre_msg_header.msg_type_id
+                            || cur_msg_header.msg_length != pre_msg_header.msg_length
+                        {
+                            Some((chunk_type::TYPE_1, delta))
+                        } else if delta != pre_msg_header.timestamp_delta {
+                            Some((chunk_type::TYPE_2, delta))
+                        } else {
+                            Some((chunk_type::TYPE_3, delta))
+                        }
                     }
+                    _ => None,
                 }
             }
-        }
-        Ok(PackResult::Success)
+            None => None,
+        };
+
+        /*after a fmt 0 header the timestamp itself is the delta a
+        following fmt 3 header refers to*/
+        let (format, timestamp_field) =
+            zipped.unwrap_or((chunk_type::TYPE_0, cur_msg_header.timestamp));
+
+        let mut message_header = cur_msg_header.clone();
+        message_header.timestamp_delta = timestamp_field;
+        message_header.is_extended_timestamp = timestamp_field >= 0xFFFFFF;
+        self.csid_2_chunk_header.insert(
+            csid,
+            ChunkHeader {
+                basic_header: ChunkBasicHeader::new(format, csid),
+                message_header,
+            },
+        );
+
+        (format, timestamp_field)
     }
 
     fn write_basic_header(&mut self, fmt: u8, csid: u32) -> Result<(), PackError> {
@@ -78,16 +118,17 @@ impl ChunkPacketizer {
 
     fn write_message_header(
         &mut self,
-        basic_header: &ChunkBasicHeader,
+        format: u8,
+        timestamp_field: u32,
         message_header: &ChunkMessageHeader,
     ) -> Result<(), PackError> {
-        let timestamp = if message_header.timestamp >= 0xFFFFFF {
+        let timestamp = if timestamp_field >= 0xFFFFFF {
             0xFFFFFF
         } else {
-            message_header.timestamp
+            timestamp_field
         };
 
-        match basic_header.format {
+        match format {
             0 => {
                 self.writer.write_u24::<BigEndian>(timestamp)?;
                 self.writer
@@ -100,6 +141,7 @@ impl ChunkPacketizer {
                 self.writer.write_u24::<BigEndian>(timestamp)?;
                 self.writer
                     .write_u24::<BigEndian>(message_header.msg_length)?;
+                self.writer.write_u8(message_header.msg_type_id)?;
             }
             2 => {
                 self.writer.write_u24::<BigEndian>(timestamp)?;
@@ -116,44 +158,168 @@ impl ChunkPacketizer {
         Ok(())
     }
 
+    /* The header format is chosen here, the one in chunk_info is ignored. */
     pub async fn write_chunk(&mut self, chunk_info: &mut ChunkInfo) -> Result<(), PackError> {
-        self.zip_chunk_header(chunk_info)?;
+        let payload = std::mem::take(&mut chunk_info.payload);
+        self.write_message(chunk_info, &payload[..]).await
+    }
 
-        let mut whole_payload_size = chunk_info.payload.len();
+    /* Writes a message whose payload is kept outside of chunk_info, so a
+    frame shared by many players is not copied before it is chunked. */
+    pub async fn write_message(
+        &mut self,
+        chunk_info: &mut ChunkInfo,
+        payload: &[u8],
+    ) -> Result<(), PackError> {
+        let (format, timestamp_field) = self.zip_chunk_header(chunk_info);
+        chunk_info.basic_header.format = format;
+        chunk_info.message_header.is_extended_timestamp = timestamp_field >= 0xFFFFFF;
 
-        self.write_basic_header(
-            chunk_info.basic_header.format,
-            chunk_info.basic_header.chunk_stream_id,
-        )?;
-        self.write_message_header(&chunk_info.basic_header, &chunk_info.message_header)?;
+        self.write_basic_header(format, chunk_info.basic_header.chunk_stream_id)?;
+        self.write_message_header(format, timestamp_field, &chunk_info.message_header)?;
 
         if chunk_info.message_header.is_extended_timestamp {
-            self.write_extened_timestamp(chunk_info.message_header.timestamp)?;
+            self.write_extened_timestamp(timestamp_field)?;
         }
 
-        let mut cur_payload_size: usize;
-
-        while whole_payload_size > 0 {
-            cur_payload_size = if whole_payload_size > self.max_chunk_size {
-                self.max_chunk_size
-            } else {
-                whole_payload_size
-            };
-
-            let payload_bytes = chunk_info.payload.split_to(cur_payload_size);
-            self.writer.write(&payload_bytes[0..])?;
-
-            whole_payload_size -= cur_payload_size;
+        let mut chunks = payload.chunks(self.max_chunk_size).peekable();
+        while let Some(chunk) = chunks.next() {
+            self.writer.write(chunk)?;
 
-            if whole_payload_size > 0 {
+            if chunks.peek().is_some() {
                 self.write_basic_header(3, chunk_info.basic_header.chunk_stream_id)?;
                 if chunk_info.message_header.is_extended_timestamp {
-                    self.write_extened_timestamp(chunk_info.message_header.timestamp)?;
+                    self.write_extened_timestamp(timestamp_field)?;
                 }
             }
         }
+        self.bytes_written = self
+            .bytes_written
+            .wrapping_add(self.writer.bytes_writer.len() as u32);
         self.writer.flush().await?;
 
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::ChunkPacketizer,
+        crate::chunk::{
+            define::{chunk_type, csid_type, CHUNK_SIZE},
+            ChunkInfo,
+        },
+        bytes::BytesMut,
+        bytesio::bytesio::BytesIO,
+        std::sync::Arc,
+        tokio::{io::AsyncReadExt, sync::Mutex},
+    };
+
+    fn read_u24(data: &[u8], pos: &mut usize) -> u32 {
+        let value = u32::from_be_bytes([0, data[*pos], data[*pos + 1], data[*pos + 2]]);
+        *pos += 3;
+        value
+    }
+
+    fn read_u32(data: &[u8], pos: &mut usize) -> u32 {
+        let value =
+            u32::from_be_bytes([data[*pos], data[*pos + 1], data[*pos + 2], data[*pos + 3]]);
+        *pos += 4;
+        value
+    }
+
+    /* The timestamps of the messages of one chunk stream, decoded as the
+    peer does: fmt 1, 2 and 3 add a delta to the previous timestamp, fmt 3
+    repeating the previous delta. */
+    fn decode_timestamps(data: &[u8], lengths: &[u32]) -> Vec<u32> {
+        let (mut pos, mut timestamp, mut delta, mut extended) = (0, 0u32, 0u32, false);
+        let mut timestamps = Vec::new();
+
+        for length in lengths {
+            let format = data[pos] >> 6;
+            pos += 1;
+            if format < 3 {
+                let field = read_u24(data, &mut pos);
+                pos += match format {
+                    0 => 8,
+                    1 => 4,
+                    _ => 0,
+                };
+                extended = field == 0xFFFFFF;
+                delta = if extended {
+                    read_u32(data, &mut pos)
+                } else {
+                    field
+                };
+            } else if extended {
+                pos += 4;
+            }
+            timestamp = if format == 0 {
+                delta
+            } else {
+                timestamp.wrapping_add(delta)
+            };
+            timestamps.push(timestamp);
+
+            /*the following chunks of the message have fmt 3 headers*/
+            let mut remaining = *length as usize;
+            loop {
+                let size = remaining.min(CHUNK_SIZE as usize);
+                pos += size;
+                remaining -= size;
+                if remaining == 0 {
+                    break;
+                }
+                assert_eq!(data[pos] >> 6, 3);
+                pos += if extended { 5 } else { 1 };
+            }
+        }
+        assert_eq!(pos, data.len());
+
+        timestamps
+    }
+
+    #[tokio::test]
+    async fn test_header_compression() {
+        let (local, mut remote) = tokio::io::duplex(1 << 16);
+        let mut packetizer = ChunkPacketizer::new(Arc::new(Mutex::new(BytesIO::new(local))));
+
+        //(timestamp, payload length, expected format)
+        let messages = [
+            (0, 10, chunk_type::TYPE_0),
+            (23, 10, chunk_type::TYPE_2),
+            (46, 10, chunk_type::TYPE_3),
+            (69, 10, chunk_type::TYPE_3),
+            (92, 5000, chunk_type::TYPE_1),
+            (50, 10, chunk_type::TYPE_0),
+            (0x2000000, 10, chunk_type::TYPE_0),
+            (0x2000017, 10, chunk_type::TYPE_2),
+        ];
+
+        for (timestamp, length, format) in messages {
+            let mut chunk_info = ChunkInfo::new(
+                csid_type::AUDIO,
+                chunk_type::TYPE_0,
+                timestamp,
+                length,
+                8,
+                1,
+                BytesMut::from(&vec![0x2f; length as usize][..]),
+            );
+            packetizer.write_chunk(&mut chunk_info).await.unwrap();
+            assert_eq!(chunk_info.basic_header.format, format);
+        }
+        drop(packetizer);
+
+        let mut data = Vec::new();
+        remote.read_to_end(&mut data).await.unwrap();
+
+        let lengths: Vec<u32> = messages.iter().map(|(_, length, _)| *length).collect();
+        let timestamps: Vec<u32> = messages
+            .iter()
+            .map(|(timestamp, _, _)| *timestamp)
+            .collect();
+        assert_eq!(decode_timestamps(&data, &lengths), timestamps);
+    }
+}
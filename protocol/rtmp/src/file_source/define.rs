@@ -0,0 +1,12 @@
+use serde_derive::{Deserialize, Serialize};
+
+/* An FLV file published as app_name/stream_name. A looped file is played
+again from its start, the timestamps continuing those of the previous pass. */
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct FileSource {
+    pub path: String,
+    pub app_name: String,
+    pub stream_name: String,
+    #[serde(default, rename = "loop")]
+    pub looped: bool,
+}
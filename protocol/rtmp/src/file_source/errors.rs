@@ -0,0 +1,71 @@
+use {
+    crate::{amf0::Amf0WriteError, channels::errors::ChannelError},
+    failure::Fail,
+    std::{fmt, io::Error},
+    xflv::errors::{DemuxerErrorValue, FlvDemuxerError},
+};
+
+#[derive(Debug)]
+pub struct FileSourceError {
+    pub value: FileSourceErrorValue,
+}
+
+impl fmt::Display for FileSourceError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&self.value, f)
+    }
+}
+
+#[derive(Debug, Fail)]
+pub enum FileSourceErrorValue {
+    #[fail(display = "io error: {}\n", _0)]
+    IOError(Error),
+    #[fail(display = "not an flv file\n")]
+    NotFlv,
+    #[fail(display = "no audio or video tag\n")]
+    NoMediaTags,
+    #[fail(display = "channel error: {}\n", _0)]
+    ChannelError(ChannelError),
+    #[fail(display = "channel event send error\n")]
+    ChannelEventSendErr,
+    #[fail(display = "send error\n")]
+    SendError,
+    #[fail(display = "amf0 write error: {}\n", _0)]
+    Amf0WriteError(Amf0WriteError),
+    #[fail(display = "flv demuxer error: {}\n", _0)]
+    FlvDemuxerError(FlvDemuxerError),
+}
+
+impl From<Error> for FileSourceError {
+    fn from(error: Error) -> Self {
+        FileSourceError {
+            value: FileSourceErrorValue::IOError(error),
+        }
+    }
+}
+
+impl From<ChannelError> for FileSourceError {
+    fn from(error: ChannelError) -> Self {
+        FileSourceError {
+            value: FileSourceErrorValue::ChannelError(error),
+        }
+    }
+}
+
+impl From<Amf0WriteError> for FileSourceError {
+    fn from(error: Amf0WriteError) -> Self {
+        FileSourceError {
+            value: FileSourceErrorValue::Amf0WriteError(error),
+        }
+    }
+}
+
+impl From<FlvDemuxerError> for FileSourceError {
+    fn from(error: FlvDemuxerError) -> Self {
+        let value = match error.value {
+            DemuxerErrorValue::NotFlv => FileSourceErrorValue::NotFlv,
+            _ => FileSourceErrorValue::FlvDemuxerError(error),
+        };
+        FileSourceError { value }
+    }
+}
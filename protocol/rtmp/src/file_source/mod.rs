@@ -0,0 +1,3 @@
+pub mod define;
+pub mod errors;
+pub mod publisher;
@@ -0,0 +1,443 @@
+use {
+    super::{
+        define::FileSource,
+        errors::{FileSourceError, FileSourceErrorValue},
+    },
+    crate::{
+        amf0::amf0_writer::Amf0Writer,
+        channels::define::{ChannelData, ChannelDataProducer, ChannelEvent, ChannelEventProducer},
+        session::common::{PublisherInfo, SessionInfo},
+    },
+    bytes::{Bytes, BytesMut},
+    bytesio::{bytes_reader::BytesReader, bytes_writer::BytesWriter},
+    std::io::ErrorKind,
+    tokio::{
+        fs::File,
+        io::{AsyncReadExt, BufReader},
+        sync::oneshot,
+        time::{sleep_until, Duration, Instant},
+    },
+    tokio_util::sync::CancellationToken,
+    uuid::Uuid,
+    xflv::{
+        define::tag_type,
+        demuxer::{
+            parse_flv_header, FlvTagHeader, FLV_HEADER_SIZE, FLV_TAG_HEADER_SIZE, PREVIOUS_TAG_SIZE,
+        },
+    },
+};
+
+/* The AMF0 string "onMetaData" a script data tag of the metadata starts with. */
+const ON_METADATA: &[u8] = b"\x02\x00\x0aonMetaData";
+
+/* Publishes an FLV file to the channels like an encoder would, each tag is
+sent when the time since the start reaches its timestamp. The tags are read
+one by one, a loop reads the file again from its start. */
+pub struct FilePublisher {
+    source: FileSource,
+    event_producer: ChannelEventProducer,
+    publisher_id: Uuid,
+    session_info: SessionInfo,
+}
+
+impl FilePublisher {
+    pub fn new(source: FileSource, event_producer: ChannelEventProducer) -> Self {
+        Self {
+            source,
+            event_producer,
+            publisher_id: Uuid::new_v4(),
+            session_info: SessionInfo::new(None),
+        }
+    }
+
+    /* Cancelled to stop the publisher, and by the publisher once it stops. */
+    pub fn kick_token(&self) -> CancellationToken {
+        self.session_info.kick_token.clone()
+    }
+
+    pub async fn run(&mut self) -> Result<(), FileSourceError> {
+        let kick_token = self.kick_token();
+        let rv = self.publish_file().await;
+        kick_token.cancel();
+        rv
+    }
+
+    async fn publish_file(&mut self) -> Result<(), FileSourceError> {
+        /*a file which is not an flv file is not published*/
+        FlvTagReader::open(&self.source.path).await?;
+        let data_producer = self.publish().await?;
+        tracing::info!(
+            "publish file: {}, app_name: {}, stream_name: {}",
+            self.source.path,
+            self.source.app_name,
+            self.source.stream_name
+        );
+
+        let kick_token = self.kick_token();
+        let rv = tokio::select! {
+            rv = self.send_tags(&data_producer) => rv,
+            _ = kick_token.cancelled() => Ok(()),
+        };
+        self.unpublish();
+
+        rv
+    }
+
+    async fn publish(&mut self) -> Result<ChannelDataProducer, FileSourceError> {
+        let (responder, receiver) = oneshot::channel();
+        let publish_event = ChannelEvent::Publish {
+            app_name: self.source.app_name.clone(),
+            stream_name: self.source.stream_name.clone(),
+            info: PublisherInfo {
+                id: self.publisher_id,
+                session_info: self.session_info.clone(),
+            },
+            responder,
+        };
+        if self.event_producer.send(publish_event).is_err() {
+            return Err(FileSourceError {
+                value: FileSourceErrorValue::ChannelEventSendErr,
+            });
+        }
+
+        match receiver.await {
+            Ok(rv) => Ok(rv?),
+            Err(_) => Err(FileSourceError {
+                value: FileSourceErrorValue::ChannelEventSendErr,
+            }),
+        }
+    }
+
+    fn unpublish(&mut self) {
+        let unpublish_event = ChannelEvent::UnPublish {
+            app_name: self.source.app_name.clone(),
+            stream_name: self.source.stream_name.clone(),
+            publisher_id: self.publisher_id,
+        };
+        if self.event_producer.send(unpublish_event).is_err() {
+            tracing::error!(
+                "unpublish file error, app_name: {}, stream_name: {}",
+                self.source.app_name,
+                self.source.stream_name
+            );
+        }
+    }
+
+    /* The timestamps start at 0, those of a looped file keep increasing and
+    wrap past 2^32 ms like the rtmp timestamps. */
+    async fn send_tags(&self, data_producer: &ChannelDataProducer) -> Result<(), FileSourceError> {
+        let start = Instant::now();
+        let mut offset: u64 = 0;
+
+        loop {
+            let mut reader = FlvTagReader::open(&self.source.path).await?;
+            let mut duration = PassDuration::default();
+
+            while let Some(mut data) = reader.read_tag().await? {
+                let first_timestamp = duration.add(&data);
+                let timestamp = offset + data.timestamp().saturating_sub(first_timestamp) as u64;
+                sleep_until(start + Duration::from_millis(timestamp)).await;
+
+                data.set_timestamp(timestamp as u32);
+                data_producer.send(data).map_err(|_| FileSourceError {
+                    value: FileSourceErrorValue::SendError,
+                })?;
+            }
+
+            /*an empty file would be looped without end*/
+            if !duration.has_media() {
+                return Err(FileSourceError {
+                    value: FileSourceErrorValue::NoMediaTags,
+                });
+            }
+            if !self.source.looped {
+                return Ok(());
+            }
+            offset += duration.get();
+        }
+    }
+}
+
+/* Reads the tags of an FLV file one by one: the audio, the video and the
+script data tags of the metadata. The headers are parsed by xflv, this only
+reads their bytes from the file. */
+struct FlvTagReader {
+    reader: BufReader<File>,
+}
+
+impl FlvTagReader {
+    async fn open(path: &String) -> Result<Self, FileSourceError> {
+        let mut reader = BufReader::new(File::open(path).await?);
+
+        let mut header = BytesMut::zeroed(FLV_HEADER_SIZE);
+        if reader.read_exact(&mut header).await.is_err() {
+            return Err(FileSourceError {
+                value: FileSourceErrorValue::NotFlv,
+            });
+        }
+        let header_size = parse_flv_header(&mut BytesReader::new(header))?;
+
+        /*the header may be longer, the size of the tag before the first one follows*/
+        let skipped =
+            (header_size as u64).saturating_sub(FLV_HEADER_SIZE as u64) + PREVIOUS_TAG_SIZE as u64;
+        tokio::io::copy(&mut (&mut reader).take(skipped), &mut tokio::io::sink()).await?;
+
+        Ok(Self { reader })
+    }
+
+    /* None at the end of the file, or at a truncated last tag. */
+    async fn read_tag(&mut self) -> Result<Option<ChannelData>, FileSourceError> {
+        loop {
+            let mut header = BytesMut::zeroed(FLV_TAG_HEADER_SIZE);
+            if !self.read_fully(&mut header).await? {
+                return Ok(None);
+            }
+            let header = FlvTagHeader::parse(&mut BytesReader::new(header))?;
+            let data_size = header.data_size as usize;
+
+            /*the body is followed by the size of the tag*/
+            let mut body = BytesMut::zeroed(data_size + PREVIOUS_TAG_SIZE);
+            if !self.read_fully(&mut body).await? {
+                return Ok(None);
+            }
+            body.truncate(data_size);
+            let (timestamp, data) = (header.timestamp, body.freeze());
+
+            match header.tag_type {
+                tag_type::AUDIO => return Ok(Some(ChannelData::Audio { timestamp, data })),
+                tag_type::VIDEO => return Ok(Some(ChannelData::Video { timestamp, data })),
+                tag_type::SCRIPT_DATA_AMF if data.starts_with(ON_METADATA) => {
+                    return Ok(Some(ChannelData::MetaData {
+                        timestamp,
+                        data: set_data_frame(&data)?,
+                    }))
+                }
+                _ => {}
+            }
+        }
+    }
+
+    async fn read_fully(&mut self, buf: &mut [u8]) -> Result<bool, FileSourceError> {
+        match self.reader.read_exact(buf).await {
+            Ok(_) => Ok(true),
+            Err(err) if err.kind() == ErrorKind::UnexpectedEof => Ok(false),
+            Err(err) => Err(err.into()),
+        }
+    }
+}
+
+/* The metadata is published like an encoder does, with @setDataFrame. */
+fn set_data_frame(data: &[u8]) -> Result<Bytes, FileSourceError> {
+    let mut amf_writer = Amf0Writer::new(BytesWriter::new());
+    amf_writer.write_string(&String::from("@setDataFrame"))?;
+
+    let mut metadata = BytesMut::from(&amf_writer.extract_current_bytes()[..]);
+    metadata.extend_from_slice(data);
+    Ok(metadata.freeze())
+}
+
+/* The duration of a pass over the file: the span of the timestamps plus the
+interval of the last frame, so the first frame of the next pass does not
+share the last one's timestamp. */
+#[derive(Default)]
+struct PassDuration {
+    first: Option<u32>,
+    last: u32,
+    has_media: bool,
+    //the timestamps of the last two video frames, and of the last two frames
+    video: [Option<u32>; 2],
+    frames: [Option<u32>; 2],
+}
+
+impl PassDuration {
+    /* Returns the first timestamp of the pass. */
+    fn add(&mut self, data: &ChannelData) -> u32 {
+        let timestamp = data.timestamp();
+        let first = *self.first.get_or_insert(timestamp);
+        self.last = self.last.max(timestamp);
+
+        match data {
+            ChannelData::Video { .. } => {
+                self.video = [self.video[1], Some(timestamp)];
+                self.frames = [self.frames[1], Some(timestamp)];
+                self.has_media = true;
+            }
+            ChannelData::Audio { .. } => {
+                self.frames = [self.frames[1], Some(timestamp)];
+                self.has_media = true;
+            }
+            _ => {}
+        }
+
+        first
+    }
+
+    fn has_media(&self) -> bool {
+        self.has_media
+    }
+
+    fn get(&self) -> u64 {
+        let frame_interval = match (self.video, self.frames) {
+            ([Some(previous), Some(last)], _) | (_, [Some(previous), Some(last)]) => {
+                last.saturating_sub(previous)
+            }
+            _ => 0,
+        };
+        let first = self.first.unwrap_or(0);
+
+        (self.last.saturating_sub(first)) as u64 + frame_interval.max(1) as u64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{FilePublisher, FlvTagReader, PassDuration, ON_METADATA},
+        crate::{
+            channels::{
+                define::{ChannelData, ChannelEvent},
+                tests::subscriber_info,
+                ChannelsManager,
+            },
+            file_source::define::FileSource,
+            session::define::SubscribeType,
+        },
+        bytes::{BufMut, Bytes, BytesMut},
+        tokio::{
+            sync::oneshot,
+            time::{Duration, Instant},
+        },
+        uuid::Uuid,
+    };
+
+    fn put_tag(file: &mut BytesMut, tag_type: u8, timestamp: u32, body: &[u8]) {
+        file.put_u8(tag_type);
+        file.put_uint(body.len() as u64, 3);
+        file.put_uint((timestamp & 0xffffff) as u64, 3);
+        file.put_u8((timestamp >> 24) as u8);
+        file.put_uint(0, 3);
+        file.put_slice(body);
+        file.put_u32(11 + body.len() as u32);
+    }
+
+    /* An FLV file of video tags, with a key frame every ten tags. */
+    fn flv_file(frames: u32, interval: u32) -> BytesMut {
+        let mut file = BytesMut::new();
+        file.put_slice(b"FLV\x01\x01\x00\x00\x00\x09\x00\x00\x00\x00");
+        for idx in 0..frames {
+            let flags: u8 = if idx % 10 == 0 { 0x17 } else { 0x27 };
+            put_tag(
+                &mut file,
+                9,
+                idx * interval,
+                &[flags, 0x01, 0x00, 0x00, 0x00],
+            );
+        }
+        file
+    }
+
+    #[test]
+    fn test_pass_duration() {
+        let mut duration = PassDuration::default();
+        for idx in 0..25 {
+            duration.add(&ChannelData::Video {
+                timestamp: 1000 + idx * 40,
+                data: Bytes::new(),
+            });
+        }
+        assert!(duration.has_media());
+        assert_eq!(duration.get(), 1000);
+    }
+
+    #[tokio::test]
+    async fn test_flv_tag_reader() {
+        let path = std::env::temp_dir().join(format!("flv_tag_reader_{}.flv", Uuid::new_v4()));
+        let path_name = path.to_string_lossy().to_string();
+
+        /*the metadata and three frames, the last one truncated*/
+        let mut file = BytesMut::new();
+        file.put_slice(b"FLV\x01\x01\x00\x00\x00\x09\x00\x00\x00\x00");
+        put_tag(
+            &mut file,
+            18,
+            0,
+            &[ON_METADATA, &[0x08, 0, 0, 0, 0, 0, 0, 9]].concat(),
+        );
+        put_tag(&mut file, 9, 0, &[0x17, 0x01, 0x00, 0x00, 0x00]);
+        put_tag(&mut file, 8, 20, &[0xaf, 0x01]);
+        put_tag(&mut file, 9, 40, &[0x27, 0x01, 0x00, 0x00, 0x00]);
+        file.truncate(file.len() - 6);
+        std::fs::write(&path, &file).unwrap();
+
+        let mut reader = FlvTagReader::open(&path_name).await.unwrap();
+        let mut tags = Vec::new();
+        while let Some(tag) = reader.read_tag().await.unwrap() {
+            tags.push(tag);
+        }
+        assert_eq!(tags.len(), 3);
+        assert!(matches!(&tags[0], ChannelData::MetaData { data, .. }
+            if data.starts_with(b"\x02\x00\x0d@setDataFrame")));
+        assert!(matches!(tags[1], ChannelData::Video { timestamp: 0, .. }));
+        assert!(matches!(tags[2], ChannelData::Audio { timestamp: 20, .. }));
+
+        /*not an flv file*/
+        std::fs::write(&path, b"not an flv file").unwrap();
+        assert!(FlvTagReader::open(&path_name).await.is_err());
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_file_publisher() {
+        let path = std::env::temp_dir().join(format!("file_publisher_{}.flv", Uuid::new_v4()));
+        std::fs::write(&path, flv_file(10, 20)).unwrap();
+
+        let mut manager = ChannelsManager::new();
+        manager.set_rtmp_gop_num(0);
+        let event_producer = manager.get_channel_event_producer();
+        let channel = tokio::spawn(async move { manager.run().await });
+
+        /*the player waits for the file to be published*/
+        let (app_name, stream_name) = (String::from("live"), String::from("file"));
+        let (responder, receiver) = oneshot::channel();
+        event_producer
+            .send(ChannelEvent::Subscribe {
+                app_name: app_name.clone(),
+                stream_name: stream_name.clone(),
+                info: subscriber_info(SubscribeType::PlayerRtmp),
+                responder,
+            })
+            .unwrap();
+
+        let mut publisher = FilePublisher::new(
+            FileSource {
+                path: path.to_string_lossy().to_string(),
+                app_name,
+                stream_name,
+                looped: true,
+            },
+            event_producer,
+        );
+        let kick_token = publisher.kick_token();
+        let publisher = tokio::spawn(async move { publisher.run().await });
+
+        let mut consumer = receiver.await.unwrap().unwrap();
+        let start = Instant::now();
+        let mut timestamps = Vec::new();
+        while timestamps.len() < 15 {
+            timestamps.push(consumer.recv().await.unwrap().timestamp());
+        }
+        /*paced by the timestamps, which go on increasing in the second pass*/
+        assert!(start.elapsed() >= Duration::from_millis(260));
+        assert_eq!(
+            timestamps,
+            (0..15).map(|idx| idx * 20).collect::<Vec<u32>>()
+        );
+
+        kick_token.cancel();
+        assert!(publisher.await.unwrap().is_ok());
+        /*the stream is unpublished*/
+        while consumer.recv().await.is_some() {}
+        channel.abort();
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -1,11 +1,15 @@
 use {
     super::{
-        define, define::ClientHandshakeState, errors::HandshakeError,
+        define, define::ClientHandshakeState, digest::DigestProcessor, errors::HandshakeError,
         handshake_trait::THandshakeClient, utils,
     },
     byteorder::BigEndian,
     bytes::BytesMut,
-    bytesio::{bytes_reader::BytesReader, bytes_writer::AsyncBytesWriter, bytesio::BytesIO},
+    bytesio::{
+        bytes_reader::BytesReader,
+        bytes_writer::{AsyncBytesWriter, BytesWriter},
+        bytesio::BytesIO,
+    },
     std::sync::Arc,
     tokio::sync::Mutex,
 };
@@ -14,6 +18,8 @@ use {
 // use super::utils;
 // use super::{define::ClientHandshakeState, handshake_trait::THandshakeClient};
 
+const S0S1S2_SIZE: usize = 1 + 2 * define::RTMP_HANDSHAKE_SIZE;
+
 pub struct SimpleHandshakeClient {
     reader: BytesReader,
     writer: AsyncBytesWriter,
@@ -51,6 +57,9 @@ impl SimpleHandshakeClient {
                 }
 
                 ClientHandshakeState::ReadS0S1S2 => {
+                    if self.reader.len() < S0S1S2_SIZE {
+                        break;
+                    }
                     self.read_s0()?;
                     self.read_s1()?;
                     self.read_s2()?;
@@ -73,6 +82,112 @@ impl SimpleHandshakeClient {
     }
 }
 
+/*
+ Sends a C1 carrying the client digest. A server answering with a digest
+ in S1 gets a C2 signed with it, a plain S1 is echoed back in C2 as the
+ simple handshake does.
+*/
+pub struct ComplexHandshakeClient {
+    reader: BytesReader,
+    writer: AsyncBytesWriter,
+    s1_bytes: BytesMut,
+    /* None if the server answered with a plain S1. */
+    s1_digest: Option<BytesMut>,
+    pub state: ClientHandshakeState,
+}
+
+impl ComplexHandshakeClient {
+    pub fn new(io: Arc<Mutex<BytesIO>>) -> Self {
+        Self {
+            reader: BytesReader::new(BytesMut::new()),
+            writer: AsyncBytesWriter::new(io),
+            s1_bytes: BytesMut::new(),
+            s1_digest: None,
+            state: ClientHandshakeState::WriteC0C1,
+        }
+    }
+
+    pub fn extend_data(&mut self, data: &[u8]) {
+        self.reader.extend_from_slice(data);
+    }
+
+    pub async fn handshake(&mut self) -> Result<(), HandshakeError> {
+        loop {
+            match self.state {
+                ClientHandshakeState::WriteC0C1 => {
+                    tracing::info!("[ C->S ] [complex handshake] write C0C1");
+                    self.write_c0()?;
+                    self.write_c1()?;
+                    self.writer.flush().await?;
+                    self.state = ClientHandshakeState::ReadS0S1S2;
+                    break;
+                }
+
+                ClientHandshakeState::ReadS0S1S2 => {
+                    if self.reader.len() < S0S1S2_SIZE {
+                        break;
+                    }
+                    tracing::info!("[ C<-S ] [complex handshake] read S0S1S2");
+                    self.read_s0()?;
+                    self.read_s1()?;
+                    self.read_s2()?;
+                    self.state = ClientHandshakeState::WriteC2;
+                }
+
+                ClientHandshakeState::WriteC2 => {
+                    tracing::info!("[ C->S ] [complex handshake] write C2");
+                    self.write_c2()?;
+                    self.writer.flush().await?;
+                    self.state = ClientHandshakeState::Finish;
+                }
+
+                ClientHandshakeState::Finish => {
+                    break;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+pub struct HandshakeClient {
+    simple_handshaker: SimpleHandshakeClient,
+    complex_handshaker: ComplexHandshakeClient,
+    is_complex: bool,
+}
+
+impl HandshakeClient {
+    pub fn new(io: Arc<Mutex<BytesIO>>, is_complex: bool) -> Self {
+        Self {
+            simple_handshaker: SimpleHandshakeClient::new(io.clone()),
+            complex_handshaker: ComplexHandshakeClient::new(io),
+            is_complex,
+        }
+    }
+
+    pub fn extend_data(&mut self, data: &[u8]) {
+        match self.is_complex {
+            true => self.complex_handshaker.extend_data(data),
+            false => self.simple_handshaker.extend_data(data),
+        }
+    }
+
+    pub fn state(&self) -> &ClientHandshakeState {
+        match self.is_complex {
+            true => &self.complex_handshaker.state,
+            false => &self.simple_handshaker.state,
+        }
+    }
+
+    pub async fn handshake(&mut self) -> Result<(), HandshakeError> {
+        match self.is_complex {
+            true => self.complex_handshaker.handshake().await,
+            false => self.simple_handshaker.handshake().await,
+        }
+    }
+}
+
 impl THandshakeClient for SimpleHandshakeClient {
     fn write_c0(&mut self) -> Result<(), HandshakeError> {
         self.writer.write_u8(define::RTMP_VERSION as u8)?;
@@ -104,3 +219,165 @@ impl THandshakeClient for SimpleHandshakeClient {
         Ok(())
     }
 }
+
+impl THandshakeClient for ComplexHandshakeClient {
+    fn write_c0(&mut self) -> Result<(), HandshakeError> {
+        self.writer.write_u8(define::RTMP_VERSION as u8)?;
+        Ok(())
+    }
+
+    fn write_c1(&mut self) -> Result<(), HandshakeError> {
+        let mut writer = BytesWriter::new();
+
+        writer.write_u32::<BigEndian>(utils::current_time())?;
+        writer.write(&define::RTMP_CLIENT_VERSION)?;
+        writer.write_random_bytes(define::RTMP_HANDSHAKE_SIZE as u32 - 8)?;
+
+        /*generate the digest*/
+        let mut key = BytesMut::new();
+        key.extend_from_slice(define::RTMP_CLIENT_KEY_FIRST_HALF.as_bytes());
+
+        let mut digest_processor = DigestProcessor::new(writer.extract_current_bytes(), key);
+        let content = digest_processor.generate_and_fill_digest()?;
+
+        self.writer.write(&content[..])?;
+        Ok(())
+    }
+
+    fn write_c2(&mut self) -> Result<(), HandshakeError> {
+        let s1_digest = match &self.s1_digest {
+            Some(digest) => digest.clone(),
+            None => {
+                self.writer.write(&self.s1_bytes[0..])?;
+                return Ok(());
+            }
+        };
+
+        let mut writer = BytesWriter::new();
+        writer.write_random_bytes(
+            (define::RTMP_HANDSHAKE_SIZE - define::RTMP_DIGEST_LENGTH) as u32,
+        )?;
+        let data = writer.extract_current_bytes();
+
+        /*the key for c2 is the s1 digest signed with the client key*/
+        let mut key = BytesMut::new();
+        key.extend_from_slice(&define::RTMP_CLIENT_KEY);
+
+        let mut digest_processor = DigestProcessor::new(BytesMut::new(), key);
+        let tmp_key = digest_processor.make_digest(Vec::from(&s1_digest[..]))?;
+
+        let mut digest_processor_2 = DigestProcessor::new(BytesMut::new(), tmp_key);
+        let digest = digest_processor_2.make_digest(Vec::from(&data[..]))?;
+
+        let content = [data, digest].concat();
+        self.writer.write(&content[..])?;
+
+        Ok(())
+    }
+
+    fn read_s0(&mut self) -> Result<(), HandshakeError> {
+        self.reader.read_u8()?;
+        Ok(())
+    }
+
+    fn read_s1(&mut self) -> Result<(), HandshakeError> {
+        self.s1_bytes = self.reader.read_bytes(define::RTMP_HANDSHAKE_SIZE)?;
+
+        let mut key = BytesMut::new();
+        key.extend_from_slice(define::RTMP_SERVER_KEY_FIRST_HALF.as_bytes());
+
+        let mut digest_processor = DigestProcessor::new(self.s1_bytes.clone(), key);
+        self.s1_digest = match digest_processor.read_digest() {
+            Ok((digest, _)) => Some(digest),
+            Err(err) => {
+                tracing::warn!(
+                    "no digest in s1, fall back to the simple handshake.. err:{}",
+                    err
+                );
+                None
+            }
+        };
+
+        Ok(())
+    }
+
+    fn read_s2(&mut self) -> Result<(), HandshakeError> {
+        let _ = self.reader.read_bytes(define::RTMP_HANDSHAKE_SIZE)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{ComplexHandshakeClient, S0S1S2_SIZE},
+        crate::handshake::{
+            define::{ClientHandshakeState, ServerHandshakeState, RTMP_HANDSHAKE_SIZE},
+            handshake_server::{ComplexHandshakeServer, SimpleHandshakeServer},
+        },
+        bytes::BytesMut,
+        bytesio::bytesio::BytesIO,
+        std::sync::Arc,
+        tokio::sync::Mutex,
+    };
+
+    async fn read_bytes(io: &Arc<Mutex<BytesIO>>, size: usize) -> BytesMut {
+        let mut data = BytesMut::new();
+        while data.len() < size {
+            data.extend_from_slice(&io.lock().await.read().await.unwrap()[..]);
+        }
+        data
+    }
+
+    #[tokio::test]
+    async fn test_complex_handshake() {
+        let (client_stream, server_stream) = tokio::io::duplex(1 << 16);
+        let client_io = Arc::new(Mutex::new(BytesIO::new(client_stream)));
+        let server_io = Arc::new(Mutex::new(BytesIO::new(server_stream)));
+
+        let mut client = ComplexHandshakeClient::new(client_io.clone());
+        let mut server = ComplexHandshakeServer::new(server_io.clone());
+
+        client.handshake().await.unwrap();
+        /*the server fails on a C1 without a valid digest*/
+        server.extend_data(&read_bytes(&server_io, 1 + RTMP_HANDSHAKE_SIZE).await[..]);
+        server.handshake().await.unwrap();
+
+        client.extend_data(&read_bytes(&client_io, S0S1S2_SIZE).await[..]);
+        client.handshake().await.unwrap();
+        assert!(client.state == ClientHandshakeState::Finish);
+        assert!(client.s1_digest.is_some());
+
+        server.extend_data(&read_bytes(&server_io, RTMP_HANDSHAKE_SIZE).await[..]);
+        server.handshake().await.unwrap();
+        assert!(matches!(server.state, ServerHandshakeState::Finish));
+    }
+
+    #[tokio::test]
+    async fn test_fall_back_to_simple_handshake() {
+        let (client_stream, server_stream) = tokio::io::duplex(1 << 16);
+        let client_io = Arc::new(Mutex::new(BytesIO::new(client_stream)));
+        let server_io = Arc::new(Mutex::new(BytesIO::new(server_stream)));
+
+        let mut client = ComplexHandshakeClient::new(client_io.clone());
+        let mut server = SimpleHandshakeServer::new(server_io.clone());
+
+        client.handshake().await.unwrap();
+        server.extend_data(&read_bytes(&server_io, 1 + RTMP_HANDSHAKE_SIZE).await[..]);
+        server.handshake().await.unwrap();
+
+        /*S1 arrives in two parts*/
+        let s0s1s2 = read_bytes(&client_io, S0S1S2_SIZE).await;
+        client.extend_data(&s0s1s2[..100]);
+        client.handshake().await.unwrap();
+        assert!(client.state == ClientHandshakeState::ReadS0S1S2);
+        client.extend_data(&s0s1s2[100..]);
+        client.handshake().await.unwrap();
+        assert!(client.state == ClientHandshakeState::Finish);
+        assert!(client.s1_digest.is_none());
+
+        /*the plain S1 is echoed back*/
+        let c2 = read_bytes(&server_io, RTMP_HANDSHAKE_SIZE).await;
+        assert_eq!(&c2[..], &s0s1s2[1..1 + RTMP_HANDSHAKE_SIZE]);
+    }
+}
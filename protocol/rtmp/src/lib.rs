@@ -10,10 +10,12 @@ extern crate sha2;
 extern crate tokio;
 
 pub mod amf0;
+pub mod amf3;
 pub mod cache;
 pub mod channels;
 pub mod chunk;
 pub mod config;
+pub mod file_source;
 pub mod handshake;
 pub mod messages;
 pub mod netconnection;
@@ -23,5 +25,6 @@ pub mod relay;
 pub mod rtmp;
 pub mod session;
 pub mod statistics;
+pub mod tls;
 pub mod user_control_messages;
 pub mod utils;
@@ -3,7 +3,7 @@ use {crate::amf0::define::Amf0ValueType, bytes::BytesMut};
 #[allow(dead_code)]
 pub struct SetPeerBandwidthProperties {
     pub window_size: u32,
-    limit_type: u8,
+    pub limit_type: u8,
 }
 
 impl SetPeerBandwidthProperties {
@@ -14,6 +14,14 @@ impl SetPeerBandwidthProperties {
         }
     }
 }
+/* One FLV tag of an aggregate message, the timestamp is already rebased
+on the aggregate message's timestamp. */
+pub struct AggregateSubMessage {
+    pub msg_type_id: u8,
+    pub timestamp: u32,
+    pub data: BytesMut,
+}
+
 pub enum RtmpMessageData {
     Amf0Command {
         command_name: Amf0ValueType,
@@ -25,6 +33,13 @@ pub enum RtmpMessageData {
         raw_data: BytesMut,
         // values: Vec<Amf0ValueType>,
     },
+    SharedObject {
+        name: String,
+        version: u32,
+        persistent: bool,
+        // the events are kept undecoded, shared objects are not supported
+        events: BytesMut,
+    },
     SetChunkSize {
         chunk_size: u32,
     },
@@ -56,6 +71,15 @@ pub enum RtmpMessageData {
     StreamIsRecorded {
         stream_id: u32,
     },
+    PingRequest {
+        timestamp: u32,
+    },
+    PingResponse {
+        timestamp: u32,
+    },
+    AggregateData {
+        messages: Vec<AggregateSubMessage>,
+    },
 
     Unknow,
 }
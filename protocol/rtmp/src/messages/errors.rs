@@ -1,6 +1,6 @@
 use {
     crate::{
-        amf0::errors::Amf0ReadError,
+        amf0::errors::{Amf0ReadError, Amf0WriteError},
         protocol_control_messages::errors::ProtocolControlMessageReaderError,
         user_control_messages::errors::EventMessagesError,
     },
@@ -17,6 +17,8 @@ pub enum MessageErrorValue {
     UnknowReadState,
     #[fail(display = "amf0 read error: {}\n", _0)]
     Amf0ReadError(Amf0ReadError),
+    #[fail(display = "amf0 write error: {}\n", _0)]
+    Amf0WriteError(Amf0WriteError),
     #[fail(display = "unknown message type")]
     UnknowMessageType,
     #[fail(display = "protocol control message read error: {}\n", _0)]
@@ -52,6 +54,14 @@ impl From<Amf0ReadError> for MessageError {
     }
 }
 
+impl From<Amf0WriteError> for MessageError {
+    fn from(error: Amf0WriteError) -> Self {
+        MessageError {
+            value: MessageErrorValue::Amf0WriteError(error),
+        }
+    }
+}
+
 impl From<ProtocolControlMessageReaderError> for MessageError {
     fn from(error: ProtocolControlMessageReaderError) -> Self {
         MessageError {
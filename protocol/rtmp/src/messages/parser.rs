@@ -1,16 +1,17 @@
 use {
     super::{
-        define::{msg_type_id, RtmpMessageData},
+        define::{msg_type_id, AggregateSubMessage, RtmpMessageData},
         errors::{MessageError, MessageErrorValue},
     },
     crate::{
-        amf0::{amf0_markers, amf0_reader::Amf0Reader},
+        amf0::{amf0_markers, amf0_reader::Amf0Reader, amf0_writer::Amf0Writer},
         chunk::ChunkInfo,
         protocol_control_messages::reader::ProtocolControlMessageReader,
         user_control_messages::reader::EventMessagesReader,
         // utils,
     },
-    bytesio::bytes_reader::BytesReader,
+    byteorder::BigEndian,
+    bytesio::{bytes_reader::BytesReader, bytes_writer::BytesWriter},
 };
 
 pub struct MessageParser {
@@ -109,27 +110,96 @@ impl MessageParser {
                     ProtocolControlMessageReader::new(reader).read_set_peer_bandwidth()?;
                 return Ok(RtmpMessageData::SetPeerBandwidth { properties });
             }
-            msg_type_id::DATA_AMF0 | msg_type_id::DATA_AMF3 => {
+            msg_type_id::DATA_AMF0 => {
                 //let values = Amf0Reader::new(reader).read_all()?;
                 return Ok(RtmpMessageData::AmfData {
                     raw_data: reader.extract_remaining_bytes(),
                 });
             }
+            msg_type_id::DATA_AMF3 => {
+                /*skip the format byte, the values are AMF0 encoded and may
+                switch to AMF3, re-encode them so that the receivers of the
+                metadata only need to understand AMF0.*/
+                reader.read_u8()?;
+                let values = Amf0Reader::new(reader).read_all()?;
 
-            msg_type_id::SHARED_OBJ_AMF3 | msg_type_id::SHARED_OBJ_AMF0 => {}
+                let mut amf_writer = Amf0Writer::new(BytesWriter::new());
+                amf_writer.write_anys(&values)?;
 
-            msg_type_id::AGGREGATE => {}
+                return Ok(RtmpMessageData::AmfData {
+                    raw_data: amf_writer.extract_current_bytes(),
+                });
+            }
+
+            msg_type_id::SHARED_OBJ_AMF3 | msg_type_id::SHARED_OBJ_AMF0 => {
+                if self.chunk_info.message_header.msg_type_id == msg_type_id::SHARED_OBJ_AMF3 {
+                    reader.read_u8()?;
+                }
+
+                let name_len = reader.read_u16::<BigEndian>()?;
+                let name = String::from_utf8(reader.read_bytes(name_len as usize)?.to_vec())
+                    .unwrap_or_default();
+                let version = reader.read_u32::<BigEndian>()?;
+                let persistent = reader.read_u32::<BigEndian>()? == 2;
+                //reserved
+                reader.read_u32::<BigEndian>()?;
 
-            _ => {
-                return Err(MessageError {
-                    value: MessageErrorValue::UnknowMessageType,
+                return Ok(RtmpMessageData::SharedObject {
+                    name,
+                    version,
+                    persistent,
+                    events: reader.extract_remaining_bytes(),
                 });
             }
+
+            msg_type_id::AGGREGATE => {
+                let messages =
+                    Self::parse_aggregate(&mut reader, self.chunk_info.message_header.timestamp)?;
+                return Ok(RtmpMessageData::AggregateData { messages });
+            }
+
+            _ => {}
         }
         Err(MessageError {
             value: MessageErrorValue::UnknowMessageType,
         })
     }
+
+    /*The aggregate body is a sequence of FLV tags, each followed by its
+    back pointer. The tag timestamps are rebased so that the first tag
+    gets the timestamp of the aggregate message.*/
+    fn parse_aggregate(
+        reader: &mut BytesReader,
+        timestamp: u32,
+    ) -> Result<Vec<AggregateSubMessage>, MessageError> {
+        let mut messages = Vec::new();
+        let mut first_timestamp: Option<u32> = None;
+
+        while reader.len() >= 11 {
+            let msg_type_id = reader.read_u8()?;
+            let data_size = reader.read_u24::<BigEndian>()?;
+            let tag_timestamp = reader.read_u24::<BigEndian>()?;
+            let timestamp_extended = reader.read_u8()?;
+            //stream id
+            reader.read_u24::<BigEndian>()?;
+
+            let tag_timestamp = (timestamp_extended as u32) << 24 | tag_timestamp;
+            let data = reader.read_bytes(data_size as usize)?;
+            //back pointer
+            if reader.len() >= 4 {
+                reader.read_u32::<BigEndian>()?;
+            }
+
+            let first_timestamp = *first_timestamp.get_or_insert(tag_timestamp);
+            messages.push(AggregateSubMessage {
+                msg_type_id,
+                timestamp: timestamp.wrapping_add(tag_timestamp.wrapping_sub(first_timestamp)),
+                data,
+            });
+        }
+
+        Ok(messages)
+    }
 }
 
 #[cfg(test)]
@@ -185,6 +255,47 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_aggregate_parse() {
+        use {
+            super::RtmpMessageData,
+            crate::{chunk::ChunkInfo, messages::define::msg_type_id},
+            bytes::BytesMut,
+        };
+
+        let payload: [u8; 37] = [
+            8, 0, 0, 2, 0, 3, 232, 0, 0, 0, 0, //audio tag header, timestamp 1000
+            175, 1, //audio data
+            0, 0, 0, 13, //back pointer
+            9, 0, 0, 5, 0, 4, 16, 0, 0, 0, 0, //video tag header, timestamp 1040
+            39, 1, 0, 0, 0, //video data
+            0, 0, 0, 16, //back pointer
+        ];
+
+        let chunk_info = ChunkInfo::new(
+            4,
+            0,
+            5000,
+            payload.len() as u32,
+            msg_type_id::AGGREGATE,
+            1,
+            BytesMut::from(&payload[..]),
+        );
+
+        match MessageParser::new(chunk_info).parse() {
+            Ok(RtmpMessageData::AggregateData { messages }) => {
+                assert_eq!(messages.len(), 2);
+                assert_eq!(messages[0].msg_type_id, msg_type_id::AUDIO);
+                assert_eq!(messages[0].timestamp, 5000);
+                assert_eq!(&messages[0].data[..], &[175, 1]);
+                assert_eq!(messages[1].msg_type_id, msg_type_id::VIDEO);
+                assert_eq!(messages[1].timestamp, 5040);
+                assert_eq!(&messages[1].data[..], &[39, 1, 0, 0, 0]);
+            }
+            _ => panic!("aggregate message not parsed"),
+        }
+    }
+
     use uuid::Uuid;
 
     #[test]
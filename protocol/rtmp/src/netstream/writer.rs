@@ -178,8 +178,7 @@ impl NetStreamWriter {
         self.write_chunk().await
     }
 
-    #[allow(dead_code)]
-    async fn write_on_bw_done(
+    pub async fn write_on_bw_done(
         &mut self,
         transaction_id: &f64,
         bandwidth: &f64,
@@ -192,6 +191,44 @@ impl NetStreamWriter {
         self.write_chunk().await
     }
 
+    pub async fn write_on_fcpublish(&mut self, stream_name: &str) -> Result<(), NetStreamError> {
+        self.write_fc_status("onFCPublish", "NetStream.Publish.Start", stream_name)
+            .await
+    }
+
+    pub async fn write_on_fcunpublish(&mut self, stream_name: &str) -> Result<(), NetStreamError> {
+        self.write_fc_status("onFCUnpublish", "NetStream.Unpublish.Success", stream_name)
+            .await
+    }
+
+    /*onFCPublish and onFCUnpublish are calls without a transaction,
+    the status object carries the stream name as its description.*/
+    async fn write_fc_status(
+        &mut self,
+        command_name: &str,
+        code: &str,
+        description: &str,
+    ) -> Result<(), NetStreamError> {
+        self.amf0_writer.write_string(&command_name.to_owned())?;
+        self.amf0_writer.write_number(&0.0)?;
+        self.amf0_writer.write_null()?;
+
+        let mut properties_map = HashMap::new();
+
+        properties_map.insert(
+            String::from("code"),
+            Amf0ValueType::UTF8String(code.to_owned()),
+        );
+        properties_map.insert(
+            String::from("description"),
+            Amf0ValueType::UTF8String(description.to_owned()),
+        );
+
+        self.amf0_writer.write_object(&properties_map)?;
+
+        self.write_chunk().await
+    }
+
     pub async fn write_on_status(
         &mut self,
         transaction_id: &f64,
@@ -1,4 +1,5 @@
 use {
+    crate::tls::errors::TlsError,
     failure::Fail,
     std::{fmt, io::Error},
     tokio::sync::broadcast::error::RecvError,
@@ -6,7 +7,7 @@ use {
 
 #[derive(Debug)]
 pub struct ClientError {
-    pub value: PushClientErrorValue,
+    pub value: ClientErrorValue,
 }
 
 impl fmt::Display for ClientError {
@@ -16,7 +17,7 @@ impl fmt::Display for ClientError {
 }
 
 #[derive(Debug, Fail)]
-pub enum PushClientErrorValue {
+pub enum ClientErrorValue {
     #[fail(display = "receive error\n")]
     ReceiveError(RecvError),
 
@@ -24,12 +25,16 @@ pub enum PushClientErrorValue {
     SendError,
     #[fail(display = "io error\n")]
     IOError(Error),
+    #[fail(display = "tls error: {}\n", _0)]
+    TlsError(TlsError),
+    #[fail(display = "connect timeout\n")]
+    ConnectTimeout,
 }
 
 impl From<Error> for ClientError {
     fn from(error: Error) -> Self {
         ClientError {
-            value: PushClientErrorValue::IOError(error),
+            value: ClientErrorValue::IOError(error),
         }
     }
 }
@@ -37,7 +42,15 @@ impl From<Error> for ClientError {
 impl From<RecvError> for ClientError {
     fn from(error: RecvError) -> Self {
         ClientError {
-            value: PushClientErrorValue::ReceiveError(error),
+            value: ClientErrorValue::ReceiveError(error),
+        }
+    }
+}
+
+impl From<TlsError> for ClientError {
+    fn from(error: TlsError) -> Self {
+        ClientError {
+            value: ClientErrorValue::TlsError(error),
         }
     }
 }
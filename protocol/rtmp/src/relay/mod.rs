@@ -1,3 +1,85 @@
 pub mod pull_client;
 pub mod push_client;
-pub mod errors;
\ No newline at end of file
+pub mod errors;
+
+use {
+    crate::tls,
+    bytesio::bytesio::NetStream,
+    errors::{ClientError, ClientErrorValue},
+    std::{net::SocketAddr, time::Duration},
+    tokio::{
+        net::TcpStream,
+        time::{sleep, timeout},
+    },
+};
+
+/* A relay which cannot connect to the other server tries again a few times
+before the stream is not relayed, an attempt includes the TLS handshake. */
+const CONNECT_ATTEMPTS: usize = 3;
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+const CONNECT_RETRY_INTERVAL: Duration = Duration::from_secs(1);
+
+async fn connect(
+    address: &str,
+    tls_server_name: Option<&str>,
+) -> Result<(Box<dyn NetStream>, SocketAddr), ClientError> {
+    let stream = TcpStream::connect(address).await?;
+    let remote_addr = stream.peer_addr()?;
+
+    let stream: Box<dyn NetStream> = match tls_server_name {
+        Some(server_name) => Box::new(tls::connect(stream, server_name).await?),
+        None => Box::new(stream),
+    };
+    Ok((stream, remote_addr))
+}
+
+/* Connects to the other server, over TLS when a server name is given. */
+async fn connect_with_retries(
+    address: &str,
+    tls_server_name: Option<&str>,
+) -> Option<(Box<dyn NetStream>, SocketAddr)> {
+    for attempt in 1..=CONNECT_ATTEMPTS {
+        let rv = match timeout(CONNECT_TIMEOUT, connect(address, tls_server_name)).await {
+            Ok(rv) => rv,
+            Err(_) => Err(ClientError {
+                value: ClientErrorValue::ConnectTimeout,
+            }),
+        };
+        match rv {
+            Ok(connection) => return Some(connection),
+            Err(err) => {
+                tracing::error!(
+                    "relay connect error: address: {}, attempt: {}, err: {}",
+                    address,
+                    attempt,
+                    err
+                );
+            }
+        }
+        if attempt < CONNECT_ATTEMPTS {
+            sleep(CONNECT_RETRY_INTERVAL).await;
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::connect_with_retries, tokio::net::TcpListener};
+
+    #[tokio::test]
+    async fn test_connect_with_retries() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        assert!(connect_with_retries(&address, None).await.is_some());
+
+        /*an invalid tls server name fails before the handshake*/
+        assert!(connect_with_retries(&address, Some("not a server name"))
+            .await
+            .is_none());
+
+        /*nothing listens any more*/
+        drop(listener);
+        assert!(connect_with_retries(&address, None).await.is_none());
+    }
+}
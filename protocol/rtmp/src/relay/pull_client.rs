@@ -1,16 +1,18 @@
 use {
-    super::errors::ClientError,
+    super::{connect_with_retries, errors::ClientError},
     crate::{
         channels::define::{ChannelEventProducer, ClientEvent, ClientEventConsumer},
         session::client_session::{ClientSession, ClientType},
     },
-    tokio::net::TcpStream,
 };
 
 pub struct PullClient {
     address: String,
     client_event_consumer: ClientEventConsumer,
     channel_event_producer: ChannelEventProducer,
+    /* The server name to verify when the relay connects over TLS (rtmps). */
+    tls_server_name: Option<String>,
+    complex_handshake: bool,
 }
 
 impl PullClient {
@@ -24,9 +26,19 @@ impl PullClient {
 
             client_event_consumer: consumer,
             channel_event_producer: producer,
+            tls_server_name: None,
+            complex_handshake: false,
         }
     }
 
+    pub fn set_tls_server_name(&mut self, server_name: String) {
+        self.tls_server_name = Some(server_name);
+    }
+
+    pub fn set_complex_handshake(&mut self, enabled: bool) {
+        self.complex_handshake = enabled;
+    }
+
     pub async fn run(&mut self) -> Result<(), ClientError> {
         loop {
             let val = self.client_event_consumer.recv().await?;
@@ -41,17 +53,31 @@ impl PullClient {
                     app_name,
                     stream_name
                 );
-                let stream = TcpStream::connect(self.address.clone()).await?;
-
-                let mut client_session = ClientSession::new(
-                    stream,
-                    ClientType::Play,
-                    app_name.clone(),
-                    stream_name.clone(),
-                    self.channel_event_producer.clone(),
+                let (address, tls_server_name, complex_handshake) = (
+                    self.address.clone(),
+                    self.tls_server_name.clone(),
+                    self.complex_handshake,
                 );
+                let channel_event_producer = self.channel_event_producer.clone();
 
+                /*a slow or failing connection does not hold the other pulls back*/
                 tokio::spawn(async move {
+                    let (stream, remote_addr) =
+                        match connect_with_retries(&address, tls_server_name.as_deref()).await {
+                            Some(connection) => connection,
+                            None => return,
+                        };
+
+                    let mut client_session = ClientSession::new(
+                        stream,
+                        ClientType::Play,
+                        app_name,
+                        stream_name,
+                        channel_event_producer,
+                    );
+                    client_session.set_complex_handshake(complex_handshake);
+                    client_session.set_remote_addr(remote_addr);
+
                     if let Err(err) = client_session.run().await {
                         tracing::error!("client_session as pull client run error: {}", err);
                     }
@@ -1,16 +1,23 @@
 use {
-    super::errors::ClientError,
+    super::{connect_with_retries, errors::ClientError},
     crate::{
         channels::define::{ChannelEventProducer, ClientEvent, ClientEventConsumer},
         session::client_session::{ClientSession, ClientType},
     },
-    tokio::net::TcpStream,
+    std::collections::HashSet,
 };
 
 pub struct PushClient {
     address: String,
     client_event_consumer: ClientEventConsumer,
     channel_event_producer: ChannelEventProducer,
+    /* The server name to verify when the relay connects over TLS (rtmps). */
+    tls_server_name: Option<String>,
+    complex_handshake: bool,
+    /* Set for the relay targets of one app, which only relay its streams. */
+    app_name: Option<String>,
+    /* The apps which have their own relay targets, skipped by the global ones. */
+    excluded_app_names: HashSet<String>,
 }
 
 impl PushClient {
@@ -24,6 +31,33 @@ impl PushClient {
 
             client_event_consumer: consumer,
             channel_event_producer: producer,
+            tls_server_name: None,
+            complex_handshake: false,
+            app_name: None,
+            excluded_app_names: HashSet::new(),
+        }
+    }
+
+    pub fn set_tls_server_name(&mut self, server_name: String) {
+        self.tls_server_name = Some(server_name);
+    }
+
+    pub fn set_complex_handshake(&mut self, enabled: bool) {
+        self.complex_handshake = enabled;
+    }
+
+    pub fn set_app_name(&mut self, app_name: String) {
+        self.app_name = Some(app_name);
+    }
+
+    pub fn set_excluded_app_names(&mut self, app_names: HashSet<String>) {
+        self.excluded_app_names = app_names;
+    }
+
+    fn relays(&self, app_name: &String) -> bool {
+        match &self.app_name {
+            Some(name) => name == app_name,
+            None => !self.excluded_app_names.contains(app_name),
         }
     }
 
@@ -37,23 +71,44 @@ impl PushClient {
                     app_name,
                     stream_name,
                 } => {
+                    if !self.relays(&app_name) {
+                        continue;
+                    }
                     tracing::info!(
                         "publish app_name: {} stream_name: {} address: {}",
                         app_name.clone(),
                         stream_name.clone(),
                         self.address.clone()
                     );
-                    let stream = TcpStream::connect(self.address.clone()).await?;
-
-                    let mut client_session = ClientSession::new(
-                        stream,
-                        ClientType::Publish,
-                        app_name.clone(),
-                        stream_name.clone(),
-                        self.channel_event_producer.clone(),
+                    let (address, tls_server_name, complex_handshake) = (
+                        self.address.clone(),
+                        self.tls_server_name.clone(),
+                        self.complex_handshake,
                     );
+                    let channel_event_producer = self.channel_event_producer.clone();
 
+                    /*a slow or failing connection does not hold the other pushes back*/
                     tokio::spawn(async move {
+                        let (stream, remote_addr) = match connect_with_retries(
+                            &address,
+                            tls_server_name.as_deref(),
+                        )
+                        .await
+                        {
+                            Some(connection) => connection,
+                            None => return,
+                        };
+
+                        let mut client_session = ClientSession::new(
+                            stream,
+                            ClientType::Publish,
+                            app_name,
+                            stream_name,
+                            channel_event_producer,
+                        );
+                        client_session.set_complex_handshake(complex_handshake);
+                        client_session.set_remote_addr(remote_addr);
+
                         if let Err(err) = client_session.run().await {
                             tracing::error!("client_session as push client run error: {}", err);
                         }
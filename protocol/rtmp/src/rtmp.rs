@@ -1,16 +1,31 @@
-use crate::session::server_session::RtmpWebhookConfig;
+use crate::session::{define, server_session::RtmpWebhookConfig};
 
 use super::channels::define::ChannelEventProducer;
 
 use super::session::server_session;
+use std::collections::HashMap;
 use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
 use tokio::io::Error;
 use tokio::net::TcpListener;
+use tokio::time::timeout;
+use tokio_rustls::TlsAcceptor;
+
+/* A client which does not finish the TLS handshake in time is dropped. */
+const TLS_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
 
 pub struct RtmpServer {
     address: String,
     event_producer: ChannelEventProducer,
     webhook_config: RtmpWebhookConfig,
+    app_webhook_configs: Arc<HashMap<String, RtmpWebhookConfig>>,
+    /* Set for the rtmps listener, the TLS is terminated
+    before the stream is handed to the session. */
+    tls_acceptor: Option<TlsAcceptor>,
+    /* The keepalive ping settings of the sessions. */
+    ping_interval: Duration,
+    ping_max_missed: u32,
 }
 
 impl RtmpServer {
@@ -23,24 +38,82 @@ impl RtmpServer {
             address,
             event_producer,
             webhook_config,
+            app_webhook_configs: Arc::new(HashMap::new()),
+            tls_acceptor: None,
+            ping_interval: Duration::from_secs(define::PING_INTERVAL_SECS),
+            ping_max_missed: define::PING_MAX_MISSED,
         }
     }
 
+    pub fn set_keepalive(&mut self, ping_interval: Duration, ping_max_missed: u32) {
+        self.ping_interval = ping_interval;
+        self.ping_max_missed = ping_max_missed;
+    }
+
+    /* Overrides the webhooks for the sessions connecting to these app names. */
+    pub fn set_app_webhook_configs(&mut self, configs: HashMap<String, RtmpWebhookConfig>) {
+        self.app_webhook_configs = Arc::new(configs);
+    }
+
+    pub fn set_tls_acceptor(&mut self, tls_acceptor: TlsAcceptor) {
+        self.tls_acceptor = Some(tls_acceptor);
+    }
+
     pub async fn run(&mut self) -> Result<(), Error> {
         let socket_addr: &SocketAddr = &self.address.parse().unwrap();
         let listener = TcpListener::bind(socket_addr).await?;
 
-        tracing::info!("Rtmp server listening on tcp://{}", socket_addr);
+        if self.tls_acceptor.is_some() {
+            tracing::info!("Rtmps server listening on tcp://{}", socket_addr);
+        } else {
+            tracing::info!("Rtmp server listening on tcp://{}", socket_addr);
+        }
         loop {
-            let (tcp_stream, _) = listener.accept().await?;
+            let (tcp_stream, remote_addr) = listener.accept().await?;
             //tcp_stream.set_keepalive(Some(Duration::from_secs(30)))?;
 
-            let mut session = server_session::ServerSession::new(
-                tcp_stream,
-                self.event_producer.clone(),
-                self.webhook_config.clone(),
-            );
+            let tls_acceptor = self.tls_acceptor.clone();
+            let event_producer = self.event_producer.clone();
+            let webhook_config = self.webhook_config.clone();
+            let app_webhook_configs = Arc::clone(&self.app_webhook_configs);
+            let (ping_interval, ping_max_missed) = (self.ping_interval, self.ping_max_missed);
+
             tokio::spawn(async move {
+                /*the TLS handshake is done in the spawned task, so that
+                a slow client does not block the accept loop*/
+                let mut session = match tls_acceptor {
+                    Some(acceptor) => {
+                        match timeout(TLS_HANDSHAKE_TIMEOUT, acceptor.accept(tcp_stream)).await {
+                            Ok(Ok(tls_stream)) => server_session::ServerSession::new(
+                                tls_stream,
+                                event_producer,
+                                webhook_config,
+                            ),
+                            Ok(Err(err)) => {
+                                tracing::info!(
+                                    "tls handshake error: addr: {}, err: {}",
+                                    remote_addr,
+                                    err
+                                );
+                                return;
+                            }
+                            Err(_) => {
+                                tracing::info!("tls handshake timeout: addr: {}", remote_addr);
+                                return;
+                            }
+                        }
+                    }
+                    None => server_session::ServerSession::new(
+                        tcp_stream,
+                        event_producer,
+                        webhook_config,
+                    ),
+                };
+
+                session.set_remote_addr(remote_addr);
+                session.set_app_webhook_configs(app_webhook_configs);
+                session.set_keepalive(ping_interval, ping_max_missed);
+
                 if let Err(err) = session.run().await {
                     tracing::info!(
                         "session run error: session_type: {}, app_name: {}, stream_name: {}, err: {}",
@@ -15,16 +15,22 @@ use {
             define::CHUNK_SIZE,
             unpacketizer::{ChunkUnpacketizer, UnpackResult},
         },
-        handshake::{define::ClientHandshakeState, handshake_client::SimpleHandshakeClient},
-        messages::{define::RtmpMessageData, parser::MessageParser},
+        handshake::{define::ClientHandshakeState, handshake_client::HandshakeClient},
+        messages::{
+            define::{RtmpMessageData, SetPeerBandwidthProperties},
+            parser::MessageParser,
+        },
         netconnection::writer::{ConnectProperties, NetConnection},
         netstream::writer::NetStreamWriter,
         protocol_control_messages::writer::ProtocolControlMessagesWriter,
         user_control_messages::writer::EventMessagesWriter,
     },
-    bytesio::{bytes_writer::AsyncBytesWriter, bytesio::BytesIO},
-    std::{collections::HashMap, sync::Arc},
-    tokio::{net::TcpStream, sync::Mutex},
+    bytesio::{
+        bytes_writer::AsyncBytesWriter,
+        bytesio::{BytesIO, NetStream},
+    },
+    std::{collections::HashMap, net::SocketAddr, sync::Arc},
+    tokio::sync::Mutex,
 };
 
 #[allow(dead_code)]
@@ -61,7 +67,7 @@ pub enum ClientType {
 pub struct ClientSession {
     io: Arc<Mutex<BytesIO>>,
     common: Common,
-    handshaker: SimpleHandshakeClient,
+    handshaker: HandshakeClient,
     unpacketizer: ChunkUnpacketizer,
     app_name: String,
     stream_name: String,
@@ -75,8 +81,8 @@ pub struct ClientSession {
 
 impl ClientSession {
     #[allow(dead_code)]
-    pub fn new(
-        stream: TcpStream,
+    pub fn new<S: NetStream + 'static>(
+        stream: S,
         client_type: ClientType,
         app_name: String,
         stream_name: String,
@@ -88,7 +94,7 @@ impl ClientSession {
         Self {
             io: Arc::clone(&net_io),
             common: Common::new(Arc::clone(&net_io), event_producer, SessionType::Client),
-            handshaker: SimpleHandshakeClient::new(Arc::clone(&net_io)),
+            handshaker: HandshakeClient::new(Arc::clone(&net_io), false),
             unpacketizer: ChunkUnpacketizer::new(),
             app_name,
             stream_name,
@@ -98,7 +104,28 @@ impl ClientSession {
         }
     }
 
+    /* Starts with the digest handshake, for servers which require it. */
+    pub fn set_complex_handshake(&mut self, enabled: bool) {
+        self.handshaker = HandshakeClient::new(Arc::clone(&self.io), enabled);
+    }
+
+    pub fn set_remote_addr(&mut self, remote_addr: SocketAddr) {
+        self.common.session_info.remote_addr = Some(remote_addr);
+    }
+
     pub async fn run(&mut self) -> Result<(), SessionError> {
+        let kick_token = self.common.session_info.kick_token.clone();
+
+        tokio::select! {
+            rv = self.process_states() => rv,
+            /*the channels have already let go of a kicked session*/
+            _ = kick_token.cancelled() => Err(SessionError {
+                value: SessionErrorValue::Kicked,
+            }),
+        }
+    }
+
+    async fn process_states(&mut self) -> Result<(), SessionError> {
         loop {
             match self.state {
                 ClientSessionState::Handshake => {
@@ -130,14 +157,26 @@ impl ClientSession {
                         .await?;
                     self.state = ClientSessionState::WaitStateChange;
                 }
-                ClientSessionState::StartPublish => {
-                    tracing::info!("[C -> S] StartPublish...");
-                    self.common.send_channel_data().await?;
-                }
+                ClientSessionState::StartPublish => {}
                 ClientSessionState::WaitStateChange => {}
             }
 
-            let data = self.io.lock().await.read().await?;
+            /* While publishing, the media is sent in between reading the
+            server's messages so acknowledgements keep being processed. */
+            let data = match self.state {
+                ClientSessionState::StartPublish => {
+                    let io = Arc::clone(&self.io);
+                    tokio::select! {
+                        data = self.common.recv_channel_data() => {
+                            self.common.send_channel_data(data?).await?;
+                            continue;
+                        }
+                        data = async { io.lock().await.read().await } => data?,
+                    }
+                }
+                _ => self.io.lock().await.read().await?,
+            };
+            self.common.on_bytes_received(data.len()).await?;
             self.unpacketizer.extend_data(&data[..]);
 
             loop {
@@ -162,7 +201,7 @@ impl ClientSession {
     async fn handshake(&mut self) -> Result<(), SessionError> {
         loop {
             self.handshaker.handshake().await?;
-            if self.handshaker.state == ClientHandshakeState::Finish {
+            if *self.handshaker.state() == ClientHandshakeState::Finish {
                 tracing::info!("handshake finish");
                 break;
             }
@@ -192,12 +231,20 @@ impl ClientSession {
                 self.on_amf0_command_message(command_name, transaction_id, command_object, others)
                     .await?
             }
-            RtmpMessageData::SetPeerBandwidth { .. } => {
+            RtmpMessageData::SetPeerBandwidth { properties } => {
                 tracing::info!("[C <- S] on_set_peer_bandwidth...");
-                self.on_set_peer_bandwidth().await?
+                self.on_set_peer_bandwidth(properties).await?
             }
-            RtmpMessageData::WindowAcknowledgementSize { .. } => {
+            RtmpMessageData::WindowAcknowledgementSize { size } => {
                 tracing::info!("[C <- S] on_windows_acknowledgement_size...");
+                self.common.on_window_acknowledgement_size(*size);
+            }
+            RtmpMessageData::Acknowledgement { sequence_number } => {
+                self.common.on_acknowledgement(*sequence_number);
+            }
+            RtmpMessageData::PingRequest { timestamp } => {
+                tracing::trace!("[C <- S] on_ping_request...");
+                self.common.on_ping_request(*timestamp).await?;
             }
             RtmpMessageData::SetChunkSize { chunk_size } => {
                 tracing::info!("[C <- S] on_set_chunk_size...");
@@ -213,6 +260,9 @@ impl ClientSession {
             }
             RtmpMessageData::AudioData { data } => self.common.on_audio_data(data, timestamp)?,
             RtmpMessageData::VideoData { data } => self.common.on_video_data(data, timestamp)?,
+            RtmpMessageData::AggregateData { messages } => {
+                self.common.on_aggregate_data(messages)?
+            }
 
             _ => {}
         }
@@ -239,7 +289,7 @@ impl ClientSession {
 
         let empty_cmd_obj: HashMap<String, Amf0ValueType> = HashMap::new();
         let _ = match command_object {
-            Amf0ValueType::Object(obj) => obj,
+            Amf0ValueType::Object(obj) | Amf0ValueType::EcmaArray(obj) => obj,
             // Amf0ValueType::Null =>
             _ => &empty_cmd_obj,
         };
@@ -261,7 +311,9 @@ impl ClientSession {
             }
             "onStatus" => {
                 match others.remove(0) {
-                    Amf0ValueType::Object(obj) => self.on_status(&obj).await?,
+                    Amf0ValueType::Object(obj) | Amf0ValueType::EcmaArray(obj) => {
+                        self.on_status(&obj).await?
+                    }
                     _ => {
                         return Err(SessionError {
                             value: SessionErrorValue::Amf0ValueCountNotCorrect,
@@ -285,6 +337,7 @@ impl ClientSession {
         let url = format!("rtmp://localhost:1935/{app_name}", app_name = self.app_name);
         properties.app = Some(self.app_name.clone());
         properties.tc_url = Some(url.clone());
+        self.common.session_info.tc_url = Some(url.clone());
 
         match self.client_type {
             ClientType::Play => {
@@ -368,12 +421,9 @@ impl ClientSession {
         &mut self,
         window_size: u32,
     ) -> Result<(), SessionError> {
-        let mut controlmessage =
-            ProtocolControlMessagesWriter::new(AsyncBytesWriter::new(self.io.clone()));
-        controlmessage
-            .write_window_acknowledgement_size(window_size)
-            .await?;
-        Ok(())
+        self.common
+            .send_window_acknowledgement_size(window_size)
+            .await
     }
 
     pub async fn send_set_buffer_length(
@@ -388,16 +438,18 @@ impl ClientSession {
     }
 
     pub async fn on_result_connect(&mut self) -> Result<(), SessionError> {
-        let mut controlmessage =
-            ProtocolControlMessagesWriter::new(AsyncBytesWriter::new(self.io.clone()));
-        controlmessage.write_acknowledgement(3107).await?;
-
         let mut netstream = NetStreamWriter::new(Arc::clone(&self.io));
         netstream
-            .write_release_stream(&(define::TRANSACTION_ID_CONNECT as f64), &self.stream_name)
+            .write_release_stream(
+                &(define::TRANSACTION_ID_RELEASE_STREAM as f64),
+                &self.stream_name,
+            )
             .await?;
         netstream
-            .write_fcpublish(&(define::TRANSACTION_ID_CONNECT as f64), &self.stream_name)
+            .write_fcpublish(
+                &(define::TRANSACTION_ID_FCPUBLISH as f64),
+                &self.stream_name,
+            )
             .await?;
 
         self.state = ClientSessionState::CreateStream;
@@ -433,8 +485,15 @@ impl ClientSession {
         Ok(())
     }
 
-    pub async fn on_set_peer_bandwidth(&mut self) -> Result<(), SessionError> {
-        self.send_window_acknowledgement_size(250000).await?;
+    pub async fn on_set_peer_bandwidth(
+        &mut self,
+        properties: &SetPeerBandwidthProperties,
+    ) -> Result<(), SessionError> {
+        self.common.on_set_peer_bandwidth(properties).await?;
+        /* Announce a window anyway so the server acknowledges what is published. */
+        if self.common.flow_control.announced_window_size() == 0 {
+            self.send_window_acknowledgement_size(250000).await?;
+        }
         Ok(())
     }
 
@@ -449,6 +508,7 @@ impl ClientSession {
         if let Some(Amf0ValueType::UTF8String(code_info)) = obj.get("code") {
             match &code_info[..] {
                 "NetStream.Publish.Start" => {
+                    tracing::info!("[C -> S] StartPublish...");
                     self.state = ClientSessionState::StartPublish;
                     self.common
                         .subscribe_from_channels(
@@ -462,7 +522,11 @@ impl ClientSession {
 
                 "NetStream.Play.Start" => {
                     self.common
-                        .publish_to_channels(self.app_name.clone(), self.stream_name.clone())
+                        .publish_to_channels(
+                            self.app_name.clone(),
+                            self.stream_name.clone(),
+                            self.subscriber_id,
+                        )
                         .await?
                 }
                 _ => {}
@@ -2,41 +2,108 @@ use {
     super::{
         define::{SessionType, SubscribeType},
         errors::{SessionError, SessionErrorValue},
+        flow_control::FlowControl,
     },
     crate::{
-        channels::define::{
-            ChannelData, ChannelDataConsumer, ChannelDataProducer, ChannelEvent,
-            ChannelEventProducer,
+        channels::{
+            define::{
+                ChannelData, ChannelDataProducer, ChannelEvent, ChannelEventProducer, StartMode,
+                SubscribeReceiver, SubscriberDataConsumer,
+            },
+            errors::ChannelError,
         },
         chunk::{
             define::{chunk_type, csid_type},
             packetizer::ChunkPacketizer,
             ChunkInfo,
         },
-        messages::define::msg_type_id,
-    },
-    bytes::BytesMut,
-    bytesio::bytesio::BytesIO,
-    std::{sync::Arc, time::Duration},
-    tokio::{
-        sync::{mpsc, oneshot, Mutex},
-        time::sleep,
+        messages::define::{msg_type_id, AggregateSubMessage, SetPeerBandwidthProperties},
+        protocol_control_messages::writer::ProtocolControlMessagesWriter,
+        user_control_messages::writer::EventMessagesWriter,
     },
+    bytes::{Bytes, BytesMut},
+    bytesio::{bytes_writer::AsyncBytesWriter, bytesio::BytesIO},
+    chrono::Local,
+    serde::Serialize,
+    std::{net::SocketAddr, sync::Arc},
+    tokio::sync::{mpsc, oneshot, Mutex},
+    tokio_util::sync::CancellationToken,
     uuid::Uuid,
 };
-#[derive(Debug)]
+/* Who is behind a session and how it connected, reported by the stats api. */
+#[derive(Debug, Clone, Serialize)]
+pub struct SessionInfo {
+    pub remote_addr: Option<SocketAddr>,
+    pub connect_time: String,
+    /* The rtmp connect command's tcUrl and flashVer. */
+    pub tc_url: Option<String>,
+    pub flash_ver: Option<String>,
+    /* The http User-Agent header. */
+    pub user_agent: Option<String>,
+    /* Cancelled when the session is kicked through the api. */
+    #[serde(skip)]
+    pub kick_token: CancellationToken,
+    /* Cancelled when the player is dropped for lagging behind the stream. */
+    #[serde(skip)]
+    pub lag_token: CancellationToken,
+    /* Cancelled when the player has to wait for the stream to be published. */
+    #[serde(skip)]
+    pub wait_token: CancellationToken,
+}
+
+impl SessionInfo {
+    pub fn new(remote_addr: Option<SocketAddr>) -> Self {
+        Self {
+            remote_addr,
+            connect_time: Local::now().to_rfc3339(),
+            tc_url: None,
+            flash_ver: None,
+            user_agent: None,
+            kick_token: CancellationToken::new(),
+            lag_token: CancellationToken::new(),
+            wait_token: CancellationToken::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct SubscriberInfo {
     pub id: Uuid,
     pub sub_type: SubscribeType,
+    pub session_info: SessionInfo,
+    /* None starts the subscriber with the channels' start mode. */
+    pub start_mode: Option<StartMode>,
+}
+
+#[derive(Debug, Clone)]
+pub struct PublisherInfo {
+    pub id: Uuid,
+    pub session_info: SessionInfo,
 }
 pub struct Common {
+    io: Arc<Mutex<BytesIO>>,
     packetizer: ChunkPacketizer,
+    pub flow_control: FlowControl,
 
-    data_consumer: ChannelDataConsumer,
+    data_consumer: SubscriberDataConsumer,
     data_producer: ChannelDataProducer,
 
     event_producer: ChannelEventProducer,
     pub session_type: SessionType,
+    pub session_info: SessionInfo,
+    /* Asked for by a player with the start_mode query parameter. */
+    pub start_mode: Option<StartMode>,
+
+    /* Set by a player's receiveAudio, receiveVideo and pause. */
+    receive_audio: bool,
+    receive_video: bool,
+    paused: bool,
+
+    /* The queue of a player subscribing again starts its timestamps at 0,
+    they are moved to follow the newest one the player got. */
+    timestamp_offset: u32,
+    last_timestamp: Option<u32>,
+    continue_timestamps: bool,
 }
 
 impl Common {
@@ -46,50 +113,203 @@ impl Common {
         session_type: SessionType,
     ) -> Self {
         //only used for init,since I don't found a better way to deal with this.
-        let (init_producer, init_consumer) = mpsc::unbounded_channel();
+        let (init_producer, _) = mpsc::unbounded_channel();
+        let (_, init_consumer) = mpsc::channel(1);
 
         Self {
             packetizer: ChunkPacketizer::new(Arc::clone(&net_io)),
+            flow_control: FlowControl::new(),
+            io: net_io,
 
             data_producer: init_producer,
             data_consumer: init_consumer,
 
             event_producer,
             session_type,
+            session_info: SessionInfo::new(None),
+            start_mode: None,
+
+            receive_audio: true,
+            receive_video: true,
+            paused: false,
+
+            timestamp_offset: 0,
+            last_timestamp: None,
+            continue_timestamps: false,
         }
     }
-    pub async fn send_channel_data(&mut self) -> Result<(), SessionError> {
-        let mut retry_times = 0;
-        loop {
-            if let Some(data) = self.data_consumer.recv().await {
-                match data {
-                    ChannelData::Audio { timestamp, data } => {
-                        self.send_audio(data, timestamp).await?;
-                    }
-                    ChannelData::Video { timestamp, data } => {
-                        self.send_video(data, timestamp).await?;
-                    }
-                    ChannelData::MetaData { timestamp, data } => {
-                        self.send_metadata(data, timestamp).await?;
-                    }
-                }
-            } else {
-                retry_times += 1;
-                tracing::debug!(
-                    "send_channel_data: no data receives ,retry {} times!",
-                    retry_times
-                );
+    /* Waits for the next media message. While the peer's window is
+    exhausted nothing is taken from the channel, so the caller keeps
+    reading until an Acknowledgement arrives. */
+    pub async fn recv_channel_data(&mut self) -> Result<ChannelData, SessionError> {
+        if self.paused || self.flow_control.is_window_exhausted() {
+            std::future::pending::<()>().await;
+        }
+
+        match self.data_consumer.recv().await {
+            Some(mut data) => {
+                let timestamp = self.continue_timestamp(data.timestamp());
+                data.set_timestamp(timestamp);
+                Ok(data)
+            }
+            None => {
+                tracing::debug!("recv_channel_data: the channel is closed");
+                Err(SessionError {
+                    value: SessionErrorValue::NoMediaDataReceived,
+                })
+            }
+        }
+    }
+
+    fn continue_timestamp(&mut self, timestamp: u32) -> u32 {
+        if self.continue_timestamps {
+            self.continue_timestamps = false;
+            if let Some(last) = self.last_timestamp {
+                self.timestamp_offset = last.wrapping_add(1).wrapping_sub(timestamp);
+            }
+        }
+
+        let timestamp = timestamp.wrapping_add(self.timestamp_offset);
+        /*the timestamps wrap past 2^32 ms, the newest is the one ahead*/
+        let is_newest = match self.last_timestamp {
+            Some(last) => (timestamp.wrapping_sub(last) as i32) > 0,
+            None => true,
+        };
+        if is_newest {
+            self.last_timestamp = Some(timestamp);
+        }
+        timestamp
+    }
 
-                if retry_times > 10 {
-                    return Err(SessionError {
-                        value: SessionErrorValue::NoMediaDataReceived,
-                    });
+    pub async fn send_channel_data(&mut self, data: ChannelData) -> Result<(), SessionError> {
+        let bytes_written = self.packetizer.bytes_written();
+
+        match data {
+            ChannelData::Audio { timestamp, data } => {
+                if self.receive_audio {
+                    self.send_audio(data, timestamp).await?;
                 }
             }
+            ChannelData::Video { timestamp, data } => {
+                if self.receive_video {
+                    self.send_video(data, timestamp).await?;
+                }
+            }
+            ChannelData::MetaData { timestamp, data } => {
+                self.send_metadata(data, timestamp).await?;
+            }
+            /*the timestamps keep increasing, the players follow the new frames*/
+            ChannelData::Discontinuity { .. } => {}
+        }
+
+        self.flow_control
+            .on_bytes_sent(self.packetizer.bytes_written().wrapping_sub(bytes_written));
+
+        Ok(())
+    }
+
+    pub fn set_receive_audio(&mut self, enabled: bool) {
+        self.receive_audio = enabled;
+    }
+
+    pub fn set_receive_video(&mut self, enabled: bool) {
+        self.receive_video = enabled;
+    }
+
+    /* Nothing is taken from the channel while paused. */
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /* Counts the bytes read from the peer and acknowledges every window. */
+    pub async fn on_bytes_received(&mut self, len: usize) -> Result<(), SessionError> {
+        if let Some(sequence_number) = self.flow_control.on_bytes_received(len) {
+            let mut controlmessage =
+                ProtocolControlMessagesWriter::new(AsyncBytesWriter::new(self.io.clone()));
+            controlmessage
+                .write_acknowledgement(sequence_number)
+                .await?;
+        }
+        Ok(())
+    }
+
+    pub fn on_acknowledgement(&mut self, sequence_number: u32) {
+        tracing::trace!(
+            "acknowledgement received, sequence number: {}",
+            sequence_number
+        );
+        self.flow_control.on_acknowledgement(sequence_number);
+    }
+
+    pub fn on_window_acknowledgement_size(&mut self, size: u32) {
+        tracing::trace!("window acknowledgement size: {}", size);
+        self.flow_control.set_ack_window_size(size);
+    }
+
+    pub async fn on_set_peer_bandwidth(
+        &mut self,
+        properties: &SetPeerBandwidthProperties,
+    ) -> Result<(), SessionError> {
+        if let Some(window_size) = self
+            .flow_control
+            .on_set_peer_bandwidth(properties.window_size, properties.limit_type)
+        {
+            self.send_window_acknowledgement_size(window_size).await?;
+        }
+        Ok(())
+    }
+
+    pub async fn on_ping_request(&mut self, timestamp: u32) -> Result<(), SessionError> {
+        let mut eventmessages = EventMessagesWriter::new(AsyncBytesWriter::new(self.io.clone()));
+        eventmessages.write_ping_response(timestamp).await?;
+        Ok(())
+    }
+
+    pub async fn send_ping_request(&mut self, timestamp: u32) -> Result<(), SessionError> {
+        let mut eventmessages = EventMessagesWriter::new(AsyncBytesWriter::new(self.io.clone()));
+        eventmessages.write_ping_request(timestamp).await?;
+        Ok(())
+    }
+
+    /*Report the round trip time to the stream's statistics*/
+    pub fn report_rtt(
+        &mut self,
+        app_name: String,
+        stream_name: String,
+        id: Uuid,
+        is_publisher: bool,
+        rtt: u32,
+    ) {
+        let rtt_event = ChannelEvent::UpdateRtt {
+            app_name,
+            stream_name,
+            id,
+            is_publisher,
+            rtt,
+        };
+        if let Err(err) = self.event_producer.send(rtt_event) {
+            tracing::error!("report_rtt err {}\n", err);
         }
     }
 
-    pub async fn send_audio(&mut self, data: BytesMut, timestamp: u32) -> Result<(), SessionError> {
+    pub async fn send_window_acknowledgement_size(
+        &mut self,
+        window_size: u32,
+    ) -> Result<(), SessionError> {
+        let mut controlmessage =
+            ProtocolControlMessagesWriter::new(AsyncBytesWriter::new(self.io.clone()));
+        controlmessage
+            .write_window_acknowledgement_size(window_size)
+            .await?;
+        self.flow_control.set_announced_window_size(window_size);
+        Ok(())
+    }
+
+    pub async fn send_audio(&mut self, data: Bytes, timestamp: u32) -> Result<(), SessionError> {
         let mut chunk_info = ChunkInfo::new(
             csid_type::AUDIO,
             chunk_type::TYPE_0,
@@ -97,15 +317,17 @@ impl Common {
             data.len() as u32,
             msg_type_id::AUDIO,
             0,
-            data,
+            BytesMut::new(),
         );
 
-        self.packetizer.write_chunk(&mut chunk_info).await?;
+        self.packetizer
+            .write_message(&mut chunk_info, &data[..])
+            .await?;
 
         Ok(())
     }
 
-    pub async fn send_video(&mut self, data: BytesMut, timestamp: u32) -> Result<(), SessionError> {
+    pub async fn send_video(&mut self, data: Bytes, timestamp: u32) -> Result<(), SessionError> {
         let mut chunk_info = ChunkInfo::new(
             csid_type::VIDEO,
             chunk_type::TYPE_0,
@@ -113,19 +335,17 @@ impl Common {
             data.len() as u32,
             msg_type_id::VIDEO,
             0,
-            data,
+            BytesMut::new(),
         );
 
-        self.packetizer.write_chunk(&mut chunk_info).await?;
+        self.packetizer
+            .write_message(&mut chunk_info, &data[..])
+            .await?;
 
         Ok(())
     }
 
-    pub async fn send_metadata(
-        &mut self,
-        data: BytesMut,
-        timestamp: u32,
-    ) -> Result<(), SessionError> {
+    pub async fn send_metadata(&mut self, data: Bytes, timestamp: u32) -> Result<(), SessionError> {
         let mut chunk_info = ChunkInfo::new(
             csid_type::DATA_AMF0_AMF3,
             chunk_type::TYPE_0,
@@ -133,10 +353,12 @@ impl Common {
             data.len() as u32,
             msg_type_id::DATA_AMF0,
             0,
-            data,
+            BytesMut::new(),
         );
 
-        self.packetizer.write_chunk(&mut chunk_info).await?;
+        self.packetizer
+            .write_message(&mut chunk_info, &data[..])
+            .await?;
         Ok(())
     }
 
@@ -147,7 +369,7 @@ impl Common {
     ) -> Result<(), SessionError> {
         let data = ChannelData::Video {
             timestamp: *timestamp,
-            data: data.clone(),
+            data: data.split().freeze(),
         };
 
         match self.data_producer.send(data) {
@@ -170,7 +392,7 @@ impl Common {
     ) -> Result<(), SessionError> {
         let data = ChannelData::Audio {
             timestamp: *timestamp,
-            data: data.clone(),
+            data: data.split().freeze(),
         };
 
         match self.data_producer.send(data) {
@@ -193,7 +415,7 @@ impl Common {
     ) -> Result<(), SessionError> {
         let data = ChannelData::MetaData {
             timestamp: *timestamp,
-            data: body.clone(),
+            data: body.split().freeze(),
         };
 
         match self.data_producer.send(data) {
@@ -208,15 +430,44 @@ impl Common {
         Ok(())
     }
 
+    /*The sub messages of an aggregate message take the same path as
+    the ones received separately.*/
+    pub fn on_aggregate_data(
+        &mut self,
+        messages: &mut Vec<AggregateSubMessage>,
+    ) -> Result<(), SessionError> {
+        for message in messages {
+            match message.msg_type_id {
+                msg_type_id::AUDIO => self.on_audio_data(&mut message.data, &message.timestamp)?,
+                msg_type_id::VIDEO => self.on_video_data(&mut message.data, &message.timestamp)?,
+                msg_type_id::DATA_AMF0 => {
+                    self.on_meta_data(&mut message.data, &message.timestamp)?
+                }
+                _ => {
+                    tracing::trace!(
+                        "aggregate sub message type {} is ignored",
+                        message.msg_type_id
+                    );
+                }
+            }
+        }
+
+        Ok(())
+    }
+
     fn get_subscriber_info(&mut self, sub_id: Uuid) -> SubscriberInfo {
         match self.session_type {
             SessionType::Client => SubscriberInfo {
                 id: sub_id,
                 sub_type: SubscribeType::PublisherRtmp,
+                session_info: self.session_info.clone(),
+                start_mode: None,
             },
             SessionType::Server => SubscriberInfo {
                 id: sub_id,
                 sub_type: SubscribeType::PlayerRtmp,
+                session_info: self.session_info.clone(),
+                start_mode: self.start_mode,
             },
         }
     }
@@ -228,48 +479,98 @@ impl Common {
         stream_name: String,
         sub_id: Uuid,
     ) -> Result<(), SessionError> {
+        let info = self.get_subscriber_info(sub_id);
+        self.subscribe(app_name, stream_name, info).await
+    }
+
+    /*A player subscribing again after a pause or a seek starts at the latest
+    keyframe, not with the whole cache again.*/
+    pub async fn resubscribe_from_channels(
+        &mut self,
+        app_name: String,
+        stream_name: String,
+        sub_id: Uuid,
+    ) -> Result<(), SessionError> {
+        let mut info = self.get_subscriber_info(sub_id);
+        info.start_mode = Some(StartMode::LatestKeyframe);
+        self.continue_timestamps = true;
+        self.subscribe(app_name, stream_name, info).await
+    }
+
+    async fn subscribe(
+        &mut self,
+        app_name: String,
+        stream_name: String,
+        info: SubscriberInfo,
+    ) -> Result<(), SessionError> {
+        let receiver = self.send_subscribe_event(app_name, stream_name, info)?;
+        /*Waits until the stream is published or the channels give up.*/
+        self.on_subscribe_response(receiver.await)
+    }
+
+    /*A player waiting for the stream to be published keeps reading its
+    connection, the answer is passed to on_subscribe_response.*/
+    pub fn request_subscribe(
+        &mut self,
+        app_name: String,
+        stream_name: String,
+        sub_id: Uuid,
+    ) -> Result<SubscribeReceiver, SessionError> {
+        self.session_info.wait_token = CancellationToken::new();
+        let info = self.get_subscriber_info(sub_id);
+        self.send_subscribe_event(app_name, stream_name, info)
+    }
+
+    fn send_subscribe_event(
+        &mut self,
+        app_name: String,
+        stream_name: String,
+        info: SubscriberInfo,
+    ) -> Result<SubscribeReceiver, SessionError> {
         tracing::info!(
             "subscribe_from_channels, app_name: {} stream_name: {} subscribe_id: {}",
             app_name,
             stream_name.clone(),
-            sub_id
+            info.id
         );
 
-        let mut retry_count: u8 = 0;
+        let (sender, receiver) = oneshot::channel();
+
+        let subscribe_event = ChannelEvent::Subscribe {
+            app_name,
+            stream_name,
+            info,
+            responder: sender,
+        };
+        let rv = self.event_producer.send(subscribe_event);
 
-        loop {
-            let (sender, receiver) = oneshot::channel();
+        if rv.is_err() {
+            return Err(SessionError {
+                value: SessionErrorValue::ChannelEventSendErr,
+            });
+        }
 
-            let subscribe_event = ChannelEvent::Subscribe {
-                app_name: app_name.clone(),
-                stream_name: stream_name.clone(),
-                info: self.get_subscriber_info(sub_id),
-                responder: sender,
-            };
-            let rv = self.event_producer.send(subscribe_event);
+        Ok(receiver)
+    }
 
-            if rv.is_err() {
+    pub fn on_subscribe_response(
+        &mut self,
+        response: Result<Result<SubscriberDataConsumer, ChannelError>, oneshot::error::RecvError>,
+    ) -> Result<(), SessionError> {
+        match response {
+            Ok(Ok(consumer)) => {
+                self.data_consumer = consumer;
+            }
+            Ok(Err(err)) => {
                 return Err(SessionError {
-                    value: SessionErrorValue::ChannelEventSendErr,
+                    value: SessionErrorValue::ChannelError(err),
                 });
             }
-
-            match receiver.await {
-                Ok(consumer) => {
-                    self.data_consumer = consumer;
-                    break;
-                }
-                Err(_) => {
-                    if retry_count > 10 {
-                        return Err(SessionError {
-                            value: SessionErrorValue::SubscribeCountLimitReach,
-                        });
-                    }
-                }
+            Err(_) => {
+                return Err(SessionError {
+                    value: SessionErrorValue::ChannelEventSendErr,
+                });
             }
-
-            sleep(Duration::from_millis(800)).await;
-            retry_count += 1;
         }
 
         Ok(())
@@ -299,11 +600,16 @@ impl Common {
         &mut self,
         app_name: String,
         stream_name: String,
+        publisher_id: Uuid,
     ) -> Result<(), SessionError> {
         let (sender, receiver) = oneshot::channel();
         let publish_event = ChannelEvent::Publish {
             app_name,
             stream_name,
+            info: PublisherInfo {
+                id: publisher_id,
+                session_info: self.session_info.clone(),
+            },
             responder: sender,
         };
 
@@ -315,9 +621,14 @@ impl Common {
         }
 
         match receiver.await {
-            Ok(producer) => {
+            Ok(Ok(producer)) => {
                 self.data_producer = producer;
             }
+            Ok(Err(err)) => {
+                return Err(SessionError {
+                    value: SessionErrorValue::ChannelError(err),
+                });
+            }
             Err(err) => {
                 tracing::error!("publish_to_channels err{}\n", err);
             }
@@ -329,6 +640,7 @@ impl Common {
         &mut self,
         app_name: String,
         stream_name: String,
+        publisher_id: Uuid,
     ) -> Result<(), SessionError> {
         tracing::info!(
             "unpublish_to_channels, app_name:{}, stream_name:{}",
@@ -338,6 +650,7 @@ impl Common {
         let unpublish_event = ChannelEvent::UnPublish {
             app_name: app_name.clone(),
             stream_name: stream_name.clone(),
+            publisher_id,
         };
 
         let rv = self.event_producer.send(unpublish_event);
@@ -5,6 +5,10 @@ use std::fmt;
 pub const WINDOW_ACKNOWLEDGEMENT_SIZE: u32 = 4096;
 pub const PEER_BANDWIDTH: u32 = 4096;
 
+//the keepalive ping, a peer missing PING_MAX_MISSED pings in a row is disconnected
+pub const PING_INTERVAL_SECS: u64 = 30;
+pub const PING_MAX_MISSED: u32 = 3;
+
 pub mod peer_bandwidth_limit_type {
     pub const HARD: u8 = 0;
     pub const SOFT: u8 = 1;
@@ -22,6 +26,8 @@ pub const STREAM_ID: f64 = 1.0;
 
 pub const TRANSACTION_ID_CONNECT: u8 = 1;
 pub const TRANSACTION_ID_CREATE_STREAM: u8 = 2;
+pub const TRANSACTION_ID_RELEASE_STREAM: u8 = 3;
+pub const TRANSACTION_ID_FCPUBLISH: u8 = 4;
 
 //pub mod
 pub const RTMP_LEVEL_WARNING: &str = "warning";
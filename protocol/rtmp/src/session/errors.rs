@@ -1,6 +1,7 @@
 use {
     crate::{
         amf0::errors::Amf0WriteError,
+        channels::errors::ChannelError,
         chunk::errors::{PackError, UnpackError},
         handshake::errors::HandshakeError,
         messages::errors::MessageError,
@@ -47,6 +48,8 @@ pub enum SessionErrorValue {
     PackError(#[cause] PackError),
     #[fail(display = "handshake error: {}\n", _0)]
     HandshakeError(#[cause] HandshakeError),
+    #[fail(display = "channel error: {}\n", _0)]
+    ChannelError(ChannelError),
 
     #[fail(display = "amf0 count not correct error\n")]
     Amf0ValueCountNotCorrect,
@@ -67,6 +70,10 @@ pub enum SessionErrorValue {
     NoAppName,
     #[fail(display = "no media data can be received now.\n")]
     NoMediaDataReceived,
+    #[fail(display = "the peer has not answered {} pings.\n", _0)]
+    PeerNotResponding(u32),
+    #[fail(display = "the session is kicked.\n")]
+    Kicked,
 
     #[fail(display = "session is finished.")]
     Finish,
@@ -0,0 +1,175 @@
+use super::define::peer_bandwidth_limit_type;
+
+/*
+ Byte accounting for the acknowledgement window in both directions.
+ Sequence numbers are the byte counts modulo 2^32 as on the wire.
+*/
+pub struct FlowControl {
+    /* Bytes received so far and the count carried by the last Acknowledgement sent. */
+    received_bytes: u32,
+    last_ack_sent: u32,
+    /* Set by the peer's Window Acknowledgement Size, 0 means no acks are sent. */
+    ack_window_size: u32,
+    /* The Window Acknowledgement Size this side has announced. */
+    announced_window_size: u32,
+
+    /* Bytes sent so far and the sequence number of the last Acknowledgement received. */
+    sent_bytes: u32,
+    peer_acked: Option<u32>,
+    /* The output limit from the peer's Set Peer Bandwidth. */
+    peer_bandwidth: Option<u32>,
+    peer_limit_type: u8,
+}
+
+impl Default for FlowControl {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FlowControl {
+    pub fn new() -> Self {
+        Self {
+            received_bytes: 0,
+            last_ack_sent: 0,
+            ack_window_size: 0,
+            announced_window_size: 0,
+            sent_bytes: 0,
+            peer_acked: None,
+            peer_bandwidth: None,
+            peer_limit_type: peer_bandwidth_limit_type::HARD,
+        }
+    }
+
+    /* Returns the sequence number to acknowledge once a whole window was received. */
+    pub fn on_bytes_received(&mut self, len: usize) -> Option<u32> {
+        self.received_bytes = self.received_bytes.wrapping_add(len as u32);
+
+        if self.ack_window_size == 0 {
+            return None;
+        }
+
+        if self.received_bytes.wrapping_sub(self.last_ack_sent) >= self.ack_window_size {
+            self.last_ack_sent = self.received_bytes;
+            return Some(self.received_bytes);
+        }
+
+        None
+    }
+
+    pub fn set_ack_window_size(&mut self, size: u32) {
+        self.ack_window_size = size;
+    }
+
+    pub fn set_announced_window_size(&mut self, size: u32) {
+        self.announced_window_size = size;
+    }
+
+    pub fn announced_window_size(&self) -> u32 {
+        self.announced_window_size
+    }
+
+    pub fn on_bytes_sent(&mut self, len: u32) {
+        self.sent_bytes = self.sent_bytes.wrapping_add(len);
+    }
+
+    pub fn on_acknowledgement(&mut self, sequence_number: u32) {
+        self.peer_acked = Some(sequence_number);
+    }
+
+    /* Applies Set Peer Bandwidth and returns the window size to announce
+    back when it differs from the one announced last. */
+    pub fn on_set_peer_bandwidth(&mut self, window_size: u32, limit_type: u8) -> Option<u32> {
+        let limit_type = match limit_type {
+            /* Dynamic is treated as Hard if the previous limit was Hard, otherwise ignored. */
+            peer_bandwidth_limit_type::DYNAMIC => {
+                if self.peer_bandwidth.is_none()
+                    || self.peer_limit_type != peer_bandwidth_limit_type::HARD
+                {
+                    return None;
+                }
+                peer_bandwidth_limit_type::HARD
+            }
+            other => other,
+        };
+
+        let window_size = match (limit_type, self.peer_bandwidth) {
+            (peer_bandwidth_limit_type::SOFT, Some(current)) => current.min(window_size),
+            _ => window_size,
+        };
+
+        self.peer_bandwidth = Some(window_size);
+        self.peer_limit_type = limit_type;
+
+        if window_size != self.announced_window_size {
+            self.announced_window_size = window_size;
+            return Some(window_size);
+        }
+
+        None
+    }
+
+    /* Peers that never acknowledge are not throttled. */
+    pub fn is_window_exhausted(&self) -> bool {
+        match (self.peer_bandwidth, self.peer_acked) {
+            (Some(bandwidth), Some(acked)) => self.sent_bytes.wrapping_sub(acked) >= bandwidth,
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{peer_bandwidth_limit_type, FlowControl};
+
+    #[test]
+    fn test_ack_every_window() {
+        let mut flow_control = FlowControl::new();
+        assert_eq!(flow_control.on_bytes_received(5000), None);
+
+        flow_control.set_ack_window_size(4096);
+        assert_eq!(flow_control.on_bytes_received(10), Some(5010));
+        assert_eq!(flow_control.on_bytes_received(4000), None);
+        assert_eq!(flow_control.on_bytes_received(96), Some(9106));
+    }
+
+    #[test]
+    fn test_sequence_number_wraps() {
+        let mut flow_control = FlowControl::new();
+        flow_control.set_ack_window_size(4096);
+        flow_control.on_bytes_received(u32::MAX as usize);
+        assert_eq!(flow_control.on_bytes_received(4096), Some(4095));
+        assert_eq!(flow_control.on_bytes_received(4095), None);
+        assert_eq!(flow_control.on_bytes_received(1), Some(8191));
+    }
+
+    #[test]
+    fn test_peer_bandwidth() {
+        let mut flow_control = FlowControl::new();
+        flow_control.set_announced_window_size(4096);
+
+        assert_eq!(
+            flow_control.on_set_peer_bandwidth(4096, peer_bandwidth_limit_type::DYNAMIC),
+            None
+        );
+        assert_eq!(
+            flow_control.on_set_peer_bandwidth(8192, peer_bandwidth_limit_type::HARD),
+            Some(8192)
+        );
+        assert_eq!(
+            flow_control.on_set_peer_bandwidth(10000, peer_bandwidth_limit_type::SOFT),
+            None
+        );
+        assert_eq!(
+            flow_control.on_set_peer_bandwidth(2048, peer_bandwidth_limit_type::SOFT),
+            Some(2048)
+        );
+
+        flow_control.on_bytes_sent(4096);
+        assert!(!flow_control.is_window_exhausted());
+        flow_control.on_acknowledgement(1024);
+        assert!(flow_control.is_window_exhausted());
+        flow_control.on_acknowledgement(3000);
+        assert!(!flow_control.is_window_exhausted());
+    }
+}
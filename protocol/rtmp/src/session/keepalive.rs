@@ -0,0 +1,104 @@
+use {
+    std::time::Duration,
+    tokio::time::{interval_at, Instant, Interval},
+};
+
+/*
+ Sends a PingRequest every interval and measures the round trip time
+ from the PingResponse. A peer is considered dead when max_missed pings
+ in a row went by without anything received from it.
+*/
+pub struct KeepAlive {
+    /* A zero interval disables the pings. */
+    interval: Duration,
+    max_missed: u32,
+    timer: Option<Interval>,
+    /* The ping timestamps are milliseconds since the session started. */
+    epoch: Instant,
+    unanswered: u32,
+    pub rtt: Option<u32>,
+}
+
+impl KeepAlive {
+    pub fn new(interval: Duration, max_missed: u32) -> Self {
+        Self {
+            interval,
+            max_missed,
+            timer: None,
+            epoch: Instant::now(),
+            unanswered: 0,
+            rtt: None,
+        }
+    }
+
+    /* Completes when the next ping is due, never if pings are disabled. */
+    pub async fn tick(&mut self) {
+        if self.interval.is_zero() {
+            std::future::pending::<()>().await;
+        }
+
+        let interval = self.interval;
+        self.timer
+            .get_or_insert_with(|| interval_at(Instant::now() + interval, interval))
+            .tick()
+            .await;
+    }
+
+    /* Returns the timestamp of the PingRequest to send, or None if the
+    peer has not answered the last max_missed ones. */
+    pub fn next_ping(&mut self) -> Option<u32> {
+        if self.max_missed > 0 && self.unanswered >= self.max_missed {
+            return None;
+        }
+        self.unanswered += 1;
+        Some(self.elapsed_millis())
+    }
+
+    pub fn max_missed(&self) -> u32 {
+        self.max_missed
+    }
+
+    pub fn on_data_received(&mut self) {
+        self.unanswered = 0;
+    }
+
+    /* Returns the round trip time in milliseconds. */
+    pub fn on_ping_response(&mut self, timestamp: u32) -> u32 {
+        let rtt = self.elapsed_millis().wrapping_sub(timestamp);
+        self.rtt = Some(rtt);
+        self.unanswered = 0;
+        rtt
+    }
+
+    fn elapsed_millis(&self) -> u32 {
+        self.epoch.elapsed().as_millis() as u32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::KeepAlive, std::time::Duration};
+
+    #[test]
+    fn test_missed_pings() {
+        let mut keepalive = KeepAlive::new(Duration::from_secs(10), 2);
+        assert!(keepalive.next_ping().is_some());
+        keepalive.on_data_received();
+
+        assert!(keepalive.next_ping().is_some());
+        assert!(keepalive.next_ping().is_some());
+        assert!(keepalive.next_ping().is_none());
+    }
+
+    #[test]
+    fn test_rtt() {
+        let mut keepalive = KeepAlive::new(Duration::from_secs(10), 3);
+        let timestamp = keepalive.next_ping().unwrap();
+        std::thread::sleep(Duration::from_millis(20));
+
+        let rtt = keepalive.on_ping_response(timestamp);
+        assert!(rtt >= 20);
+        assert_eq!(keepalive.rtt, Some(rtt));
+        assert!(keepalive.next_ping().is_some());
+    }
+}
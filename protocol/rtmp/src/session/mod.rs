@@ -2,5 +2,7 @@
 pub mod define;
 pub mod errors;
 pub mod common;
+pub mod flow_control;
+pub mod keepalive;
 pub mod client_session;
 pub mod server_session;
@@ -6,10 +6,14 @@ use {
         define,
         define::SessionType,
         errors::{SessionError, SessionErrorValue},
+        keepalive::KeepAlive,
     },
     crate::{
         amf0::Amf0ValueType,
-        channels::define::ChannelEventProducer,
+        channels::{
+            define::{ChannelEventProducer, StartMode, SubscribeReceiver, SubscriberDataConsumer},
+            errors::{ChannelError, ChannelErrorValue},
+        },
         chunk::{
             define::CHUNK_SIZE,
             unpacketizer::{ChunkUnpacketizer, UnpackResult},
@@ -21,14 +25,19 @@ use {
         netstream::writer::NetStreamWriter,
         protocol_control_messages::writer::ProtocolControlMessagesWriter,
         user_control_messages::writer::EventMessagesWriter,
+        utils::query,
     },
     bytes::BytesMut,
-    bytesio::{bytes_writer::AsyncBytesWriter, bytesio::BytesIO},
+    bytesio::{
+        bytes_writer::AsyncBytesWriter,
+        bytesio::{BytesIO, NetStream},
+        bytesio_errors::BytesIOError,
+    },
     hyper::client,
     hyper_rustls::ConfigBuilderExt,
     serde_derive::Deserialize,
-    std::{collections::HashMap, sync::Arc, time::Duration},
-    tokio::{net::TcpStream, sync::Mutex},
+    std::{collections::HashMap, net::SocketAddr, sync::Arc, time::Duration},
+    tokio::sync::{oneshot::error::RecvError, Mutex},
     uuid::Uuid,
 };
 
@@ -46,6 +55,7 @@ enum ServerSessionState {
     // OnCreateStream,
     //Publish,
     DeleteStream,
+    WaitPublish,
     Play,
 }
 
@@ -58,6 +68,8 @@ pub struct ServerSession {
     state: ServerSessionState,
     pub common: Common,
     webhook_config: RtmpWebhookConfig,
+    /* The webhooks of the app names which have their own. */
+    app_webhook_configs: Arc<HashMap<String, RtmpWebhookConfig>>,
 
     bytesio_data: BytesMut,
     has_remaining_data: bool,
@@ -67,11 +79,26 @@ pub struct ServerSession {
     is called. */
     pub subscriber_id: Uuid,
     connect_command_object: Option<HashMap<String, Amf0ValueType>>,
+    /* The object encoding negotiated in connect, AMF0 or AMF3. */
+    pub object_encoding: f64,
+    keepalive: KeepAlive,
+    /* Set once the stream is published to the channels, so it is only
+    unpublished once, by FCUnpublish, deleteStream or a broken connection. */
+    is_publishing: bool,
+    /* Query parameters of the tcUrl and the app name from connect. */
+    connect_params: HashMap<String, String>,
+    /* The connect parameters merged with the ones of the publish or play
+    stream name, which take precedence. Sent to the webhooks. */
+    pub query_params: HashMap<String, String>,
+    /* The answer to the subscribe of a play command, awaited while the
+    player's messages keep being read, and the command's transaction id. */
+    subscribe_receiver: Option<SubscribeReceiver>,
+    play_transaction_id: f64,
 }
 
 impl ServerSession {
-    pub fn new(
-        stream: TcpStream,
+    pub fn new<S: NetStream + 'static>(
+        stream: S,
         event_producer: ChannelEventProducer,
         webhook_config: RtmpWebhookConfig,
     ) -> Self {
@@ -90,11 +117,66 @@ impl ServerSession {
             has_remaining_data: false,
 
             connect_command_object: None,
+            object_encoding: define::OBJENCODING_AMF0,
             webhook_config,
+            app_webhook_configs: Arc::new(HashMap::new()),
+            keepalive: KeepAlive::new(
+                Duration::from_secs(define::PING_INTERVAL_SECS),
+                define::PING_MAX_MISSED,
+            ),
+            is_publishing: false,
+            connect_params: HashMap::new(),
+            query_params: HashMap::new(),
+            subscribe_receiver: None,
+            play_transaction_id: 0.0,
         }
     }
 
+    pub fn set_remote_addr(&mut self, remote_addr: SocketAddr) {
+        self.common.session_info.remote_addr = Some(remote_addr);
+    }
+
+    pub fn set_app_webhook_configs(&mut self, configs: Arc<HashMap<String, RtmpWebhookConfig>>) {
+        self.app_webhook_configs = configs;
+    }
+
+    fn get_webhook_config(&self) -> RtmpWebhookConfig {
+        match self.app_webhook_configs.get(&self.app_name) {
+            Some(config) => config.clone(),
+            None => self.webhook_config.clone(),
+        }
+    }
+
+    /* A zero interval disables the keepalive pings. */
+    pub fn set_keepalive(&mut self, interval: Duration, max_missed: u32) {
+        self.keepalive = KeepAlive::new(interval, max_missed);
+    }
+
     pub async fn run(&mut self) -> Result<(), SessionError> {
+        let kick_token = self.common.session_info.kick_token.clone();
+
+        tokio::select! {
+            rv = self.process_states() => rv,
+            _ = kick_token.cancelled() => {
+                /*a subscriber kicked by its idle Transmitter is still known
+                to the channels*/
+                if self.is_playing() || self.is_waiting_publish() {
+                    self.common
+                        .unsubscribe_from_channels(
+                            self.app_name.clone(),
+                            self.stream_name.clone(),
+                            self.subscriber_id,
+                        )
+                        .await?;
+                }
+                Err(SessionError {
+                    value: SessionErrorValue::Kicked,
+                })
+            }
+        }
+    }
+
+    async fn process_states(&mut self) -> Result<(), SessionError> {
         loop {
             match self.state {
                 ServerSessionState::Handshake => {
@@ -103,6 +185,9 @@ impl ServerSession {
                 ServerSessionState::ReadChunk => {
                     self.read_parse_chunks().await?;
                 }
+                ServerSessionState::WaitPublish => {
+                    self.wait_publish().await?;
+                }
                 ServerSessionState::Play => {
                     self.play().await?;
                 }
@@ -132,6 +217,7 @@ impl ServerSession {
             if !left_bytes.is_empty() {
                 self.unpacketizer.extend_data(&left_bytes[..]);
                 self.has_remaining_data = true;
+                self.common.on_bytes_received(left_bytes.len()).await?;
             }
             tracing::info!("[ S->C ] [send_set_chunk_size] ");
             self.send_set_chunk_size().await?;
@@ -143,28 +229,35 @@ impl ServerSession {
 
     async fn read_parse_chunks(&mut self) -> Result<(), SessionError> {
         if !self.has_remaining_data {
-            match self
-                .io
-                .lock()
-                .await
-                .read_timeout(Duration::from_secs(2))
-                .await
-            {
-                Ok(data) => {
+            let io = Arc::clone(&self.io);
+
+            let result = tokio::select! {
+                data = async { io.lock().await.read_timeout(Duration::from_secs(2)).await } => {
+                    data.map(Some).map_err(|err| SessionError {
+                        value: SessionErrorValue::BytesIOError(err),
+                    })
+                }
+                _ = self.keepalive.tick() => self.on_ping_timer().await.map(|_| None),
+            };
+
+            match result {
+                Ok(Some(data)) => {
                     self.bytesio_data = data;
                 }
+                Ok(None) => {
+                    return Ok(());
+                }
                 Err(err) => {
-                    self.common
-                        .unpublish_to_channels(self.app_name.clone(), self.stream_name.clone())
-                        .await?;
-
-                    return Err(SessionError {
-                        value: SessionErrorValue::BytesIOError(err),
-                    });
+                    self.unpublish().await?;
+                    return Err(err);
                 }
             }
 
+            self.keepalive.on_data_received();
             self.unpacketizer.extend_data(&self.bytesio_data[..]);
+            self.common
+                .on_bytes_received(self.bytesio_data.len())
+                .await?;
         }
 
         self.has_remaining_data = false;
@@ -190,25 +283,210 @@ impl ServerSession {
         Ok(())
     }
 
+    /* Sends the media to the player and keeps reading its messages,
+    acknowledgements included, in between. */
     async fn play(&mut self) -> Result<(), SessionError> {
-        match self.common.send_channel_data().await {
-            Ok(_) => {}
+        let io = Arc::clone(&self.io);
+
+        let result = tokio::select! {
+            data = self.common.recv_channel_data() => match data {
+                Ok(data) => self.common.send_channel_data(data).await,
+                Err(err) => match self.send_play_end_status().await {
+                    Ok(_) => Err(err),
+                    Err(notify_err) => Err(notify_err),
+                },
+            },
+            data = async { io.lock().await.read().await } => self.on_player_data(data).await,
+            _ = self.keepalive.tick() => self.on_ping_timer().await,
+        };
 
-            Err(err) => {
-                self.common
-                    .unsubscribe_from_channels(
-                        self.app_name.clone(),
-                        self.stream_name.clone(),
-                        self.subscriber_id,
+        if let Err(err) = result {
+            self.common
+                .unsubscribe_from_channels(
+                    self.app_name.clone(),
+                    self.stream_name.clone(),
+                    self.subscriber_id,
+                )
+                .await?;
+            return Err(err);
+        }
+
+        Ok(())
+    }
+
+    /* Waits for the stream of a play command to be published, reading the
+    player's messages, pings and closeStream included, in between. */
+    async fn wait_publish(&mut self) -> Result<(), SessionError> {
+        let mut receiver = match self.subscribe_receiver.take() {
+            Some(receiver) => receiver,
+            None => {
+                self.state = ServerSessionState::ReadChunk;
+                return Ok(());
+            }
+        };
+        let io = Arc::clone(&self.io);
+
+        let result = tokio::select! {
+            response = &mut receiver => {
+                return self.on_play_subscribed(response).await;
+            }
+            data = async { io.lock().await.read().await } => self.on_player_data(data).await,
+            _ = self.keepalive.tick() => self.on_ping_timer().await,
+        };
+
+        if let Err(err) = result {
+            self.common
+                .unsubscribe_from_channels(
+                    self.app_name.clone(),
+                    self.stream_name.clone(),
+                    self.subscriber_id,
+                )
+                .await?;
+            return Err(err);
+        }
+
+        /*the wait is over once the player closed or deleted the stream*/
+        if self.is_waiting_publish() {
+            self.subscribe_receiver = Some(receiver);
+        }
+
+        Ok(())
+    }
+
+    /* Starts the play once the stream is published, the player is told
+    so only if it had to wait. */
+    async fn on_play_subscribed(
+        &mut self,
+        response: Result<Result<SubscriberDataConsumer, ChannelError>, RecvError>,
+    ) -> Result<(), SessionError> {
+        let transaction_id = self.play_transaction_id;
+        let mut netstream = NetStreamWriter::new(Arc::clone(&self.io));
+
+        match self.common.on_subscribe_response(response) {
+            Ok(_) => {}
+            Err(SessionError {
+                value:
+                    SessionErrorValue::ChannelError(ChannelError {
+                        value: ChannelErrorValue::NoAppOrStreamName,
+                    }),
+            }) => {
+                tracing::info!(
+                    "[ S->C ] [NetStream.Play.StreamNotFound]  app_name: {}, stream_name: {}",
+                    self.app_name,
+                    self.stream_name
+                );
+                netstream
+                    .write_on_status(
+                        &transaction_id,
+                        "error",
+                        "NetStream.Play.StreamNotFound",
+                        "stream is not published",
                     )
                     .await?;
+                self.state = ServerSessionState::DeleteStream;
+                return Ok(());
+            }
+            Err(err) => {
                 return Err(err);
             }
         }
 
+        if self.common.session_info.wait_token.is_cancelled() {
+            netstream
+                .write_on_status(
+                    &transaction_id,
+                    "status",
+                    "NetStream.Play.PublishNotify",
+                    "play publish notify.",
+                )
+                .await?;
+        }
+
+        self.state = ServerSessionState::Play;
+
+        Ok(())
+    }
+
+    async fn on_player_data(
+        &mut self,
+        data: Result<BytesMut, BytesIOError>,
+    ) -> Result<(), SessionError> {
+        match data {
+            Ok(data) => {
+                self.bytesio_data = data;
+                self.keepalive.on_data_received();
+                self.unpacketizer.extend_data(&self.bytesio_data[..]);
+                self.has_remaining_data = true;
+                self.common
+                    .on_bytes_received(self.bytesio_data.len())
+                    .await?;
+                self.read_parse_chunks().await
+            }
+            Err(err) => Err(SessionError {
+                value: SessionErrorValue::BytesIOError(err),
+            }),
+        }
+    }
+
+    /*The channel is closed once the stream is unpublished, or when the
+    player is dropped for lagging behind.*/
+    async fn send_play_end_status(&mut self) -> Result<(), SessionError> {
+        let (level, code, description) = if self.common.session_info.lag_token.is_cancelled() {
+            (
+                "error",
+                "NetStream.Play.Failed",
+                "the player lags behind the stream.",
+            )
+        } else {
+            (
+                "status",
+                "NetStream.Play.UnpublishNotify",
+                "stream is unpublished.",
+            )
+        };
+
+        let mut netstream = NetStreamWriter::new(Arc::clone(&self.io));
+        netstream
+            .write_on_status(&0.0, level, code, description)
+            .await?;
+        tracing::info!(
+            "[ S->C ] [{}]  app_name: {}, stream_name: {}",
+            code,
+            self.app_name,
+            self.stream_name
+        );
+
         Ok(())
     }
 
+    async fn on_ping_timer(&mut self) -> Result<(), SessionError> {
+        match self.keepalive.next_ping() {
+            Some(timestamp) => {
+                tracing::trace!("[ S->C ] [ping request] timestamp: {}", timestamp);
+                self.common.send_ping_request(timestamp).await
+            }
+            None => Err(SessionError {
+                value: SessionErrorValue::PeerNotResponding(self.keepalive.max_missed()),
+            }),
+        }
+    }
+
+    fn on_ping_response(&mut self, timestamp: u32) {
+        let rtt = self.keepalive.on_ping_response(timestamp);
+        tracing::trace!("[ S<-C ] [ping response] rtt: {}ms", rtt);
+
+        if !self.stream_name.is_empty() {
+            let is_publisher = self.is_publishing;
+            self.common.report_rtt(
+                self.app_name.clone(),
+                self.stream_name.clone(),
+                self.subscriber_id,
+                is_publisher,
+                rtt,
+            );
+        }
+    }
+
     pub async fn send_set_chunk_size(&mut self) -> Result<(), SessionError> {
         let mut controlmessage =
             ProtocolControlMessagesWriter::new(AsyncBytesWriter::new(self.io.clone()));
@@ -242,6 +520,34 @@ impl ServerSession {
             RtmpMessageData::SetChunkSize { chunk_size } => {
                 self.on_set_chunk_size(*chunk_size as usize)?;
             }
+            RtmpMessageData::Acknowledgement { sequence_number } => {
+                self.common.on_acknowledgement(*sequence_number);
+            }
+            RtmpMessageData::WindowAcknowledgementSize { size } => {
+                self.common.on_window_acknowledgement_size(*size);
+            }
+            RtmpMessageData::SetPeerBandwidth { properties } => {
+                self.common.on_set_peer_bandwidth(properties).await?;
+            }
+            RtmpMessageData::PingRequest { timestamp } => {
+                self.common.on_ping_request(*timestamp).await?;
+            }
+            RtmpMessageData::PingResponse { timestamp } => {
+                self.on_ping_response(*timestamp);
+            }
+            /*encoders may still send media between FCUnpublish and deleteStream*/
+            RtmpMessageData::AudioData { .. }
+            | RtmpMessageData::VideoData { .. }
+            | RtmpMessageData::AmfData { .. }
+            | RtmpMessageData::AggregateData { .. }
+                if !self.is_publishing =>
+            {
+                tracing::trace!(
+                    "[ S<-C ] media of an unpublished stream is dropped, app_name: {}, stream_name: {}",
+                    self.app_name,
+                    self.stream_name
+                );
+            }
             RtmpMessageData::AudioData { data } => {
                 self.common.on_audio_data(data, timestamp)?;
             }
@@ -251,6 +557,16 @@ impl ServerSession {
             RtmpMessageData::AmfData { raw_data } => {
                 self.common.on_meta_data(raw_data, timestamp)?;
             }
+            RtmpMessageData::AggregateData { messages } => {
+                self.common.on_aggregate_data(messages)?;
+            }
+            RtmpMessageData::SharedObject { name, .. } => {
+                tracing::info!(
+                    "[ S<-C ] [shared object] app_name: {}, name: {}, not supported, ignored",
+                    self.app_name,
+                    name
+                );
+            }
 
             _ => {}
         }
@@ -278,7 +594,7 @@ impl ServerSession {
 
         let empty_cmd_obj: HashMap<String, Amf0ValueType> = HashMap::new();
         let obj = match command_object {
-            Amf0ValueType::Object(obj) => obj,
+            Amf0ValueType::Object(obj) | Amf0ValueType::EcmaArray(obj) => obj,
             _ => &empty_cmd_obj,
         };
 
@@ -304,10 +620,19 @@ impl ServerSession {
                         self.stream_name
                     );
 
+                    self.close_play().await?;
                     self.on_delete_stream(transaction_id, &stream_id).await?;
                     self.state = ServerSessionState::DeleteStream;
                 }
             }
+            "closeStream" => {
+                tracing::info!(
+                    "[ S<-C ] [close stream] app_name: {}, stream_name: {}",
+                    self.app_name,
+                    self.stream_name
+                );
+                self.close_play().await?;
+            }
             "play" => {
                 tracing::info!(
                     "[ S<-C ] [play]  app_name: {}, stream_name: {}",
@@ -321,7 +646,69 @@ impl ServerSession {
                 self.unpacketizer.session_type = config::SERVER_PUSH;
                 self.on_publish(transaction_id, stream_id, others).await?;
             }
-            _ => {}
+            "releaseStream" => {
+                tracing::info!("[ S<-C ] [release stream]  app_name: {}", self.app_name);
+                self.on_release_stream(transaction_id).await?;
+            }
+            "FCPublish" => {
+                tracing::info!("[ S<-C ] [FCPublish]  app_name: {}", self.app_name);
+                self.on_fcpublish(transaction_id, others).await?;
+            }
+            "FCUnpublish" => {
+                tracing::info!(
+                    "[ S<-C ] [FCUnpublish]  app_name: {}, stream_name: {}",
+                    self.app_name,
+                    self.stream_name
+                );
+                self.on_fcunpublish(transaction_id, others).await?;
+            }
+            "getStreamLength" => {
+                tracing::info!("[ S<-C ] [get stream length]  app_name: {}", self.app_name);
+                self.on_get_stream_length(transaction_id).await?;
+            }
+            "checkBandwidth" | "_checkbw" => {
+                tracing::info!("[ S<-C ] [check bandwidth]  app_name: {}", self.app_name);
+                self.on_check_bandwidth(transaction_id).await?;
+            }
+            "pause" => {
+                tracing::info!(
+                    "[ S<-C ] [pause]  app_name: {}, stream_name: {}",
+                    self.app_name,
+                    self.stream_name
+                );
+                self.on_pause(transaction_id, stream_id, others).await?;
+            }
+            "seek" => {
+                tracing::info!(
+                    "[ S<-C ] [seek]  app_name: {}, stream_name: {}",
+                    self.app_name,
+                    self.stream_name
+                );
+                self.on_seek(transaction_id).await?;
+            }
+            "receiveAudio" | "receiveVideo" => {
+                let enabled = !matches!(others.first(), Some(Amf0ValueType::Boolean(false)));
+                tracing::info!(
+                    "[ S<-C ] [{}]  app_name: {}, stream_name: {}, enabled: {}",
+                    cmd_name,
+                    self.app_name,
+                    self.stream_name,
+                    enabled
+                );
+                if cmd_name == "receiveAudio" {
+                    self.common.set_receive_audio(enabled);
+                } else {
+                    self.common.set_receive_video(enabled);
+                }
+                /*Only enabling a track is answered, the player flushes its
+                buffer and the stream restarts from the latest keyframe.*/
+                if enabled {
+                    self.on_seek(transaction_id).await?;
+                }
+            }
+            _ => {
+                tracing::trace!("[ S<-C ] [{}] command is ignored", cmd_name);
+            }
         }
 
         Ok(())
@@ -344,13 +731,14 @@ impl ServerSession {
         command_obj: &HashMap<String, Amf0ValueType>,
     ) -> Result<(), SessionError> {
         self.connect_command_object = Some(command_obj.clone());
-        let mut control_message =
-            ProtocolControlMessagesWriter::new(AsyncBytesWriter::new(self.io.clone()));
         tracing::info!("[ S->C ] [set window_acknowledgement_size]");
-        control_message
-            .write_window_acknowledgement_size(define::WINDOW_ACKNOWLEDGEMENT_SIZE)
+        self.common
+            .send_window_acknowledgement_size(define::WINDOW_ACKNOWLEDGEMENT_SIZE)
             .await?;
 
+        let mut control_message =
+            ProtocolControlMessagesWriter::new(AsyncBytesWriter::new(self.io.clone()));
+
         tracing::info!("[ S->C ] [set set_peer_bandwidth]",);
         control_message
             .write_set_peer_bandwidth(
@@ -359,15 +747,32 @@ impl ServerSession {
             )
             .await?;
 
+        /*Only AMF0 and AMF3 are defined, the other values fall back to AMF0.
+        The commands are always answered in AMF0, which AMF3 clients accept.*/
         let obj_encoding = command_obj.get("objectEncoding");
-        let encoding = match obj_encoding {
-            Some(Amf0ValueType::Number(encoding)) => encoding,
-            _ => &define::OBJENCODING_AMF0,
+        self.object_encoding = match obj_encoding {
+            Some(Amf0ValueType::Number(encoding)) if *encoding == define::OBJENCODING_AMF3 => {
+                define::OBJENCODING_AMF3
+            }
+            _ => define::OBJENCODING_AMF0,
         };
 
+        if let Some(Amf0ValueType::UTF8String(tc_url)) = command_obj.get("tcUrl") {
+            self.connect_params = query::parse_url_query(tc_url);
+            self.common.session_info.tc_url = Some(tc_url.clone());
+        }
+        if let Some(Amf0ValueType::UTF8String(flash_ver)) = command_obj.get("flashVer") {
+            self.common.session_info.flash_ver = Some(flash_ver.clone());
+        }
+
+        /*Some encoders append the query to the app name instead.*/
         let app_name = command_obj.get("app");
         self.app_name = match app_name {
-            Some(Amf0ValueType::UTF8String(app)) => app.clone(),
+            Some(Amf0ValueType::UTF8String(app)) => {
+                let (app_name, params) = query::split_stream_name(app);
+                self.connect_params.extend(params);
+                app_name
+            }
             _ => {
                 return Err(SessionError {
                     value: SessionErrorValue::NoAppName,
@@ -385,7 +790,7 @@ impl ServerSession {
                 &String::from("NetConnection.Connect.Success"),
                 define::LEVEL,
                 &String::from("Connection Succeeded."),
-                encoding,
+                &self.object_encoding,
             )
             .await?;
 
@@ -406,14 +811,104 @@ impl ServerSession {
         Ok(())
     }
 
+    /*The stream name is the first value after the command object.*/
+    fn get_command_stream_name(other_values: &[Amf0ValueType]) -> String {
+        match other_values.first() {
+            Some(Amf0ValueType::UTF8String(stream_name)) => stream_name.clone(),
+            _ => String::new(),
+        }
+    }
+
+    pub async fn on_release_stream(&mut self, transaction_id: &f64) -> Result<(), SessionError> {
+        let mut netconnection = NetConnection::new(Arc::clone(&self.io));
+        netconnection
+            .write_result(transaction_id, &Amf0ValueType::Undefined)
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn on_fcpublish(
+        &mut self,
+        transaction_id: &f64,
+        other_values: &[Amf0ValueType],
+    ) -> Result<(), SessionError> {
+        let stream_name = Self::get_command_stream_name(other_values);
+
+        let mut netstream = NetStreamWriter::new(Arc::clone(&self.io));
+        netstream.write_on_fcpublish(&stream_name).await?;
+
+        let mut netconnection = NetConnection::new(Arc::clone(&self.io));
+        netconnection
+            .write_result(transaction_id, &Amf0ValueType::Undefined)
+            .await?;
+
+        Ok(())
+    }
+
+    /*The encoder is about to stop, unpublish right away instead of
+    waiting for deleteStream or the connection to be closed.*/
+    pub async fn on_fcunpublish(
+        &mut self,
+        transaction_id: &f64,
+        other_values: &[Amf0ValueType],
+    ) -> Result<(), SessionError> {
+        let stream_name = Self::get_command_stream_name(other_values);
+
+        let mut netstream = NetStreamWriter::new(Arc::clone(&self.io));
+        netstream.write_on_fcunpublish(&stream_name).await?;
+
+        let mut netconnection = NetConnection::new(Arc::clone(&self.io));
+        netconnection
+            .write_result(transaction_id, &Amf0ValueType::Undefined)
+            .await?;
+
+        self.unpublish().await
+    }
+
+    /*Live streams have no length.*/
+    pub async fn on_get_stream_length(&mut self, transaction_id: &f64) -> Result<(), SessionError> {
+        let mut netconnection = NetConnection::new(Arc::clone(&self.io));
+        netconnection
+            .write_result(transaction_id, &Amf0ValueType::Number(0.0))
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn on_check_bandwidth(&mut self, transaction_id: &f64) -> Result<(), SessionError> {
+        let mut netconnection = NetConnection::new(Arc::clone(&self.io));
+        netconnection
+            .write_result(transaction_id, &Amf0ValueType::Undefined)
+            .await?;
+
+        let mut netstream = NetStreamWriter::new(Arc::clone(&self.io));
+        netstream.write_on_bw_done(&0.0, &0.0).await?;
+
+        Ok(())
+    }
+
+    async fn unpublish(&mut self) -> Result<(), SessionError> {
+        if !self.is_publishing {
+            return Ok(());
+        }
+        self.is_publishing = false;
+
+        self.common
+            .unpublish_to_channels(
+                self.app_name.clone(),
+                self.stream_name.clone(),
+                self.subscriber_id,
+            )
+            .await
+    }
+
     pub async fn on_delete_stream(
         &mut self,
         transaction_id: &f64,
         stream_id: &f64,
     ) -> Result<(), SessionError> {
-        self.common
-            .unpublish_to_channels(self.app_name.clone(), self.stream_name.clone())
-            .await?;
+        self.unpublish().await?;
 
         let mut netstream = NetStreamWriter::new(Arc::clone(&self.io));
         netstream
@@ -425,7 +920,7 @@ impl ServerSession {
             )
             .await?;
 
-        let webhook_config = self.webhook_config.clone();
+        let webhook_config = self.get_webhook_config();
         if webhook_config.enabled {
             let status = self.auth(&webhook_config.publish_done_url).await;
             if !status.is_success() {
@@ -449,6 +944,15 @@ impl ServerSession {
         Ok(())
     }
 
+    /*Keeps the clean stream name for the channels and collects the query
+    parameters of the raw one.*/
+    fn set_stream_name(&mut self, raw_stream_name: &str) {
+        let (stream_name, params) = query::split_stream_name(raw_stream_name);
+        self.stream_name = stream_name;
+        self.query_params = self.connect_params.clone();
+        self.query_params.extend(params);
+    }
+
     #[allow(clippy::never_loop)]
     pub async fn on_play(
         &mut self,
@@ -502,6 +1006,12 @@ impl ServerSession {
             };
         }
 
+        self.set_stream_name(&stream_name.unwrap_or_default());
+        self.common.start_mode = self
+            .query_params
+            .get("start_mode")
+            .and_then(|value| StartMode::parse(value));
+
         let mut event_messages = EventMessagesWriter::new(AsyncBytesWriter::new(self.io.clone()));
         event_messages.write_stream_begin(*stream_id).await?;
         tracing::info!(
@@ -539,31 +1049,21 @@ impl ServerSession {
             )
             .await?;
 
-        netstream
-            .write_on_status(
-                transaction_id,
-                "status",
-                "NetStream.Play.PublishNotify",
-                "play publish notify.",
-            )
-            .await?;
-
         event_messages.write_stream_is_record(*stream_id).await?;
         tracing::info!(
             "[ S->C ] [stream is record]  app_name: {}, stream_name: {}",
             self.app_name,
             self.stream_name
         );
-        self.stream_name = stream_name.clone().unwrap();
-        self.common
-            .subscribe_from_channels(
-                self.app_name.clone(),
-                stream_name.unwrap(),
-                self.subscriber_id,
-            )
-            .await?;
 
-        self.state = ServerSessionState::Play;
+        /*A stream which is not published yet is waited for.*/
+        self.subscribe_receiver = Some(self.common.request_subscribe(
+            self.app_name.clone(),
+            self.stream_name.clone(),
+            self.subscriber_id,
+        )?);
+        self.play_transaction_id = *transaction_id;
+        self.state = ServerSessionState::WaitPublish;
 
         Ok(())
     }
@@ -592,9 +1092,14 @@ impl ServerSession {
             .method(Method::POST)
             .uri(url)
             .header("Content-Type", "application/json")
-            .body(Body::from(format!(
-                r#"{{"app_name":"{app_name}","stream_key":"{stream_key}"}}"#
-            )))
+            .body(Body::from(
+                serde_json::json!({
+                    "app_name": app_name,
+                    "stream_key": stream_key,
+                    "params": self.query_params,
+                })
+                .to_string(),
+            ))
             .expect("request body");
 
         let future = client.request(req);
@@ -635,7 +1140,7 @@ impl ServerSession {
             }
         };
 
-        self.stream_name = stream_name;
+        self.set_stream_name(&stream_name);
 
         let _ = match other_values.remove(0) {
             Amf0ValueType::UTF8String(val) => val,
@@ -652,6 +1157,52 @@ impl ServerSession {
             self.stream_name
         );
 
+        if self.stream_name.is_empty() {
+            return self
+                .reject_publish(
+                    transaction_id,
+                    "NetStream.Publish.BadName",
+                    "no stream name",
+                )
+                .await;
+        }
+
+        /*Nothing is reported to the encoder before the publish is authorized.*/
+        let webhook_config = self.get_webhook_config();
+        if webhook_config.enabled {
+            let status = self.auth(&webhook_config.publish_url).await;
+            if !status.is_success() {
+                return self
+                    .reject_publish(
+                        transaction_id,
+                        "NetStream.Publish.Denied",
+                        "publish is not authorized",
+                    )
+                    .await;
+            }
+        }
+
+        match self
+            .common
+            .publish_to_channels(
+                self.app_name.clone(),
+                self.stream_name.clone(),
+                self.subscriber_id,
+            )
+            .await
+        {
+            Ok(_) => {}
+            Err(err) => {
+                tracing::error!("publish to channels error: {}", err);
+                let (code, description) = match &err.value {
+                    SessionErrorValue::ChannelError(err) => Self::publish_rejection(&err.value),
+                    _ => ("NetStream.Publish.Denied", "publish failed"),
+                };
+                return self.reject_publish(transaction_id, code, description).await;
+            }
+        }
+        self.is_publishing = true;
+
         tracing::info!(
             "[ S->C ] [stream begin]  app_name: {}, stream_name: {}",
             self.app_name,
@@ -671,29 +1222,454 @@ impl ServerSession {
             self.stream_name
         );
 
-        let webhook_config = self.webhook_config.clone();
-        if webhook_config.enabled {
-            let status = self.auth(&webhook_config.publish_url).await;
-            if !status.is_success() {
-                tracing::info!(
-                    "[ S->C ] [publish failed]  app_name: {}, stream_name: {}",
-                    self.app_name,
-                    self.stream_name
-                );
-                return Err(SessionError {
-                    value: SessionErrorValue::Finish,
-                });
+        Ok(())
+    }
+
+    fn is_playing(&self) -> bool {
+        matches!(self.state, ServerSessionState::Play)
+    }
+
+    fn is_waiting_publish(&self) -> bool {
+        matches!(self.state, ServerSessionState::WaitPublish)
+    }
+
+    /* closeStream and deleteStream end the play, or the wait for the stream
+    to be published. */
+    async fn close_play(&mut self) -> Result<(), SessionError> {
+        if !self.is_playing() && !self.is_waiting_publish() {
+            return Ok(());
+        }
+        self.subscribe_receiver = None;
+        self.state = ServerSessionState::ReadChunk;
+
+        self.common
+            .unsubscribe_from_channels(
+                self.app_name.clone(),
+                self.stream_name.clone(),
+                self.subscriber_id,
+            )
+            .await
+    }
+
+    /*Subscribes again, so the Transmitter resends the metadata, the
+    sequence headers and the newest cached gop.*/
+    async fn restart_from_cache(&mut self) -> Result<(), SessionError> {
+        self.common
+            .unsubscribe_from_channels(
+                self.app_name.clone(),
+                self.stream_name.clone(),
+                self.subscriber_id,
+            )
+            .await?;
+        self.common
+            .resubscribe_from_channels(
+                self.app_name.clone(),
+                self.stream_name.clone(),
+                self.subscriber_id,
+            )
+            .await
+    }
+
+    /*A live stream cannot be paused, delivery stops and resumes at the
+    latest keyframe.*/
+    pub async fn on_pause(
+        &mut self,
+        transaction_id: &f64,
+        stream_id: &u32,
+        other_values: &[Amf0ValueType],
+    ) -> Result<(), SessionError> {
+        if !self.is_playing() {
+            return Ok(());
+        }
+
+        let pause = !matches!(other_values.first(), Some(Amf0ValueType::Boolean(false)));
+        if pause == self.common.is_paused() {
+            return Ok(());
+        }
+
+        let mut event_messages = EventMessagesWriter::new(AsyncBytesWriter::new(self.io.clone()));
+        let mut netstream = NetStreamWriter::new(Arc::clone(&self.io));
+
+        if pause {
+            self.common.set_paused(true);
+            self.common
+                .unsubscribe_from_channels(
+                    self.app_name.clone(),
+                    self.stream_name.clone(),
+                    self.subscriber_id,
+                )
+                .await?;
+
+            event_messages.write_stream_eof(*stream_id).await?;
+            netstream
+                .write_on_status(
+                    transaction_id,
+                    "status",
+                    "NetStream.Pause.Notify",
+                    "Paused live",
+                )
+                .await?;
+        } else {
+            self.common
+                .resubscribe_from_channels(
+                    self.app_name.clone(),
+                    self.stream_name.clone(),
+                    self.subscriber_id,
+                )
+                .await?;
+            self.common.set_paused(false);
+
+            event_messages.write_stream_begin(*stream_id).await?;
+            netstream
+                .write_on_status(
+                    transaction_id,
+                    "status",
+                    "NetStream.Unpause.Notify",
+                    "Unpaused live",
+                )
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    /*Seeking in a live stream restarts it from the latest keyframe.*/
+    pub async fn on_seek(&mut self, transaction_id: &f64) -> Result<(), SessionError> {
+        if !self.is_playing() {
+            return Ok(());
+        }
+
+        if !self.common.is_paused() {
+            self.restart_from_cache().await?;
+        }
+
+        let mut netstream = NetStreamWriter::new(Arc::clone(&self.io));
+        netstream
+            .write_on_status(
+                transaction_id,
+                "status",
+                "NetStream.Seek.Notify",
+                "Seeking live stream",
+            )
+            .await?;
+        netstream
+            .write_on_status(
+                transaction_id,
+                "status",
+                "NetStream.Play.Start",
+                "play start",
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    /*Answers a refused publish with an error status and ends the session.*/
+    /* Name errors and conflicts are a bad name, limits and the other errors
+    a denied publish. */
+    fn publish_rejection(value: &ChannelErrorValue) -> (&'static str, &'static str) {
+        match value {
+            ChannelErrorValue::Exists => {
+                ("NetStream.Publish.BadName", "stream is already publishing")
             }
+            ChannelErrorValue::NoAppName
+            | ChannelErrorValue::NoStreamName
+            | ChannelErrorValue::NoAppOrStreamName => {
+                ("NetStream.Publish.BadName", "no app or stream name")
+            }
+            ChannelErrorValue::TooManyPublishers => {
+                ("NetStream.Publish.Denied", "too many publishers of the app")
+            }
+            _ => ("NetStream.Publish.Denied", "publish failed"),
         }
+    }
+
+    async fn reject_publish(
+        &mut self,
+        transaction_id: &f64,
+        code: &str,
+        description: &str,
+    ) -> Result<(), SessionError> {
         tracing::info!(
-            "[ S->C ] [publish success]  app_name: {}, stream_name: {}",
+            "[ S->C ] [{}]  app_name: {}, stream_name: {}, {}",
+            code,
             self.app_name,
-            self.stream_name
+            self.stream_name,
+            description
         );
-        self.common
-            .publish_to_channels(self.app_name.clone(), self.stream_name.clone())
+
+        let mut netstream = NetStreamWriter::new(Arc::clone(&self.io));
+        netstream
+            .write_on_status(transaction_id, "error", code, description)
             .await?;
 
+        self.state = ServerSessionState::DeleteStream;
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{RtmpWebhookConfig, ServerSession, ServerSessionState},
+        crate::{
+            amf0::Amf0ValueType,
+            channels::{
+                define::{AppProfile, ChannelData, ChannelEvent},
+                ChannelsManager,
+            },
+            messages::define::RtmpMessageData,
+            netstream::writer::NetStreamWriter,
+            session::common::{PublisherInfo, SessionInfo},
+        },
+        bytes::{Bytes, BytesMut},
+        bytesio::bytesio::BytesIO,
+        std::{sync::Arc, time::Duration},
+        tokio::{
+            io::DuplexStream,
+            sync::{oneshot, Mutex},
+            time::timeout,
+        },
+        uuid::Uuid,
+    };
+
+    fn new_session(stream: DuplexStream, manager: &mut ChannelsManager) -> ServerSession {
+        let webhook_config = RtmpWebhookConfig {
+            enabled: false,
+            publish_url: String::new(),
+            publish_done_url: String::new(),
+        };
+        let mut session =
+            ServerSession::new(stream, manager.get_channel_event_producer(), webhook_config);
+        session.app_name = String::from("live");
+        session
+    }
+
+    async fn play(session: &mut ServerSession, stream_name: &str) {
+        let mut values = vec![Amf0ValueType::UTF8String(String::from(stream_name))];
+        session.on_play(&1.0, &1, &mut values).await.unwrap();
+        assert!(session.is_waiting_publish());
+    }
+
+    /* What the session wrote to the player so far. */
+    async fn read_player(player: &Arc<Mutex<BytesIO>>) -> String {
+        let mut data = Vec::new();
+        while let Ok(Ok(bytes)) = timeout(Duration::from_millis(200), async {
+            player.lock().await.read().await
+        })
+        .await
+        {
+            data.extend_from_slice(&bytes[..]);
+        }
+        String::from_utf8_lossy(&data[..]).into_owned()
+    }
+
+    /* The timestamps of the frames the player gets until none comes. */
+    async fn recv_timestamps(session: &mut ServerSession) -> Vec<u32> {
+        let mut timestamps = Vec::new();
+        while let Ok(Ok(data)) = timeout(
+            Duration::from_millis(200),
+            session.common.recv_channel_data(),
+        )
+        .await
+        {
+            timestamps.push(data.timestamp());
+        }
+        timestamps
+    }
+
+    #[tokio::test]
+    async fn test_pause_and_seek() {
+        let mut manager = ChannelsManager::new();
+        manager.set_rtmp_gop_num(2);
+        let (app_name, stream_name) = (String::from("live"), String::from("test"));
+        let publisher = PublisherInfo {
+            id: Uuid::new_v4(),
+            session_info: SessionInfo::new(None),
+        };
+        let producer = manager.publish(&app_name, &stream_name, publisher).unwrap();
+        let event_producer = manager.get_channel_event_producer();
+        tokio::spawn(async move { manager.run().await });
+
+        /*two gops of ten frames, from 10s on*/
+        for idx in 0..20u32 {
+            let flags: u8 = if idx.is_multiple_of(10) { 0x17 } else { 0x27 };
+            let data = ChannelData::Video {
+                timestamp: 10_000 + idx * 100,
+                data: Bytes::copy_from_slice(&[flags, 0x01, 0x00, 0x00, 0x00]),
+            };
+            assert!(producer.send(data).is_ok());
+        }
+
+        /*the player's end of the connection is kept open*/
+        let (stream, _player): (DuplexStream, DuplexStream) = tokio::io::duplex(1 << 20);
+        let webhook_config = RtmpWebhookConfig {
+            enabled: false,
+            publish_url: String::new(),
+            publish_done_url: String::new(),
+        };
+        let mut session = ServerSession::new(stream, event_producer, webhook_config);
+        session.app_name = app_name.clone();
+        session.stream_name = stream_name.clone();
+        session.state = ServerSessionState::Play;
+        session
+            .common
+            .subscribe_from_channels(app_name, stream_name, session.subscriber_id)
+            .await
+            .unwrap();
+
+        /*the whole cache at first*/
+        let timestamps = recv_timestamps(&mut session).await;
+        assert_eq!(timestamps.len(), 20);
+        assert_eq!(timestamps[19], 1900);
+
+        /*nothing while paused*/
+        session
+            .on_pause(&0.0, &1, &[Amf0ValueType::Boolean(true)])
+            .await
+            .unwrap();
+        assert!(session.common.is_paused());
+        assert!(recv_timestamps(&mut session).await.is_empty());
+
+        /*the unpaused player starts at the latest keyframe, after the frames it got*/
+        session
+            .on_pause(&0.0, &1, &[Amf0ValueType::Boolean(false)])
+            .await
+            .unwrap();
+        assert!(!session.common.is_paused());
+        let timestamps = recv_timestamps(&mut session).await;
+        assert_eq!(timestamps.len(), 10);
+        assert_eq!(timestamps[0], 1901);
+        assert_eq!(timestamps[9], 2801);
+
+        /*so does a seek*/
+        session.on_seek(&0.0).await.unwrap();
+        let timestamps = recv_timestamps(&mut session).await;
+        assert_eq!(timestamps.len(), 10);
+        assert_eq!(timestamps[0], 2802);
+        assert_eq!(timestamps[9], 3702);
+    }
+
+    #[tokio::test]
+    async fn test_wait_publish() {
+        let mut manager = ChannelsManager::new();
+        let publisher = PublisherInfo {
+            id: Uuid::new_v4(),
+            session_info: SessionInfo::new(None),
+        };
+        let _producer = manager
+            .publish(&String::from("live"), &String::from("published"), publisher)
+            .unwrap();
+        let (stream, player) = tokio::io::duplex(1 << 16);
+        let player = Arc::new(Mutex::new(BytesIO::new(player)));
+        let mut session = new_session(stream, &mut manager);
+        let event_producer = manager.get_channel_event_producer();
+        tokio::spawn(async move { manager.run().await });
+
+        /*a published stream is played at once, without a publish notify*/
+        play(&mut session, "published").await;
+        session.wait_publish().await.unwrap();
+        assert!(session.is_playing());
+        let status = read_player(&player).await;
+        assert!(status.contains("NetStream.Play.Start"));
+        assert!(!status.contains("NetStream.Play.PublishNotify"));
+
+        /*the player closing the stream ends the wait*/
+        session.close_play().await.unwrap();
+        play(&mut session, "waiting").await;
+        NetStreamWriter::new(Arc::clone(&player))
+            .write_close_stream(&0.0, &1.0)
+            .await
+            .unwrap();
+        timeout(Duration::from_secs(1), session.wait_publish())
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(!session.is_waiting_publish() && !session.is_playing());
+        read_player(&player).await;
+
+        /*the player is notified once the stream it waits for is published*/
+        play(&mut session, "waiting").await;
+        let (sender, receiver) = oneshot::channel();
+        let publish_event = ChannelEvent::Publish {
+            app_name: String::from("live"),
+            stream_name: String::from("waiting"),
+            info: PublisherInfo {
+                id: Uuid::new_v4(),
+                session_info: SessionInfo::new(None),
+            },
+            responder: sender,
+        };
+        assert!(event_producer.send(publish_event).is_ok());
+        let _producer = receiver.await.unwrap().unwrap();
+        timeout(Duration::from_secs(1), session.wait_publish())
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(session.is_playing());
+        assert!(read_player(&player)
+            .await
+            .contains("NetStream.Play.PublishNotify"));
+    }
+
+    #[tokio::test]
+    async fn test_publish_rejected() {
+        let mut manager = ChannelsManager::new();
+        manager.set_app_profile(
+            String::from("live"),
+            AppProfile {
+                max_publishers: Some(1),
+                ..Default::default()
+            },
+        );
+        let publisher = PublisherInfo {
+            id: Uuid::new_v4(),
+            session_info: SessionInfo::new(None),
+        };
+        let _producer = manager
+            .publish(&String::from("live"), &String::from("first"), publisher)
+            .unwrap();
+        let (stream, player) = tokio::io::duplex(1 << 16);
+        let player = Arc::new(Mutex::new(BytesIO::new(player)));
+        let mut session = new_session(stream, &mut manager);
+        tokio::spawn(async move { manager.run().await });
+
+        /*the encoder is told why instead of being disconnected*/
+        let mut values = vec![
+            Amf0ValueType::UTF8String(String::from("second")),
+            Amf0ValueType::UTF8String(String::from("live")),
+        ];
+        session.on_publish(&1.0, &1, &mut values).await.unwrap();
+        assert!(!session.is_publishing);
+        assert!(read_player(&player)
+            .await
+            .contains("NetStream.Publish.Denied"));
+    }
+
+    #[tokio::test]
+    async fn test_media_after_fcunpublish() {
+        let mut manager = ChannelsManager::new();
+        let (stream, _player) = tokio::io::duplex(1 << 16);
+        let mut session = new_session(stream, &mut manager);
+        tokio::spawn(async move { manager.run().await });
+
+        let mut values = vec![
+            Amf0ValueType::UTF8String(String::from("test")),
+            Amf0ValueType::UTF8String(String::from("live")),
+        ];
+        session.on_publish(&1.0, &1, &mut values).await.unwrap();
+        assert!(session.is_publishing);
+        session
+            .on_fcunpublish(&2.0, &[Amf0ValueType::UTF8String(String::from("test"))])
+            .await
+            .unwrap();
+        /*let the channels close the stream*/
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        /*the media which follows is dropped, the session goes on to deleteStream*/
+        let mut msg = RtmpMessageData::VideoData {
+            data: BytesMut::from(&[0x17, 0x01, 0x00, 0x00, 0x00][..]),
+        };
+        session.process_messages(&mut msg, &1, &40).await.unwrap();
+    }
+}
@@ -11,7 +11,7 @@ use {
     },
     xflv::{
         define,
-        define::{aac_packet_type, AvcCodecId, SoundFormat},
+        define::{aac_packet_type, AvcCodecId, AvcLevel, AvcProfile, SoundFormat},
         mpeg4_aac::Mpeg4Aac,
         mpeg4_avc::Mpeg4Avc,
     },
@@ -51,13 +51,29 @@ impl AvStatistics {
         audio_info.channels = codec_info.channels;
     }
 
-    pub async fn notify_video_codec_info(&mut self, codec_info: &Mpeg4Avc) {
+    /*the profile, level and resolution are only known for AVC, whose
+    decoder configuration record is parsed.*/
+    pub async fn notify_video_codec_info(
+        &mut self,
+        codec: AvcCodecId,
+        codec_info: Option<&Mpeg4Avc>,
+    ) {
         let video_info = &mut self.stream_statistics.lock().await.video;
-        video_info.codec = AvcCodecId::H264;
-        video_info.profile = define::u8_2_avc_profile(codec_info.profile);
-        video_info.level = define::u8_2_avc_level(codec_info.level);
-        video_info.height = codec_info.height;
-        video_info.width = codec_info.width;
+        video_info.codec = codec;
+        match codec_info {
+            Some(codec_info) => {
+                video_info.profile = define::u8_2_avc_profile(codec_info.profile);
+                video_info.level = define::u8_2_avc_level(codec_info.level);
+                video_info.height = codec_info.height;
+                video_info.width = codec_info.width;
+            }
+            None => {
+                video_info.profile = AvcProfile::UNKNOWN;
+                video_info.level = AvcLevel::UNKNOWN;
+                video_info.height = 0;
+                video_info.width = 0;
+            }
+        }
     }
 
     pub async fn notify_audio_statistics_info(&mut self, data_size: usize, aac_packet_type: u8) {
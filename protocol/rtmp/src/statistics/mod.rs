@@ -1,6 +1,7 @@
 pub mod avstatistics;
 
 use {
+    crate::session::{common::SessionInfo, define::SubscribeType},
     serde::Serialize,
     xflv::define::{AacProfile, AvcCodecId, AvcLevel, AvcProfile, SoundFormat},
 };
@@ -32,6 +33,38 @@ pub struct StreamStatistics {
     pub stream_name: String,
     pub video: VideoInfo,
     pub audio: AudioInfo,
+    pub publisher: Option<PublisherStatistics>,
+    pub subscribers: Vec<SubscriberStatistics>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PublisherStatistics {
+    pub id: String,
+    #[serde(flatten)]
+    pub session_info: SessionInfo,
+    pub bytes_received: u64,
+    #[serde(rename = "rtt(ms)")]
+    pub rtt: Option<u32>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SubscriberStatistics {
+    pub id: String,
+    pub sub_type: SubscribeType,
+    #[serde(flatten)]
+    pub session_info: SessionInfo,
+    /* The media bytes queued for the subscriber. */
+    pub bytes_sent: u64,
+    pub dropped_frames: DroppedFrames,
+    #[serde(rename = "rtt(ms)")]
+    pub rtt: Option<u32>,
+}
+
+/* The frames dropped because a subscriber did not keep up. */
+#[derive(Debug, Clone, Copy, Serialize, Default)]
+pub struct DroppedFrames {
+    pub video: u64,
+    pub audio: u64,
 }
 
 impl StreamStatistics {
@@ -0,0 +1,55 @@
+use {
+    failure::{Backtrace, Fail},
+    std::{fmt, io::Error},
+};
+
+#[derive(Debug, Fail)]
+pub enum TlsErrorValue {
+    #[fail(display = "io error: {}\n", _0)]
+    IOError(Error),
+    #[fail(display = "no certificate found in: {}\n", _0)]
+    NoCertificate(String),
+    #[fail(display = "no private key found in: {}\n", _0)]
+    NoPrivateKey(String),
+    #[fail(display = "tls error: {}\n", _0)]
+    RustlsError(rustls::Error),
+    #[fail(display = "invalid server name: {}\n", _0)]
+    InvalidServerName(String),
+}
+
+#[derive(Debug)]
+pub struct TlsError {
+    pub value: TlsErrorValue,
+}
+
+impl From<Error> for TlsError {
+    fn from(error: Error) -> Self {
+        TlsError {
+            value: TlsErrorValue::IOError(error),
+        }
+    }
+}
+
+impl From<rustls::Error> for TlsError {
+    fn from(error: rustls::Error) -> Self {
+        TlsError {
+            value: TlsErrorValue::RustlsError(error),
+        }
+    }
+}
+
+impl fmt::Display for TlsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&self.value, f)
+    }
+}
+
+impl Fail for TlsError {
+    fn cause(&self) -> Option<&dyn Fail> {
+        self.value.cause()
+    }
+
+    fn backtrace(&self) -> Option<&Backtrace> {
+        self.value.backtrace()
+    }
+}
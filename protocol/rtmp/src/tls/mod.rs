@@ -0,0 +1,69 @@
+pub mod errors;
+
+use {
+    errors::{TlsError, TlsErrorValue},
+    hyper_rustls::ConfigBuilderExt,
+    rustls::{Certificate, ClientConfig, PrivateKey, ServerConfig, ServerName},
+    std::{fs::File, io::BufReader, sync::Arc},
+    tokio::net::TcpStream,
+    tokio_rustls::{client, TlsAcceptor, TlsConnector},
+};
+
+/*Load the PEM encoded certificate chain and private key used by the rtmps listener.*/
+pub fn load_server_config(cert_path: &str, key_path: &str) -> Result<ServerConfig, TlsError> {
+    let mut cert_reader = BufReader::new(File::open(cert_path)?);
+    let certs: Vec<Certificate> = rustls_pemfile::certs(&mut cert_reader)?
+        .into_iter()
+        .map(Certificate)
+        .collect();
+    if certs.is_empty() {
+        return Err(TlsError {
+            value: TlsErrorValue::NoCertificate(String::from(cert_path)),
+        });
+    }
+
+    let mut key_reader = BufReader::new(File::open(key_path)?);
+    let key = loop {
+        match rustls_pemfile::read_one(&mut key_reader)? {
+            Some(rustls_pemfile::Item::RSAKey(key))
+            | Some(rustls_pemfile::Item::PKCS8Key(key))
+            | Some(rustls_pemfile::Item::ECKey(key)) => break PrivateKey(key),
+            Some(_) => {}
+            None => {
+                return Err(TlsError {
+                    value: TlsErrorValue::NoPrivateKey(String::from(key_path)),
+                });
+            }
+        }
+    };
+
+    let config = ServerConfig::builder()
+        .with_safe_defaults()
+        .with_no_client_auth()
+        .with_single_cert(certs, key)?;
+
+    Ok(config)
+}
+
+pub fn new_acceptor(config: ServerConfig) -> TlsAcceptor {
+    TlsAcceptor::from(Arc::new(config))
+}
+
+/*Wrap a connected TcpStream with TLS, the server certificate is
+verified against the platform's native roots.*/
+pub async fn connect(
+    stream: TcpStream,
+    domain: &str,
+) -> Result<client::TlsStream<TcpStream>, TlsError> {
+    let server_name = ServerName::try_from(domain).map_err(|_| TlsError {
+        value: TlsErrorValue::InvalidServerName(String::from(domain)),
+    })?;
+
+    let config = ClientConfig::builder()
+        .with_safe_defaults()
+        .with_native_roots()
+        .with_no_client_auth();
+    let connector = TlsConnector::from(Arc::new(config));
+
+    Ok(connector.connect(server_name, stream).await?)
+}
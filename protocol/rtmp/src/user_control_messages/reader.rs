@@ -31,6 +31,14 @@ impl EventMessagesReader {
                 self.read_stream_is_recorded()
             }
 
+            define::RTMP_EVENT_PING => {
+                self.read_ping_request()
+            }
+
+            define::RTMP_EVENT_PONG => {
+                self.read_ping_response()
+            }
+
             _ => {
                 Err(errors::EventMessagesError {
                     value: errors::EventMessagesErrorValue::UnknowEventMessageType,
@@ -69,4 +77,24 @@ impl EventMessagesReader {
             stream_id,
         })
     }
+
+    pub fn read_ping_request(
+        &mut self,
+    ) -> Result<message_define::RtmpMessageData, errors::EventMessagesError> {
+        let timestamp = self.reader.read_u32::<BigEndian>()?;
+
+        Ok(message_define::RtmpMessageData::PingRequest {
+            timestamp,
+        })
+    }
+
+    pub fn read_ping_response(
+        &mut self,
+    ) -> Result<message_define::RtmpMessageData, errors::EventMessagesError> {
+        let timestamp = self.reader.read_u32::<BigEndian>()?;
+
+        Ok(message_define::RtmpMessageData::PingResponse {
+            timestamp,
+        })
+    }
 }
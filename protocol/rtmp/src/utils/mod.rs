@@ -1 +1,2 @@
-pub mod print;
\ No newline at end of file
+pub mod print;
+pub mod query;
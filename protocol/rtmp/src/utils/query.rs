@@ -0,0 +1,94 @@
+use std::collections::HashMap;
+
+/*
+ Splits "mystream?token=abc&expires=123" into the clean name and its
+ query parameters. A name without a query yields an empty map.
+*/
+pub fn split_stream_name(raw: &str) -> (String, HashMap<String, String>) {
+    match raw.split_once('?') {
+        Some((name, query)) => (String::from(name), parse_query(query)),
+        None => (String::from(raw), HashMap::new()),
+    }
+}
+
+/* The query parameters of a tcUrl like rtmp://host/live?token=abc */
+pub fn parse_url_query(url: &str) -> HashMap<String, String> {
+    match url.split_once('?') {
+        Some((_, query)) => parse_query(query),
+        None => HashMap::new(),
+    }
+}
+
+pub fn parse_query(query: &str) -> HashMap<String, String> {
+    let mut params = HashMap::new();
+
+    for pair in query.split('&') {
+        if pair.is_empty() {
+            continue;
+        }
+        let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+        if key.is_empty() {
+            continue;
+        }
+        params.insert(percent_decode(key), percent_decode(value));
+    }
+
+    params
+}
+
+/* '+' is a space and invalid escapes are kept as they are. */
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+
+    let mut idx = 0;
+    while idx < bytes.len() {
+        match bytes[idx] {
+            b'+' => decoded.push(b' '),
+            /*from_str_radix alone would take a sign, as in "%+1"*/
+            b'%' => match bytes
+                .get(idx + 1..idx + 3)
+                .filter(|hex| hex.iter().all(u8::is_ascii_hexdigit))
+                .and_then(|hex| std::str::from_utf8(hex).ok())
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+            {
+                Some(byte) => {
+                    decoded.push(byte);
+                    idx += 2;
+                }
+                None => decoded.push(b'%'),
+            },
+            other => decoded.push(other),
+        }
+        idx += 1;
+    }
+
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_url_query, split_stream_name};
+
+    #[test]
+    fn test_split_stream_name() {
+        let (name, params) = split_stream_name("mystream?token=abc%3D&expires=123&flag&=x");
+        assert_eq!(name, "mystream");
+        assert_eq!(params.len(), 3);
+        assert_eq!(params["token"], "abc=");
+        assert_eq!(params["expires"], "123");
+        assert_eq!(params["flag"], "");
+
+        let (name, params) = split_stream_name("mystream");
+        assert_eq!(name, "mystream");
+        assert!(params.is_empty());
+
+        let params = parse_url_query("rtmp://localhost:1935/live?user=a+b&bad=%zz");
+        assert_eq!(params["user"], "a b");
+        assert_eq!(params["bad"], "%zz");
+
+        let params = parse_url_query("rtmp://localhost:1935/live?sign=%+1&minus=%-1");
+        assert_eq!(params["sign"], "% 1");
+        assert_eq!(params["minus"], "%-1");
+    }
+}
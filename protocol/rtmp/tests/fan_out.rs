@@ -0,0 +1,135 @@
+/*
+ The global allocator counts every allocation of this test binary, so the
+ fan-out test lives in its own file.
+*/
+use {
+    bytes::{Bytes, BytesMut},
+    rtmp::{
+        channels::{define::ChannelData, ChannelsManager},
+        session::{
+            common::{PublisherInfo, SessionInfo, SubscriberInfo},
+            define::SubscribeType,
+        },
+    },
+    std::{
+        alloc::{GlobalAlloc, Layout, System},
+        hint::black_box,
+        sync::atomic::{AtomicUsize, Ordering},
+        time::Duration,
+    },
+    uuid::Uuid,
+};
+
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+static ALLOCATED_BYTES: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        ALLOCATED_BYTES.fetch_add(layout.size(), Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+const SUBSCRIBERS: usize = 100;
+const FRAMES: usize = 20;
+const FRAME_SIZE: usize = 64 * 1024;
+
+#[tokio::test]
+async fn test_fan_out_allocations() {
+    let mut manager = ChannelsManager::new();
+    let app_name = String::from("live");
+    let stream_name = String::from("test");
+
+    let publisher = PublisherInfo {
+        id: Uuid::new_v4(),
+        session_info: SessionInfo::new(None),
+    };
+    let producer = manager.publish(&app_name, &stream_name, publisher).unwrap();
+    let mut consumers = Vec::new();
+    for _ in 0..SUBSCRIBERS {
+        let info = SubscriberInfo {
+            id: Uuid::new_v4(),
+            sub_type: SubscribeType::PlayerRtmp,
+            session_info: SessionInfo::new(None),
+            start_mode: None,
+        };
+        consumers.push(
+            manager
+                .subscribe(&app_name, &stream_name, info)
+                .await
+                .unwrap(),
+        );
+    }
+    /*let the transmitter register the subscribers before the frames arrive*/
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let frames: Vec<Bytes> = (0..FRAMES)
+        .map(|idx| {
+            let mut frame = vec![0; FRAME_SIZE];
+            /*an AVC key frame starts the GOP, inter frames follow*/
+            frame[0] = if idx == 0 { 0x17 } else { 0x27 };
+            frame[1] = 0x01;
+            Bytes::from(frame)
+        })
+        .collect();
+
+    let (allocations, allocated_bytes) = (
+        ALLOCATIONS.load(Ordering::Relaxed),
+        ALLOCATED_BYTES.load(Ordering::Relaxed),
+    );
+
+    for (idx, frame) in frames.iter().enumerate() {
+        let data = ChannelData::Video {
+            timestamp: idx as u32 * 40,
+            data: frame.clone(),
+        };
+        assert!(producer.send(data).is_ok());
+    }
+    for consumer in consumers.iter_mut() {
+        for frame in frames.iter() {
+            match consumer.recv().await {
+                Some(ChannelData::Video { data, .. }) => assert_eq!(data.as_ptr(), frame.as_ptr()),
+                _ => panic!("expected a video frame"),
+            }
+        }
+    }
+
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed) - allocations;
+    let allocated_bytes = ALLOCATED_BYTES.load(Ordering::Relaxed) - allocated_bytes;
+    /*less than a single frame for all the subscribers together, and fewer
+    allocations than delivered frames*/
+    assert!(allocated_bytes < FRAME_SIZE);
+    assert!(allocations < FRAMES * SUBSCRIBERS);
+
+    /*the baseline: frames carried as BytesMut were copied for each subscriber
+    when the transmitter cloned them*/
+    let frames: Vec<BytesMut> = frames
+        .iter()
+        .map(|frame| BytesMut::from(&frame[..]))
+        .collect();
+    let (baseline_allocations, baseline_allocated_bytes) = (
+        ALLOCATIONS.load(Ordering::Relaxed),
+        ALLOCATED_BYTES.load(Ordering::Relaxed),
+    );
+    for _ in 0..SUBSCRIBERS {
+        for frame in frames.iter() {
+            black_box(frame.clone());
+        }
+    }
+    let baseline_allocations = ALLOCATIONS.load(Ordering::Relaxed) - baseline_allocations;
+    let baseline_allocated_bytes =
+        ALLOCATED_BYTES.load(Ordering::Relaxed) - baseline_allocated_bytes;
+    assert_eq!(baseline_allocations, FRAMES * SUBSCRIBERS);
+    assert_eq!(baseline_allocated_bytes, FRAMES * SUBSCRIBERS * FRAME_SIZE);
+    assert!(allocations < baseline_allocations);
+}